@@ -1,7 +1,12 @@
 use std::collections::HashMap;
-use crate::{Piece, Position, piece::{PieceType, Color}, Move};
+use crate::{Piece, Position, piece::{PieceType, Color}, Move, zobrist::KEYS, GameResult, DrawReason, GamePhase};
+use crate::game_phase::{
+    KNIGHT_PHASE, BISHOP_PHASE, ROOK_PHASE, QUEEN_PHASE, TOTAL_PHASE,
+    ENDGAME_PHASE, OPENING_PHASE, OPENING_UNDEVELOPED_MINORS,
+};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CastlingRights {
     pub white_kingside: bool,
     pub white_queenside: bool,
@@ -20,24 +25,334 @@ impl Default for CastlingRights {
     }
 }
 
-#[derive(Debug, Clone)]
+// State a move destroys, kept so the move can be taken back
+struct Undo {
+    captured: Option<(Position, Piece)>,  // Captured piece and the square it stood on
+    en_passant: Option<Position>,
+}
+
+// An EPD operation: its opcode and the operands as written
+pub type EpdOperation = (String, String);
+
+// Why Board::validate considers a position impossible
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionError {
+    KingCount(Color),              // That side has no king, or more than one
+    PawnOnBackRank(Position),      // Pawns can never stand on rank 1 or 8
+    OpponentInCheck,               // The side that just moved left its king in check
+    CastlingRightsWithoutPieces,   // A castling right whose king or rook isn't on its home square
+    ImpossibleEnPassant(Position), // No pawn can just have double-pushed past this square
+}
+
+impl PositionError {
+    pub fn message(self) -> &'static str {
+        match self {
+            PositionError::KingCount(_) => "Each side must have exactly one king",
+            PositionError::PawnOnBackRank(_) => "Pawns can't stand on the first or last rank",
+            PositionError::OpponentInCheck => "The side not to move can't be in check",
+            PositionError::CastlingRightsWithoutPieces =>
+                "Castling rights need the king and rook on their starting squares",
+            PositionError::ImpossibleEnPassant(_) =>
+                "The en passant square needs a pawn that just moved two squares past it",
+        }
+    }
+}
+
+impl std::fmt::Display for PositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+#[derive(Debug)]
 pub struct Board {
     pieces: HashMap<Position, Piece>,
     current_turn: Color,
     castling_rights: CastlingRights,
     last_move: Option<Move>,
+    en_passant: Option<Position>,  // Square a pawn can capture onto en passant this move
+    move_history: Vec<Move>,  // Moves made through make_move, oldest first
+    position_history: Vec<u64>,  // Zobrist hash of the position before each of those moves
+    halfmove_clock: u32,  // Plies since the last capture or pawn move
+    last_irreversible_ply: usize,  // Length of move_history right after the last capture or pawn move
+    start_fullmove: u32,  // Fullmove number of the position the board was set up in
+}
+
+// Written out so that clone_from can reuse the target's allocations, for
+// callers that copy positions into one scratch board over and over
+impl Clone for Board {
+    fn clone(&self) -> Self {
+        Self {
+            pieces: self.pieces.clone(),
+            current_turn: self.current_turn,
+            castling_rights: self.castling_rights,
+            last_move: self.last_move,
+            en_passant: self.en_passant,
+            move_history: self.move_history.clone(),
+            position_history: self.position_history.clone(),
+            halfmove_clock: self.halfmove_clock,
+            last_irreversible_ply: self.last_irreversible_ply,
+            start_fullmove: self.start_fullmove,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.pieces.clone_from(&source.pieces);
+        self.current_turn = source.current_turn;
+        self.castling_rights = source.castling_rights;
+        self.last_move = source.last_move;
+        self.en_passant = source.en_passant;
+        self.move_history.clone_from(&source.move_history);
+        self.position_history.clone_from(&source.position_history);
+        self.halfmove_clock = source.halfmove_clock;
+        self.last_irreversible_ply = source.last_irreversible_ply;
+        self.start_fullmove = source.start_fullmove;
+    }
+}
+
+// Two boards are equal when they hold the same position: pieces, side to move,
+// castling rights and en passant square. How they got there (move history,
+// last move) is deliberately not compared.
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        self.pieces == other.pieces &&
+        self.current_turn == other.current_turn &&
+        self.castling_rights == other.castling_rights &&
+        self.en_passant == other.en_passant
+    }
+}
+
+impl Eq for Board {}
+
+// Consistent with PartialEq since the Zobrist hash covers exactly the compared state
+impl std::hash::Hash for Board {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_u64(self.zobrist_hash());
+    }
 }
 
 impl Board {
     pub fn new() -> Self {
-        let mut board = Self {
+        let mut board = Self::new_empty();
+        board.castling_rights = CastlingRights::default();
+        board.setup_initial_position();
+        board
+    }
+
+    // A board without any pieces, White to move and no castling rights, for
+    // setting a position up piece by piece with set_piece
+    pub fn new_empty() -> Self {
+        Self {
             pieces: HashMap::new(),
             current_turn: Color::White,
-            castling_rights: CastlingRights::default(),
+            castling_rights: CastlingRights {
+                white_kingside: false,
+                white_queenside: false,
+                black_kingside: false,
+                black_queenside: false,
+            },
             last_move: None,
-        };
-        board.setup_initial_position();
-        board
+            en_passant: None,
+            move_history: Vec::new(),
+            position_history: Vec::new(),
+            halfmove_clock: 0,
+            last_irreversible_ply: 0,
+            start_fullmove: 1,
+        }
+    }
+
+    // Loads a position from Forsyth-Edwards Notation. The halfmove clock and
+    // fullmove number are optional and default to 0 and 1.
+    pub fn from_fen(fen: &str) -> Result<Self, &'static str> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 || fields.len() > 6 {
+            return Err("FEN must have between 4 and 6 fields");
+        }
+
+        let mut board = Self::new_empty();
+
+        // Piece placement, rank 8 first
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err("FEN piece placement must have 8 ranks");
+        }
+        for (i, rank_str) in ranks.iter().enumerate() {
+            let rank = 8 - i as u8;
+            let mut file = 1;
+            for c in rank_str.chars() {
+                if let Some(skip) = c.to_digit(10) {
+                    if !(1..=8).contains(&skip) {
+                        return Err("Invalid empty square count in FEN");
+                    }
+                    file += skip as u8;
+                } else {
+                    let piece = Piece::from_fen_char(c).ok_or("Invalid piece letter in FEN")?;
+                    if file > 8 {
+                        return Err("Too many squares in FEN rank");
+                    }
+                    board.set_piece(Position { file, rank }, Some(piece));
+                    file += 1;
+                }
+            }
+            if file != 9 {
+                return Err("Each FEN rank must describe exactly 8 squares");
+            }
+        }
+
+        board.set_turn(match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err("Side to move must be 'w' or 'b'"),
+        });
+
+        // "-" for none, otherwise any subset of KQkq ("Kq" and the like). The
+        // letters may come in any order, but each only once.
+        if fields[2] != "-" {
+            for c in fields[2].chars() {
+                let right = match c {
+                    'K' => &mut board.castling_rights.white_kingside,
+                    'Q' => &mut board.castling_rights.white_queenside,
+                    'k' => &mut board.castling_rights.black_kingside,
+                    'q' => &mut board.castling_rights.black_queenside,
+                    _ => return Err("Invalid castling availability in FEN"),
+                };
+                if *right {
+                    return Err("Repeated castling letter in FEN");
+                }
+                *right = true;
+            }
+        }
+
+        if fields[3] != "-" {
+            let square = Position::from_algebraic(fields[3]).ok_or("Invalid en passant square in FEN")?;
+            let expected_rank = if board.current_turn == Color::White { 6 } else { 3 };
+            if square.rank != expected_rank {
+                return Err("En passant square is on the wrong rank");
+            }
+            board.en_passant = Some(square);
+        }
+
+        if let Some(field) = fields.get(4) {
+            board.halfmove_clock = field.parse().map_err(|_| "Invalid move counter in FEN")?;
+        }
+        if let Some(field) = fields.get(5) {
+            let fullmove: u32 = field.parse().map_err(|_| "Invalid move counter in FEN")?;
+            board.start_fullmove = fullmove.max(1);
+        }
+
+        board.validate().map_err(PositionError::message)?;
+        Ok(board)
+    }
+
+    // Loads a position from an Extended Position Description: FEN's first four
+    // fields followed by operations such as `bm Nf3; id "WAC.001";`. EPD has no
+    // move counters, so they start at 0 and 1 unless hmvc and fmvn operations
+    // give them. The operations come back in the order they appear.
+    pub fn from_epd(epd: &str) -> Result<(Self, Vec<EpdOperation>), &'static str> {
+        let mut rest = epd.trim_start();
+        let mut fields = Vec::new();
+        for _ in 0..4 {
+            let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if field.is_empty() {
+                return Err("EPD must start with four position fields");
+            }
+            fields.push(field);
+            rest = tail.trim_start();
+        }
+        let mut board = Self::from_fen(&fields.join(" "))?;
+
+        // Operations end at a semicolon, unless it's inside a quoted operand
+        let mut operations = Vec::new();
+        let mut start = 0;
+        let mut quoted = false;
+        for (i, c) in rest.char_indices().chain(std::iter::once((rest.len(), ';'))) {
+            match c {
+                '"' => quoted = !quoted,
+                ';' if !quoted => {
+                    let operation = rest[start..i].trim();
+                    if !operation.is_empty() {
+                        let (opcode, operands) = operation.split_once(char::is_whitespace).unwrap_or((operation, ""));
+                        operations.push((opcode.to_string(), operands.trim().to_string()));
+                    }
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        if quoted {
+            return Err("Unterminated string in EPD operation");
+        }
+
+        if let Some((_, operands)) = operations.iter().find(|(opcode, _)| opcode == "hmvc") {
+            board.halfmove_clock = operands.parse().map_err(|_| "Invalid hmvc operation in EPD")?;
+        }
+        if let Some((_, operands)) = operations.iter().find(|(opcode, _)| opcode == "fmvn") {
+            let fullmove: u32 = operands.parse().map_err(|_| "Invalid fmvn operation in EPD")?;
+            board.start_fullmove = fullmove.max(1);
+        }
+
+        Ok((board, operations))
+    }
+
+    // The four position fields of the FEN, which is all EPD records of the
+    // position; the move counters are left out.
+    pub fn to_epd(&self) -> String {
+        let mut placement = Vec::new();
+        for rank in (1..=8).rev() {
+            let mut row = String::new();
+            let mut empty = 0;
+            for file in 1..=8 {
+                match self.get_piece(Position { rank, file }) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            row.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        row.push(piece.to_fen_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                row.push_str(&empty.to_string());
+            }
+            placement.push(row);
+        }
+
+        let side = if self.current_turn == Color::White { "w" } else { "b" };
+
+        let mut castling = String::new();
+        if self.castling_rights.white_kingside { castling.push('K'); }
+        if self.castling_rights.white_queenside { castling.push('Q'); }
+        if self.castling_rights.black_kingside { castling.push('k'); }
+        if self.castling_rights.black_queenside { castling.push('q'); }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let en_passant = self.en_passant
+            .map(|square| square.to_algebraic())
+            .unwrap_or_else(|| "-".to_string());
+
+        format!("{} {} {} {}", placement.join("/"), side, castling, en_passant)
+    }
+
+    // FEN for the current position. The fullmove number counts on from the
+    // one the board was set up with, so a FEN loaded and written back out
+    // comes out the same.
+    pub fn to_fen(&self) -> String {
+        format!("{} {} {}", self.to_epd(), self.halfmove_clock, self.fullmove_number())
+    }
+
+    // Move number as FEN counts it: starts at 1 (or wherever the FEN the
+    // board came from said) and goes up after each Black move
+    pub fn fullmove_number(&self) -> u32 {
+        // Black moving first means the first move number only had one ply
+        let plies = self.move_history.len() as u32;
+        let started_with_black = (self.current_turn == Color::Black) != (plies % 2 == 1);
+        self.start_fullmove + (plies + started_with_black as u32) / 2
     }
 
     pub fn setup_initial_position(&mut self) {
@@ -71,11 +386,82 @@ impl Board {
         self.pieces.get(&pos)
     }
 
+    pub fn is_empty(&self, pos: Position) -> bool {
+        !self.pieces.contains_key(&pos)
+    }
+
+    // Occupied squares with their pieces, in rank/file order (a1, b1, ... h8)
+    pub fn occupied(&self) -> impl Iterator<Item = (Position, &Piece)> {
+        let mut squares: Vec<(Position, &Piece)> = self.pieces.iter()
+            .map(|(&pos, piece)| (pos, piece))
+            .collect();
+        squares.sort_by_key(|(pos, _)| (pos.rank, pos.file));
+        squares.into_iter()
+    }
+
     pub fn current_turn(&self) -> Color {
         self.current_turn
     }
 
+    // Raw mutators for editors, FEN loading and tests. None of them validate
+    // anything or touch the side to move or castling rights - the caller is
+    // responsible for leaving the board in a sensible state before playing on it
+    // (e.g. exactly one king each). Intermediate states can be arbitrary.
+
+    // Puts a piece on a square, replacing whatever was there. None empties the square.
+    pub fn set_piece(&mut self, pos: Position, piece: Option<Piece>) {
+        match piece {
+            Some(piece) => { self.pieces.insert(pos, piece); }
+            None => { self.pieces.remove(&pos); }
+        }
+    }
+
+    // Empties a square, returning the piece that stood there
+    pub fn remove_piece(&mut self, pos: Position) -> Option<Piece> {
+        self.pieces.remove(&pos)
+    }
+
+    // Removes every piece. The en passant square goes too since the pawn
+    // that allowed it is gone; turn and castling rights are left alone.
+    pub fn clear(&mut self) {
+        self.pieces.clear();
+        self.en_passant = None;
+    }
+
+    // Sets the side to move without making a move
+    pub fn set_turn(&mut self, color: Color) {
+        self.current_turn = color;
+    }
+
+    pub fn castling_rights(&self) -> CastlingRights {
+        self.castling_rights
+    }
+
+    // Only stores the flags; the kings and rooks aren't checked against them
+    pub fn set_castling_rights(&mut self, rights: CastlingRights) {
+        self.castling_rights = rights;
+    }
+
+    // Whether make_move would accept this move in the current position
+    pub fn is_legal(&self, chess_move: Move) -> bool {
+        self.with_move(chess_move).is_ok()
+    }
+
+    // The position after a move, leaving this one as it is
+    pub fn with_move(&self, chess_move: Move) -> Result<Board, &'static str> {
+        let mut after = self.clone();
+        after.make_move(chess_move)?;
+        Ok(after)
+    }
+
     pub fn make_move(&mut self, chess_move: Move) -> Result<(), &'static str> {
+        self.make_move_capturing(chess_move).map(|_| ())
+    }
+
+    // make_move that also hands back the piece the move took, with the square
+    // it stood on - for en passant that's beside the destination, not on it.
+    // Lets a display animate the piece off the board.
+    pub fn make_move_capturing(&mut self, chess_move: Move) -> Result<Option<(Position, Piece)>, &'static str> {
         // Clone the piece early to avoid borrow checker issues
         let piece = *self.pieces.get(&chess_move.from).ok_or("No piece at starting position")?;
 
@@ -87,8 +473,10 @@ impl Board {
         if piece.piece_type == PieceType::King {
             let file_diff = (chess_move.to.file as i8 - chess_move.from.file as i8).abs();
             if file_diff == 2 {
+                let hash_before = self.zobrist_hash();
                 self.handle_castling(chess_move)?;
-                return Ok(());
+                self.record_move(chess_move, hash_before, false);
+                return Ok(None);
             }
         }
 
@@ -97,50 +485,46 @@ impl Board {
             return Err("Invalid move for this piece");
         }
 
-        // Make a clone of the board and try the move
-        let mut temp_board = self.clone();
-        temp_board.make_move_without_validation(chess_move)?;
+        let hash_before = self.zobrist_hash();
+        let irreversible = piece.piece_type == PieceType::Pawn || !self.is_empty(chess_move.to);
 
-        // Check if the move puts/leaves the king in check
-        if temp_board.is_in_check(piece.color) {
+        // Play the move and look for checks on the result, taking it back if
+        // it leaves our king attacked. An en passant capture has already lifted
+        // the captured pawn by then - which is what catches the rank pin where
+        // both pawns leave the king's rank (e.g. K on a5, pawns b5 and c5,
+        // enemy rook on h5).
+        let undo = self.make_move_without_validation(chess_move)?;
+        if self.is_in_check(piece.color) {
+            self.unmake_move_without_validation(chess_move, undo);
             return Err("Move would leave king in check");
         }
 
         // Update castling rights
         self.update_castling_rights(&piece, chess_move);
+        self.record_move(chess_move, hash_before, irreversible);
 
-        // Actually make the move
-        self.make_move_without_validation(chess_move)?;
-        self.last_move = Some(chess_move);
-
-        Ok(())
+        Ok(undo.captured)
     }
 
-    fn make_move_without_validation(&mut self, chess_move: Move) -> Result<(), &'static str> {
-        let piece = self.pieces.remove(&chess_move.from).unwrap();
-
-        // Handle en passant capture
-        if piece.piece_type == PieceType::Pawn {
-            let file_diff = (chess_move.to.file as i8 - chess_move.from.file as i8).abs();
-            let is_diagonal = file_diff == 1;
-
-            if is_diagonal && !self.pieces.contains_key(&chess_move.to) {
-                // This might be an en passant capture
-                if let Some(last_move) = self.last_move {
-                    if last_move.from.file == chess_move.to.file {
-                        if let Some(last_piece) = self.pieces.get(&last_move.to) {
-                            if last_piece.piece_type == PieceType::Pawn {
-                                let last_rank_diff = (last_move.to.rank as i8 - last_move.from.rank as i8).abs();
-                                if last_rank_diff == 2 {
-                                    // Remove the captured pawn
-                                    self.pieces.remove(&last_move.to);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    // Bookkeeping for the draw rules once a move has been played.
+    // Captures and pawn moves can't be undone, so they restart the clock.
+    fn record_move(&mut self, chess_move: Move, hash_before: u64, irreversible: bool) {
+        self.last_move = Some(chess_move);
+        self.move_history.push(chess_move);
+        self.position_history.push(hash_before);
+        if irreversible {
+            self.halfmove_clock = 0;
+            self.last_irreversible_ply = self.move_history.len();
+        } else {
+            self.halfmove_clock += 1;
         }
+    }
+
+    // Moves the pieces and passes the turn. Fails without changing anything
+    // if the promotion is impossible; otherwise returns what
+    // unmake_move_without_validation needs to put the board back.
+    fn make_move_without_validation(&mut self, chess_move: Move) -> Result<Undo, &'static str> {
+        let piece = *self.pieces.get(&chess_move.from).ok_or("No piece at starting position")?;
 
         let final_piece = if let Some(promotion_type) = chess_move.promotion {
             if piece.piece_type != PieceType::Pawn {
@@ -155,13 +539,64 @@ impl Board {
             piece
         };
 
-        self.pieces.insert(chess_move.to, final_piece);
+        let undo = Undo {
+            captured: None,
+            en_passant: self.en_passant,
+        };
+        self.pieces.remove(&chess_move.from);
+
+        // Handle en passant capture
+        let mut captured = None;
+        if piece.piece_type == PieceType::Pawn {
+            let file_diff = (chess_move.to.file as i8 - chess_move.from.file as i8).abs();
+            let is_diagonal = file_diff == 1;
+
+            if is_diagonal && !self.pieces.contains_key(&chess_move.to) &&
+               self.en_passant == Some(chess_move.to) {
+                // The captured pawn sits beside the capturing pawn, not on the target square
+                let square = Position { file: chess_move.to.file, rank: chess_move.from.rank };
+                captured = self.pieces.remove(&square).map(|pawn| (square, pawn));
+            }
+        }
+
+        // A double pawn push leaves the skipped square open to en passant for one move
+        let rank_diff = (chess_move.to.rank as i8 - chess_move.from.rank as i8).abs();
+        self.en_passant = if piece.piece_type == PieceType::Pawn && rank_diff == 2 {
+            Some(Position {
+                file: chess_move.from.file,
+                rank: (chess_move.from.rank + chess_move.to.rank) / 2,
+            })
+        } else {
+            None
+        };
+
+        if let Some(target) = self.pieces.insert(chess_move.to, final_piece) {
+            captured = Some((chess_move.to, target));
+        }
         self.current_turn = match self.current_turn {
             Color::White => Color::Black,
             Color::Black => Color::White,
         };
 
-        Ok(())
+        Ok(Undo { captured, ..undo })
+    }
+
+    // Reverses make_move_without_validation, given the same move and its undo record
+    fn unmake_move_without_validation(&mut self, chess_move: Move, undo: Undo) {
+        if let Some(mut piece) = self.pieces.remove(&chess_move.to) {
+            if chess_move.promotion.is_some() {
+                piece = Piece::new(PieceType::Pawn, piece.color);
+            }
+            self.pieces.insert(chess_move.from, piece);
+        }
+        if let Some((square, piece)) = undo.captured {
+            self.pieces.insert(square, piece);
+        }
+        self.en_passant = undo.en_passant;
+        self.current_turn = match self.current_turn {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        };
     }
 
     fn handle_castling(&mut self, chess_move: Move) -> Result<(), &'static str> {
@@ -181,32 +616,31 @@ impl Board {
             return Err("Castling is not allowed");
         }
 
-        // Check if path is clear and not under attack
-        let path = if is_kingside { 
-            vec![Position::new(5, rank).unwrap(), Position::new(6, rank).unwrap(), Position::new(7, rank).unwrap()]
+        // The squares between king and rook must be empty, and the king may
+        // not castle out of, through or into check
+        let (between, king_path): (&[u8], &[u8]) = if is_kingside {
+            (&[6, 7], &[5, 6, 7])
         } else {
-            vec![Position::new(3, rank).unwrap(), Position::new(4, rank).unwrap()]
+            (&[2, 3, 4], &[5, 4, 3])
         };
-
-        for pos in &path {
-            if self.pieces.contains_key(pos) {
-                return Err("Path is not clear for castling");
-            }
-            if self.is_position_under_attack(*pos, king.color) {
-                return Err("Cannot castle through check");
-            }
+        if between.iter().any(|&file| self.pieces.contains_key(&Position { rank, file })) {
+            return Err("Path is not clear for castling");
         }
+        if king_path.iter().any(|&file| self.is_position_under_attack(Position { rank, file }, king.color)) {
+            return Err("Cannot castle through check");
+        }
+
+        let rook_from = Position::from_file_rank(if is_kingside { 8 } else { 1 }, rank).unwrap();
+        let rook_to = Position::from_file_rank(if is_kingside { 6 } else { 4 }, rank).unwrap();
+        let rook = match self.pieces.get(&rook_from) {
+            Some(&piece) if piece == Piece::new(PieceType::Rook, king.color) => piece,
+            _ => return Err("No rook found for castling"),
+        };
 
-        // Move the king
+        // Move the king and the rook
         self.pieces.remove(&chess_move.from);
         self.pieces.insert(chess_move.to, king);
-
-        // Move the rook
-        let rook_from = Position::new(if is_kingside { 8 } else { 1 }, rank).unwrap();
-        let rook_to = Position::new(if is_kingside { 6 } else { 4 }, rank).unwrap();
-        
-        // Get and remove the rook
-        let rook = self.pieces.remove(&rook_from).ok_or("No rook found for castling")?;
+        self.pieces.remove(&rook_from);
         self.pieces.insert(rook_to, rook);
 
         // Update castling rights
@@ -217,6 +651,7 @@ impl Board {
             self.castling_rights.black_kingside = false;
             self.castling_rights.black_queenside = false;
         }
+        self.en_passant = None;
 
         self.current_turn = match self.current_turn {
             Color::White => Color::Black,
@@ -255,6 +690,15 @@ impl Board {
             }
             _ => {}
         }
+
+        // Landing on a rook's home corner captures that rook, and its right goes with it
+        match (chess_move.to.rank, chess_move.to.file) {
+            (1, 1) => self.castling_rights.white_queenside = false,
+            (1, 8) => self.castling_rights.white_kingside = false,
+            (8, 1) => self.castling_rights.black_queenside = false,
+            (8, 8) => self.castling_rights.black_kingside = false,
+            _ => {}
+        }
     }
 
     pub fn is_in_check(&self, color: Color) -> bool {
@@ -264,7 +708,79 @@ impl Board {
             .map(|(pos, _)| *pos)
             .unwrap();
 
-        self.is_position_under_attack(king_pos, color)
+        // Attack geometry only; no need to validate a full move onto the king
+        let enemy = match color {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        };
+        self.is_attacked_by(king_pos, enemy)
+    }
+
+    // Whether the side to move is in check
+    pub fn in_check(&self) -> bool {
+        self.is_in_check(self.current_turn)
+    }
+
+    // Squares of the enemy pieces giving check to `color`'s king. Two entries
+    // means double check, where only a king move can help.
+    pub fn checkers(&self, color: Color) -> Vec<Position> {
+        let king_pos = match self.pieces.iter()
+            .find(|(_, piece)| piece.piece_type == PieceType::King && piece.color == color) {
+            Some((&pos, _)) => pos,
+            None => return Vec::new(),
+        };
+
+        self.pieces.iter()
+            .filter(|(&from, piece)| {
+                piece.color != color && Move::new(from, king_pos).is_attack(piece, self)
+            })
+            .map(|(&from, _)| from)
+            .collect()
+    }
+
+    // Squares of the `color` pieces attacking `pos`, whatever stands on it.
+    // With `pos`'s own color this lists its defenders.
+    pub fn attackers(&self, pos: Position, color: Color) -> Vec<Position> {
+        self.pieces.iter()
+            .filter(|(&from, piece)| piece.color == color && Move::new(from, pos).is_attack(piece, self))
+            .map(|(&from, _)| from)
+            .collect()
+    }
+
+    // A piece is hanging when the opponent can win material by taking it:
+    // it's attacked and either undefended or attacked by something cheaper
+    pub fn is_hanging(&self, pos: Position) -> bool {
+        let piece = match self.pieces.get(&pos) {
+            Some(piece) => piece,
+            None => return false,
+        };
+        let enemy = match piece.color {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        };
+
+        let cheapest_attacker = self.attackers(pos, enemy).iter()
+            .filter_map(|from| self.pieces.get(from))
+            .map(|attacker| Self::exchange_value(attacker.piece_type))
+            .min();
+        match cheapest_attacker {
+            None => false,
+            Some(attacker_value) => {
+                self.attackers(pos, piece.color).is_empty() ||
+                    attacker_value < Self::exchange_value(piece.piece_type)
+            }
+        }
+    }
+
+    // Material values for judging exchanges; the king can never be traded
+    fn exchange_value(piece_type: PieceType) -> i32 {
+        match piece_type {
+            PieceType::Pawn => 1,
+            PieceType::Knight | PieceType::Bishop => 3,
+            PieceType::Rook => 5,
+            PieceType::Queen => 9,
+            PieceType::King => 100,
+        }
     }
 
     pub fn is_position_under_attack(&self, pos: Position, defending_color: Color) -> bool {
@@ -282,52 +798,128 @@ impl Board {
         false
     }
 
-    pub fn is_checkmate(&self) -> bool {
-        if !self.is_in_check(self.current_turn) {
-            return false;
+    // Every square attacked by at least one piece of the given color,
+    // including squares occupied by its own pieces (i.e. defended ones)
+    pub fn controlled_squares(&self, color: Color) -> Vec<Position> {
+        let mut squares = Vec::new();
+        for rank in 1..=8 {
+            for file in 1..=8 {
+                let target = Position { rank, file };
+                let attacked = self.pieces.iter().any(|(&from, piece)| {
+                    piece.color == color && Move::new(from, target).is_attack(piece, self)
+                });
+                if attacked {
+                    squares.push(target);
+                }
+            }
         }
+        squares
+    }
 
-        // Check if any move can get out of check
-        for (&from, piece) in self.pieces.iter() {
-            if piece.color != self.current_turn {
-                continue;
-            }
+    // How many of the given squares `by` attacks, e.g. around a king; a
+    // cheap stand-in for generating moves when only coverage matters
+    pub fn attack_count(&self, squares: &[Position], by: Color) -> u32 {
+        squares.iter().filter(|&&pos| self.is_attacked_by(pos, by)).count() as u32
+    }
 
-            for rank in 1..=8 {
-                for file in 1..=8 {
-                    let to = Position::new(file, rank).unwrap();
-                    let chess_move = Move::new(from, to);
-                    
-                    // Try the move on a cloned board
-                    let mut temp_board = self.clone();
-                    if chess_move.is_valid(&temp_board) {
-                        if temp_board.make_move_without_validation(chess_move).is_ok() {
-                            if !temp_board.is_in_check(self.current_turn) {
-                                return false;
-                            }
-                        }
-                    }
-                }
-            }
+    pub fn is_checkmate(&self) -> bool {
+        if !self.in_check() {
+            return false;
         }
 
-        true
+        // No legal move gets out of it
+        !self.legal_move_exists()
     }
 
     pub fn is_position_valid(&self, pos: Position) -> bool {
         pos.file >= 1 && pos.file <= 8 && pos.rank >= 1 && pos.rank <= 8
     }
 
-    pub fn get_all_pieces(&self) -> &HashMap<Position, Piece> {
+    // Every piece by square, e.g. for comparing against what's on screen
+    pub fn piece_map(&self) -> &HashMap<Position, Piece> {
         &self.pieces
     }
 
+    // Pieces of both colours on the board, kings included
+    pub fn piece_count(&self) -> usize {
+        self.pieces.len()
+    }
+
+    // Whether the position could arise in a game: one king each, no pawns on
+    // the back ranks, the side that just moved not in check, and castling
+    // rights only where the king and rook are still at home. Anything else
+    // would have the search and move generation working on nonsense.
+    pub fn validate(&self) -> Result<(), PositionError> {
+        for color in [Color::White, Color::Black] {
+            let kings = self.pieces.values()
+                .filter(|p| p.piece_type == PieceType::King && p.color == color)
+                .count();
+            if kings != 1 {
+                return Err(PositionError::KingCount(color));
+            }
+        }
+
+        if let Some((&pos, _)) = self.pieces.iter()
+            .find(|(pos, p)| p.piece_type == PieceType::Pawn && (pos.rank == 1 || pos.rank == 8)) {
+            return Err(PositionError::PawnOnBackRank(pos));
+        }
+
+        let waiting_side = match self.current_turn {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        };
+        if self.is_in_check(waiting_side) {
+            return Err(PositionError::OpponentInCheck);
+        }
+
+        let at_home = |piece_type: PieceType, color: Color, file: u8| {
+            let rank = if color == Color::White { 1 } else { 8 };
+            self.pieces.get(&Position { rank, file })
+                .is_some_and(|p| p.piece_type == piece_type && p.color == color)
+        };
+        let rights = [
+            (self.castling_rights.white_kingside, Color::White, 8),
+            (self.castling_rights.white_queenside, Color::White, 1),
+            (self.castling_rights.black_kingside, Color::Black, 8),
+            (self.castling_rights.black_queenside, Color::Black, 1),
+        ];
+        for (allowed, color, rook_file) in rights {
+            if allowed && !(at_home(PieceType::King, color, 5) && at_home(PieceType::Rook, color, rook_file)) {
+                return Err(PositionError::CastlingRightsWithoutPieces);
+            }
+        }
+
+        // The pawn that just double-pushed past the en passant square stands in
+        // front of it, and both squares it crossed are empty
+        if let Some(square) = self.en_passant {
+            let (rank, pawn_rank, origin_rank) = match waiting_side {
+                Color::Black => (6, 5, 7),
+                Color::White => (3, 4, 2),
+            };
+            let pawn_there = self.pieces.get(&Position { rank: pawn_rank, file: square.file })
+                .is_some_and(|p| *p == Piece::new(PieceType::Pawn, waiting_side));
+            let crossed_empty = self.is_empty(square) &&
+                self.is_empty(Position { rank: origin_rank, file: square.file });
+            if square.rank != rank || !pawn_there || !crossed_empty {
+                return Err(PositionError::ImpossibleEnPassant(square));
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn get_valid_moves(&self, pos: Position) -> Vec<Move> {
         let mut valid_moves = Vec::new();
-        
+        self.push_moves_from(pos, &mut valid_moves, |_| true);
+        valid_moves
+    }
+
+    // The moves get_valid_moves lists for the piece on `pos`, appended to
+    // `buf` when `keep` accepts them
+    fn push_moves_from(&self, pos: Position, buf: &mut Vec<Move>, keep: impl Fn(Move) -> bool) {
         if let Some(piece) = self.get_piece(pos) {
             if piece.color != self.current_turn {
-                return valid_moves;
+                return;
             }
 
             // Generate all possible positions
@@ -335,8 +927,8 @@ impl Board {
                 for file in 1..=8 {
                     let target_pos = Position { file, rank };
                     let chess_move = Move::new(pos, target_pos);
-                    if chess_move.is_valid(self) {
-                        valid_moves.push(chess_move);
+                    if chess_move.is_valid(self) && keep(chess_move) {
+                        buf.push(chess_move);
                     }
 
                     // Check for pawn promotion
@@ -345,8 +937,8 @@ impl Board {
                            (piece.color == Color::Black && rank == 1) {
                             for promotion_type in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight] {
                                 let promotion_move = Move::with_promotion(pos, target_pos, promotion_type);
-                                if promotion_move.is_valid(self) {
-                                    valid_moves.push(promotion_move);
+                                if promotion_move.is_valid(self) && keep(promotion_move) {
+                                    buf.push(promotion_move);
                                 }
                             }
                         }
@@ -354,46 +946,466 @@ impl Board {
                 }
             }
         }
+    }
 
-        valid_moves
+    // Every move the side to move's pieces can make by their movement rules,
+    // in rank/file order of the moving piece. Moves that leave the king in
+    // check are included; make_move rejects those.
+    pub fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        self.generate_moves_into(&mut moves);
+        moves
     }
 
-    pub fn last_move(&self) -> Option<Move> {
-        self.last_move
+    // pseudo_legal_moves appended to `buf` rather than returned in a new Vec,
+    // so a caller generating moves over and over (the search, at every node)
+    // can keep reusing one list's capacity
+    pub fn generate_moves_into(&self, buf: &mut Vec<Move>) {
+        self.generate_into(buf, |_| true);
     }
 
-    pub fn is_stalemate(&self) -> bool {
-        if self.is_in_check(self.current_turn) {
-            return false;
+    // The captures among those, en passant and capturing promotions included,
+    // in the same order
+    pub fn generate_captures_into(&self, buf: &mut Vec<Move>) {
+        self.generate_into(buf, |chess_move| chess_move.is_capture(self));
+    }
+
+    fn generate_into(&self, buf: &mut Vec<Move>, keep: impl Fn(Move) -> bool) {
+        for from in (1..=8).flat_map(|rank| (1..=8).map(move |file| Position { rank, file })) {
+            let is_pawn = match self.pieces.get(&from) {
+                Some(piece) if piece.color == self.current_turn => piece.piece_type == PieceType::Pawn,
+                _ => continue,
+            };
+
+            self.push_moves_from(from, buf, |chess_move| {
+                !Self::is_bare_promotion(is_pawn, chess_move) && keep(chess_move)
+            });
         }
+    }
 
-        // Check if any legal move exists
-        for (&from, piece) in self.pieces.iter() {
-            if piece.color != self.current_turn {
-                continue;
+    // get_valid_moves also lists a pawn's bare (unpromoted) push to the last
+    // rank, which isn't a real move
+    fn is_bare_promotion(is_pawn: bool, chess_move: Move) -> bool {
+        is_pawn && chess_move.promotion.is_none() &&
+            (chess_move.to.rank == 1 || chess_move.to.rank == 8)
+    }
+
+    // Legal moves for the piece on `pos` whichever side is to move, as if it
+    // were that piece's turn. Meant for analysis displays; play should stick
+    // to legal_moves. An en passant square belongs to the side to move, so
+    // the other side's pawns never get one here.
+    pub fn legal_moves_from(&self, pos: Position) -> Vec<Move> {
+        let color = match self.pieces.get(&pos) {
+            Some(piece) => piece.color,
+            None => return Vec::new(),
+        };
+        let moves = if color == self.current_turn {
+            self.legal_moves()
+        } else {
+            let mut turned = self.clone();
+            turned.set_turn(color);
+            turned.en_passant = None;
+            turned.legal_moves()
+        };
+        moves.into_iter().filter(|m| m.from == pos).collect()
+    }
+
+    // All legal moves for the side to move: the pseudo-legal moves that keep
+    // the king safe. Pins and checks are worked out once up front, so only king
+    // moves and en passant need a look at the resulting position.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let keeps_king_safe = match self.king_safety_filter() {
+            Some(filter) => filter,
+            None => return Vec::new(),
+        };
+        let mut moves = self.pseudo_legal_moves();
+        moves.retain(|&chess_move| keeps_king_safe(chess_move));
+        moves
+    }
+
+    // Whether the side to move has any legal move at all. Stops at the first
+    // one found, starting with the king, so the usual "not mated" answer comes
+    // without generating the whole list - is_checkmate and is_stalemate (and
+    // the search) only need the yes/no.
+    pub fn legal_move_exists(&self) -> bool {
+        let keeps_king_safe = match self.king_safety_filter() {
+            Some(filter) => filter,
+            None => return false,
+        };
+        let mut squares: Vec<(Position, bool)> = self.pieces.iter()
+            .filter(|(_, piece)| piece.color == self.current_turn)
+            .map(|(&pos, piece)| (pos, piece.piece_type == PieceType::Pawn))
+            .collect();
+        squares.sort_by_key(|&(pos, _)| self.pieces[&pos].piece_type != PieceType::King);
+
+        squares.into_iter().any(|(from, is_pawn)| {
+            self.get_valid_moves(from).into_iter().any(|chess_move| {
+                !Self::is_bare_promotion(is_pawn, chess_move) && keeps_king_safe(chess_move)
+            })
+        })
+    }
+
+    // The check legal_moves applies to each pseudo-legal move, with the pins
+    // and checkers already worked out. None when the side to move has no king.
+    fn king_safety_filter(&self) -> Option<impl Fn(Move) -> bool + '_> {
+        let us = self.current_turn;
+        let them = match us {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        };
+        let king_pos = self.pieces.iter()
+            .find(|(_, p)| p.piece_type == PieceType::King && p.color == us)
+            .map(|(&pos, _)| pos)?;
+
+        let checkers = self.checkers(us);
+        let pins = self.pinned_pieces(king_pos, us);
+
+        let is_slider = |pos: &Position| self.pieces.get(pos).is_some_and(|p| {
+            matches!(p.piece_type, PieceType::Bishop | PieceType::Rook | PieceType::Queen)
+        });
+
+        // With a single checker, other pieces must capture it or step in between
+        let check_mask: Option<Vec<Position>> = match checkers.as_slice() {
+            [] => None,
+            [checker] => {
+                let mut squares = vec![*checker];
+                if is_slider(checker) {
+                    squares.extend(Self::squares_between(king_pos, *checker));
+                }
+                Some(squares)
             }
+            _ => Some(Vec::new()),  // Double check: only the king can move
+        };
 
-            for rank in 1..=8 {
-                for file in 1..=8 {
-                    let to = Position::new(file, rank).unwrap();
-                    let chess_move = Move::new(from, to);
-                    
-                    // Try the move on a cloned board
-                    let mut temp_board = self.clone();
-                    if chess_move.is_valid(&temp_board) {
-                        if temp_board.make_move_without_validation(chess_move).is_ok() {
-                            if !temp_board.is_in_check(self.current_turn) {
-                                return false;
-                            }
+        // The king hides the square behind it from a slider checking it, so
+        // is_attacked_by would let it retreat along the slider's line
+        let behind_king: Vec<Position> = checkers.iter()
+            .filter(|checker| is_slider(checker))
+            .filter_map(|&checker| {
+                let (rank_step, file_step) = Self::direction(checker, king_pos)?;
+                let rank = king_pos.rank as i8 + rank_step;
+                let file = king_pos.file as i8 + file_step;
+                ((1..=8).contains(&rank) && (1..=8).contains(&file))
+                    .then_some(Position { rank: rank as u8, file: file as u8 })
+            })
+            .collect();
+
+        Some(move |chess_move: Move| {
+            let piece_type = self.pieces[&chess_move.from].piece_type;
+            if piece_type == PieceType::King {
+                !behind_king.contains(&chess_move.to) && !self.is_attacked_by(chess_move.to, them)
+            } else if piece_type == PieceType::Pawn && chess_move.from.file != chess_move.to.file &&
+                      !self.pieces.contains_key(&chess_move.to) {
+                // En passant removes two pieces from a rank, which pin
+                // detection doesn't see - just try it
+                self.is_legal(chess_move)
+            } else {
+                let blocks_check = check_mask.as_ref()
+                    .is_none_or(|mask| mask.contains(&chess_move.to));
+                let keeps_pin = match pins.get(&chess_move.from) {
+                    Some(&direction) => Self::direction(king_pos, chess_move.to) == Some(direction),
+                    None => true,
+                };
+                blocks_check && keeps_pin
+            }
+        })
+    }
+
+    // Pieces of `color` pinned to its king, mapped to the direction of the pin
+    // (as a rank/file step away from the king)
+    fn pinned_pieces(&self, king_pos: Position, color: Color) -> HashMap<Position, (i8, i8)> {
+        let mut pins = HashMap::new();
+        for direction in [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)] {
+            let diagonal = direction.0 != 0 && direction.1 != 0;
+            let mut candidate = None;
+            let mut rank = king_pos.rank as i8 + direction.0;
+            let mut file = king_pos.file as i8 + direction.1;
+
+            while (1..=8).contains(&rank) && (1..=8).contains(&file) {
+                let pos = Position { rank: rank as u8, file: file as u8 };
+                if let Some(piece) = self.pieces.get(&pos) {
+                    if piece.color == color {
+                        if candidate.is_some() {
+                            break;  // Two of our own pieces in a row - nothing is pinned
                         }
+                        candidate = Some(pos);
+                    } else {
+                        let pins_along = match piece.piece_type {
+                            PieceType::Queen => true,
+                            PieceType::Bishop => diagonal,
+                            PieceType::Rook => !diagonal,
+                            _ => false,
+                        };
+                        if let (true, Some(pinned)) = (pins_along, candidate) {
+                            pins.insert(pinned, direction);
+                        }
+                        break;
                     }
                 }
+                rank += direction.0;
+                file += direction.1;
             }
         }
+        pins
+    }
+
+    // Whether any piece of `attacker` attacks the square, whatever stands on it
+    fn is_attacked_by(&self, pos: Position, attacker: Color) -> bool {
+        self.pieces.iter().any(|(&from, piece)| {
+            piece.color == attacker && Move::new(from, pos).is_attack(piece, self)
+        })
+    }
 
+    // Unit rank/file step from one square towards another, if they share a line
+    fn direction(from: Position, to: Position) -> Option<(i8, i8)> {
+        let rank_diff = to.rank as i8 - from.rank as i8;
+        let file_diff = to.file as i8 - from.file as i8;
+        if (rank_diff, file_diff) == (0, 0) {
+            return None;
+        }
+        if rank_diff == 0 || file_diff == 0 || rank_diff.abs() == file_diff.abs() {
+            Some((rank_diff.signum(), file_diff.signum()))
+        } else {
+            None
+        }
+    }
+
+    // Squares strictly between two squares on the same line
+    fn squares_between(from: Position, to: Position) -> Vec<Position> {
+        let mut squares = Vec::new();
+        if let Some((rank_step, file_step)) = Self::direction(from, to) {
+            let mut rank = from.rank as i8 + rank_step;
+            let mut file = from.file as i8 + file_step;
+            while (rank as u8, file as u8) != (to.rank, to.file) {
+                squares.push(Position { rank: rank as u8, file: file as u8 });
+                rank += rank_step;
+                file += file_step;
+            }
+        }
+        squares
+    }
+
+    // A position is quiet when the side to move is not in check and has no
+    // legal capture or promotion available
+    pub fn is_quiet(&self) -> bool {
+        if self.in_check() {
+            return false;
+        }
+
+        for (&pos, piece) in self.pieces.iter() {
+            if piece.color != self.current_turn {
+                continue;
+            }
+            for chess_move in self.get_valid_moves(pos) {
+                let is_capture = self.pieces.contains_key(&chess_move.to) ||
+                    (piece.piece_type == PieceType::Pawn && self.en_passant == Some(chess_move.to));
+                if !is_capture && chess_move.promotion.is_none() {
+                    continue;
+                }
+                // Only now pay for the legality check
+                if self.is_legal(chess_move) {
+                    return false;
+                }
+            }
+        }
         true
     }
 
+    pub fn last_move(&self) -> Option<Move> {
+        self.last_move
+    }
+
+    pub fn move_history(&self) -> &[Move] {
+        &self.move_history
+    }
+
+    // The most recent n moves, oldest first. Returns the whole history if
+    // fewer than n moves have been played.
+    pub fn last_n_moves(&self, n: usize) -> &[Move] {
+        let start = self.move_history.len().saturating_sub(n);
+        &self.move_history[start..]
+    }
+
+    // Target square for an en passant capture by the side to move, if any
+    pub fn en_passant_square(&self) -> Option<Position> {
+        self.en_passant
+    }
+
+    // Zobrist hash of the position: pieces, side to move, castling rights and
+    // the en passant file
+    pub fn zobrist_hash(&self) -> u64 {
+        let mut hash = 0;
+
+        for (&pos, piece) in self.pieces.iter() {
+            hash ^= KEYS.piece(piece, pos);
+        }
+
+        if self.current_turn == Color::Black {
+            hash ^= KEYS.black_to_move();
+        }
+
+        let rights = [
+            self.castling_rights.white_kingside,
+            self.castling_rights.white_queenside,
+            self.castling_rights.black_kingside,
+            self.castling_rights.black_queenside,
+        ];
+        for (index, &allowed) in rights.iter().enumerate() {
+            if allowed {
+                hash ^= KEYS.castling(index);
+            }
+        }
+
+        if let Some(square) = self.en_passant {
+            hash ^= KEYS.en_passant_file(square.file);
+        }
+
+        hash
+    }
+
+    pub fn is_stalemate(&self) -> bool {
+        if self.in_check() {
+            return false;
+        }
+
+        // Not in check, but nothing to play
+        !self.legal_move_exists()
+    }
+
+    // Game is drawn without any further moves being possible to change it
+    pub fn is_draw(&self) -> bool {
+        self.is_stalemate() || self.has_insufficient_material() || self.is_seventy_five_move_draw()
+    }
+
+    // Whether play has ended: mate or one of the draws above. Draws that
+    // still have to be claimed (see can_claim_draw) don't end the game.
+    pub fn is_game_over(&self) -> bool {
+        self.is_checkmate() || self.is_draw()
+    }
+
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    // Plies played on this board since the last capture or pawn move, or since
+    // it was set up if there hasn't been one. Unlike halfmove_clock this
+    // doesn't include the count a FEN started with, so it always fits in the
+    // move history.
+    pub fn plies_since_irreversible(&self) -> u32 {
+        (self.move_history.len() - self.last_irreversible_ply) as u32
+    }
+
+    // How often the current position has occurred, counting this time.
+    // Nothing before the last capture or pawn move can come back.
+    pub fn repetition_count(&self) -> u8 {
+        let hash = self.zobrist_hash();
+        let reachable = &self.position_history[self.last_irreversible_ply..];
+        let earlier = reachable.iter().filter(|&&h| h == hash).count();
+        (1 + earlier).min(u8::MAX as usize) as u8
+    }
+
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.repetition_count() >= 3
+    }
+
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100
+    }
+
+    // After 75 moves each without a capture or pawn move the game is drawn
+    // whether or not anyone claims it
+    pub fn is_seventy_five_move_draw(&self) -> bool {
+        self.halfmove_clock >= 150
+    }
+
+    // A draw the player to move may claim but doesn't have to. The automatic
+    // draws in is_draw end the game by themselves and aren't reported here.
+    pub fn can_claim_draw(&self) -> Option<DrawReason> {
+        if self.is_threefold_repetition() {
+            Some(DrawReason::ThreefoldRepetition)
+        } else if self.is_fifty_move_draw() {
+            Some(DrawReason::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
+    // Result of the game if it is over, None while it's still being played
+    pub fn result(&self) -> Option<GameResult> {
+        if self.is_checkmate() {
+            let winner = match self.current_turn {
+                Color::White => Color::Black,
+                Color::Black => Color::White,
+            };
+            return Some(GameResult::win_for(winner));
+        }
+        if self.is_stalemate() {
+            return Some(GameResult::Draw(DrawReason::Stalemate));
+        }
+        if self.has_insufficient_material() {
+            return Some(GameResult::Draw(DrawReason::InsufficientMaterial));
+        }
+        if self.is_seventy_five_move_draw() {
+            return Some(GameResult::Draw(DrawReason::SeventyFiveMoveRule));
+        }
+        None
+    }
+
+    // Non-pawn material left, weighted knight/bishop 1, rook 2, queen 4.
+    // Starts at 24 and falls towards 0 as pieces come off; more than 24 is
+    // only possible after promotions, so it's capped there.
+    pub fn phase_material(&self) -> u32 {
+        let material: u32 = self.pieces.values()
+            .map(|piece| match piece.piece_type {
+                PieceType::Knight => KNIGHT_PHASE,
+                PieceType::Bishop => BISHOP_PHASE,
+                PieceType::Rook => ROOK_PHASE,
+                PieceType::Queen => QUEEN_PHASE,
+                PieceType::Pawn | PieceType::King => 0,
+            })
+            .sum();
+        material.min(TOTAL_PHASE)
+    }
+
+    pub fn game_phase(&self) -> GamePhase {
+        let material = self.phase_material();
+        if material <= ENDGAME_PHASE {
+            return GamePhase::Endgame;
+        }
+
+        let undeveloped_minors = [(1, Color::White), (8, Color::Black)].iter()
+            .flat_map(|&(rank, color)| {
+                [(2, PieceType::Knight), (7, PieceType::Knight), (3, PieceType::Bishop), (6, PieceType::Bishop)]
+                    .map(|(file, piece_type)| (Position { rank, file }, piece_type, color))
+            })
+            .filter(|&(pos, piece_type, color)| {
+                self.pieces.get(&pos).is_some_and(|p| p.piece_type == piece_type && p.color == color)
+            })
+            .count();
+        if material >= OPENING_PHASE && undeveloped_minors >= OPENING_UNDEVELOPED_MINORS {
+            GamePhase::Opening
+        } else {
+            GamePhase::Middlegame
+        }
+    }
+
+    // Material on the board as "KRPvKR": white's pieces, then black's, each
+    // from the king down. Endgame recognizers dispatch on this.
+    pub fn material_signature(&self) -> String {
+        let side = |color: Color| -> String {
+            [PieceType::King, PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight, PieceType::Pawn]
+                .iter()
+                .flat_map(|&piece_type| {
+                    let count = self.pieces.values()
+                        .filter(|p| p.piece_type == piece_type && p.color == color)
+                        .count();
+                    std::iter::repeat_n(Piece::new(piece_type, Color::White).to_fen_char(), count)
+                })
+                .collect()
+        };
+        format!("{}v{}", side(Color::White), side(Color::Black))
+    }
+
     pub fn has_insufficient_material(&self) -> bool {
         let mut white_pieces = Vec::new();
         let mut black_pieces = Vec::new();
@@ -428,7 +1440,7 @@ impl Board {
             let black_bishop = black_pieces.iter()
                 .find(|p| p.piece_type == PieceType::Bishop);
             
-            if let (Some(wb), Some(bb)) = (white_bishop, black_bishop) {
+            if white_bishop.is_some() && black_bishop.is_some() {
                 // Check if bishops are on same colored squares
                 let white_bishop_pos = self.pieces.iter()
                     .find(|(_, p)| p.piece_type == PieceType::Bishop && p.color == Color::White)
@@ -439,11 +1451,402 @@ impl Board {
                     .map(|(pos, _)| pos)
                     .unwrap();
                 
-                return (white_bishop_pos.file + white_bishop_pos.rank) % 2 ==
-                       (black_bishop_pos.file + black_bishop_pos.rank) % 2;
+                return white_bishop_pos.square_color() == black_bishop_pos.square_color();
             }
         }
 
         false
     }
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn en_passant_cannot_expose_the_king_along_the_rank() {
+        // Taking on c6 would lift both pawns off the fifth rank, leaving the
+        // king on a5 facing the rook on h5
+        let board = Board::from_fen("8/8/8/KPp4r/8/8/8/6k1 w - c6 0 1").unwrap();
+        let en_passant = Move::new(Position { rank: 5, file: 2 }, Position { rank: 6, file: 3 });
+        assert!(!board.legal_moves().contains(&en_passant));
+
+        let mut after = board.clone();
+        assert!(after.make_move(en_passant).is_err());
+        assert_eq!(after.get_piece(Position { rank: 5, file: 3 }), board.get_piece(Position { rank: 5, file: 3 }));
+
+        // Without the rook the same capture is fine
+        let unpinned = Board::from_fen("8/8/8/KPp5/8/8/8/6k1 w - c6 0 1").unwrap();
+        assert!(unpinned.legal_moves().contains(&en_passant));
+    }
+
+    // Leaf count of the legal move tree, checking at every node that the
+    // pin/check shortcuts in legal_moves agree with playing each move out
+    fn perft(board: &Board, depth: u32) -> u64 {
+        let moves = board.legal_moves();
+        let mut played_out = board.pseudo_legal_moves();
+        played_out.retain(|&m| board.is_legal(m));
+        assert_eq!(moves, played_out, "legal move mismatch in {}", board.to_fen());
+        assert_eq!(board.legal_move_exists(), !moves.is_empty());
+
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves.iter().map(|&m| perft(&board.with_move(m).unwrap(), depth - 1)).sum()
+    }
+
+    // Published perft counts. Move generation leaves castling to make_move,
+    // so these stick to positions where no castling is possible in range.
+    #[test]
+    fn perft_matches_known_counts() {
+        let cases = [
+            (crate::positions::START_FEN, [20, 400, 8902]),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", [14, 191, 2812]),
+            ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", [24, 496, 9483]),
+        ];
+        for (fen, counts) in cases {
+            let board = Board::from_fen(fen).unwrap();
+            for (depth, expected) in (1..).zip(counts) {
+                assert_eq!(perft(&board, depth), expected, "perft({}) of {}", depth, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn rejected_moves_leave_the_board_unchanged() {
+        // Pins on the e-file and a diagonal, plus an en passant capture that
+        // would expose the king along the rank
+        let fens = [
+            "4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1",
+            "4k3/8/8/7b/8/8/4N3/3K4 w - - 0 1",
+            "8/8/8/KPp4r/8/8/8/6k1 w - c6 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            for chess_move in board.pseudo_legal_moves() {
+                let mut after = board.clone();
+                match after.make_move(chess_move) {
+                    Ok(()) => assert!(board.legal_moves().contains(&chess_move)),
+                    Err(_) => {
+                        assert!(!board.legal_moves().contains(&chess_move));
+                        assert_eq!(after, board, "{} changed the board", chess_move.to_uci());
+                        assert_eq!(after.zobrist_hash(), board.zobrist_hash());
+                        assert!(after.move_history().is_empty());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn castling_is_recorded_as_the_last_move() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let castle = Move::castle(Position { rank: 1, file: 5 }, Position { rank: 1, file: 7 });
+        board.make_move(castle).unwrap();
+        assert_eq!(board.last_move(), Some(castle));
+        assert_eq!(board.move_history(), &[castle]);
+    }
+
+    #[test]
+    fn validate_rejects_impossible_positions() {
+        let cases = [
+            ("8/8/8/8/8/8/8/4K3 w - - 0 1", PositionError::KingCount(Color::Black)),
+            ("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", PositionError::KingCount(Color::White)),
+            ("4k3/8/8/8/8/8/8/P3K3 w - - 0 1", PositionError::PawnOnBackRank(Position { rank: 1, file: 1 })),
+            ("4k3/8/8/8/8/8/8/4RK2 w - - 0 1", PositionError::OpponentInCheck),
+            ("4k3/8/8/8/8/8/8/4K3 w K - 0 1", PositionError::CastlingRightsWithoutPieces),
+            ("4k3/8/8/8/8/8/8/4K3 w - e6 0 1", PositionError::ImpossibleEnPassant(Position { rank: 6, file: 5 })),
+            ("4k3/4p3/8/4p3/8/8/8/4K3 w - e6 0 1", PositionError::ImpossibleEnPassant(Position { rank: 6, file: 5 })),
+            ("4k3/8/8/8/4P3/8/8/4K3 b - d3 0 1", PositionError::ImpossibleEnPassant(Position { rank: 3, file: 4 })),
+            ("4k3/8/8/8/8/8/3P4/4K3 b - d3 0 1", PositionError::ImpossibleEnPassant(Position { rank: 3, file: 4 })),
+        ];
+        for (fen, error) in cases {
+            assert_eq!(Board::from_fen(fen).err(), Some(error.message()), "{}", fen);
+        }
+
+        let legal = crate::test_util::board_from_fen_unwrap("4k3/8/8/4pP2/8/8/8/4K3 w - e6 0 1");
+        assert_eq!(legal.validate(), Ok(()));
+    }
+
+    #[test]
+    fn capturing_a_rook_on_its_corner_clears_its_castling_right() {
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        board.make_move(Move::from_uci("h1h8", &board).unwrap()).unwrap();
+        let fen = board.to_fen();
+        assert_eq!(fen, "r3k2R/8/8/8/8/8/8/R3K3 b Qq - 0 1");
+        assert_eq!(Board::from_fen(&fen), Ok(board));
+    }
+
+    #[test]
+    fn king_cannot_retreat_along_the_checking_line() {
+        // The rook on a1 checks along the first rank; e1 is only "safe" while
+        // the king itself blocks the rook's line
+        let board = Board::from_fen("8/8/8/4k3/8/8/8/r2K4 w - - 0 1").unwrap();
+        let moves: Vec<String> = board.legal_moves().iter().map(Move::to_uci).collect();
+        assert!(!moves.contains(&"d1e1".to_string()));
+        assert!(moves.contains(&"d1d2".to_string()));
+    }
+
+    #[test]
+    fn en_passant_from_fen_takes_the_passed_pawn() {
+        // Nothing has been played on this board, so the capture rests on the
+        // FEN's en passant square alone
+        let board = crate::test_util::assert_fen_roundtrip(crate::positions::EN_PASSANT_FEN);
+        let capture = Move::from_uci("e5d6", &board).unwrap();
+        assert!(board.legal_moves().contains(&capture));
+
+        let after = board.with_move(capture).unwrap();
+        assert_eq!(after.get_piece(Position::from_algebraic("d5").unwrap()), None);
+        assert_eq!(after.get_piece(Position::from_algebraic("d6").unwrap()), Some(&Piece::new(PieceType::Pawn, Color::White)));
+    }
+
+    #[test]
+    fn repetition_count_follows_knights_going_back_and_forth() {
+        let mut board = Board::new();
+        assert_eq!(board.repetition_count(), 1);
+        for expected in [2, 3] {
+            for uci in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                board.make_move(Move::from_uci(uci, &board).unwrap()).unwrap();
+            }
+            assert_eq!(board.repetition_count(), expected);
+        }
+        assert!(board.is_threefold_repetition());
+    }
+
+    #[test]
+    fn claimable_draws_are_told_apart_from_automatic_ones() {
+        // Claimable: the game goes on until someone claims
+        let mut repeated = Board::new();
+        for _ in 0..2 {
+            for uci in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                repeated.make_move(Move::from_uci(uci, &repeated).unwrap()).unwrap();
+            }
+        }
+        let fifty_moves = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 100 80").unwrap();
+        for (board, reason) in [(&repeated, DrawReason::ThreefoldRepetition), (&fifty_moves, DrawReason::FiftyMoveRule)] {
+            assert_eq!(board.can_claim_draw(), Some(reason));
+            assert!(!board.is_draw());
+            assert_eq!(board.result(), None);
+        }
+
+        // Automatic: over without a claim, and nothing left to claim
+        let bare_kings = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(bare_kings.is_draw());
+        assert_eq!(bare_kings.can_claim_draw(), None);
+        assert_eq!(bare_kings.result(), Some(GameResult::Draw(DrawReason::InsufficientMaterial)));
+
+        // The 75th quiet move ends the game by itself
+        let mut seventy_five = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 149 100").unwrap();
+        assert!(!seventy_five.is_draw());
+        seventy_five.make_move(Move::from_uci("a1a2", &seventy_five).unwrap()).unwrap();
+        assert!(seventy_five.is_draw());
+        assert_eq!(seventy_five.result(), Some(GameResult::Draw(DrawReason::SeventyFiveMoveRule)));
+    }
+
+    #[test]
+    fn seventy_five_moves_from_fen_is_an_automatic_draw() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 150 120").unwrap();
+        assert!(board.is_seventy_five_move_draw());
+        assert!(board.is_game_over());
+        assert_eq!(board.result(), Some(GameResult::Draw(DrawReason::SeventyFiveMoveRule)));
+
+        let one_short = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 149 120").unwrap();
+        assert!(!one_short.is_seventy_five_move_draw());
+        assert!(!one_short.is_game_over());
+    }
+
+    #[test]
+    fn epd_round_trips_without_its_operations() {
+        let position = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -";
+        let (board, operations) = Board::from_epd(&format!("{} bm Bb5; id \"Ruy; Lopez\";", position)).unwrap();
+        assert_eq!(board.to_epd(), position);
+        assert_eq!(operations, vec![
+            ("bm".to_string(), "Bb5".to_string()),
+            ("id".to_string(), "\"Ruy; Lopez\"".to_string()),
+        ]);
+        // The counters EPD leaves out get their defaults
+        assert_eq!(board.to_fen(), format!("{} 0 1", position));
+        assert_eq!(Board::from_epd(&board.to_epd()).unwrap(), (board, Vec::new()));
+    }
+
+    #[test]
+    fn fen_dash_fields_and_mixed_castling_rights() {
+        let none = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1").unwrap();
+        assert_eq!(none.castling_rights(), CastlingRights {
+            white_kingside: false,
+            white_queenside: false,
+            black_kingside: false,
+            black_queenside: false,
+        });
+        assert_eq!(none.en_passant_square(), None);
+
+        let mixed = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1").unwrap();
+        assert_eq!(mixed.castling_rights(), CastlingRights {
+            white_kingside: true,
+            white_queenside: false,
+            black_kingside: false,
+            black_queenside: true,
+        });
+        assert_eq!(mixed.to_fen(), "r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1");
+    }
+
+    #[test]
+    fn empty_board_takes_a_position_piece_by_piece() {
+        let mut board = Board::new_empty();
+        assert_eq!(board.piece_count(), 0);
+        assert_eq!(board.current_turn(), Color::White);
+
+        board.set_piece(Position::from_algebraic("e1").unwrap(), Some(Piece::new(PieceType::King, Color::White)));
+        board.set_piece(Position::from_algebraic("e8").unwrap(), Some(Piece::new(PieceType::King, Color::Black)));
+        assert_eq!(board.piece_count(), 2);
+        assert_eq!(board.validate(), Ok(()));
+    }
+
+    #[test]
+    fn with_move_leaves_the_original_board_alone() {
+        let board = Board::new();
+        let fen = board.to_fen();
+        let after = board.with_move(Move::from_uci("e2e4", &board).unwrap()).unwrap();
+        assert_eq!(board.to_fen(), fen);
+        assert!(board.move_history().is_empty());
+        assert_eq!(after.current_turn(), Color::Black);
+        assert_ne!(after.zobrist_hash(), board.zobrist_hash());
+
+        // An illegal move leaves it alone too
+        let too_far = Move::new(Position::from_algebraic("e2").unwrap(), Position::from_algebraic("e5").unwrap());
+        assert!(board.with_move(too_far).is_err());
+        assert_eq!(board.to_fen(), fen);
+    }
+
+    #[test]
+    fn start_position_controls_its_third_rank() {
+        let board = Board::new();
+        for (color, own_third, far_side) in [(Color::White, 3, 4), (Color::Black, 6, 5)] {
+            let controlled = board.controlled_squares(color);
+            for file in 1..=8 {
+                assert!(controlled.contains(&Position { rank: own_third, file }), "{:?} rank {} file {}", color, own_third, file);
+                assert!(!controlled.contains(&Position { rank: far_side, file }), "{:?} rank {} file {}", color, far_side, file);
+            }
+        }
+    }
+
+    #[test]
+    fn checkers_in_single_and_double_check() {
+        let square = |name: &str| Position::from_algebraic(name).unwrap();
+        let single = Board::from_fen("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert_eq!(single.checkers(Color::Black), vec![square("e1")]);
+        assert!(single.checkers(Color::White).is_empty());
+
+        // Knight and rook both give check: only the king can answer that, so
+        // the bishop may not take the knight
+        let double = Board::from_fen("4k3/2b5/3N4/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+        let mut checkers = double.checkers(Color::Black);
+        checkers.sort_by_key(|pos| (pos.rank, pos.file));
+        assert_eq!(checkers, vec![square("e1"), square("d6")]);
+        let moves = double.legal_moves();
+        assert!(!moves.is_empty());
+        assert!(moves.iter().all(|m| m.from == square("e8")));
+    }
+
+    #[test]
+    fn occupied_yields_every_piece() {
+        let board = Board::new();
+        assert_eq!(board.occupied().count(), 32);
+        assert!(board.occupied().all(|(pos, _)| !board.is_empty(pos)));
+        assert_eq!(board.occupied().filter(|(_, piece)| piece.color == Color::White).count(), 16);
+        assert_eq!(Board::new_empty().occupied().count(), 0);
+    }
+
+    #[test]
+    fn king_move_gives_up_both_castling_rights() {
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        board.make_move(Move::from_uci("e1e2", &board).unwrap()).unwrap();
+        let rights = board.castling_rights();
+        assert!(!rights.white_kingside && !rights.white_queenside);
+        assert!(rights.black_kingside && rights.black_queenside);
+
+        board.make_move(Move::from_uci("e8d8", &board).unwrap()).unwrap();
+        assert_eq!(board.castling_rights(), Board::new_empty().castling_rights());
+    }
+
+    #[test]
+    fn in_check_agrees_with_is_in_check() {
+        let fens = [
+            crate::positions::START_FEN,
+            crate::positions::KIWIPETE_FEN,
+            "4k3/8/8/8/8/8/8/4R1K1 b - - 0 1",
+            "4k3/2b5/3N4/8/8/8/8/4R1K1 b - - 0 1",
+            "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(board.in_check(), board.is_in_check(board.current_turn()), "{}", fen);
+        }
+        assert!(Board::from_fen(fens[2]).unwrap().in_check());
+        assert!(!Board::new().in_check());
+    }
+
+    #[test]
+    fn capture_resets_the_irreversible_ply_count() {
+        let mut board = Board::new();
+        for uci in ["g1f3", "b8c6", "f3e5"] {
+            board.make_move(Move::from_uci(uci, &board).unwrap()).unwrap();
+        }
+        assert_eq!(board.plies_since_irreversible(), 3);
+        assert_eq!(board.halfmove_clock(), 3);
+
+        board.make_move(Move::from_uci("c6e5", &board).unwrap()).unwrap();
+        assert_eq!(board.plies_since_irreversible(), 0);
+        assert_eq!(board.halfmove_clock(), 0);
+    }
+
+    #[test]
+    fn legal_moves_from_ignores_whose_turn_it_is() {
+        let white_to_move = Board::new();
+        let black_to_move = white_to_move.with_move(Move::from_uci("e2e4", &white_to_move).unwrap()).unwrap();
+        for board in [&white_to_move, &black_to_move] {
+            let targets = |from: &str| -> Vec<String> {
+                let mut targets: Vec<String> = board.legal_moves_from(Position::from_algebraic(from).unwrap())
+                    .iter()
+                    .map(|m| m.to.to_algebraic())
+                    .collect();
+                targets.sort();
+                targets
+            };
+            assert_eq!(targets("b8"), ["a6", "c6"]);
+            assert_eq!(targets("b1"), ["a3", "c3"]);
+        }
+        assert!(white_to_move.legal_moves_from(Position::from_algebraic("e4").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn attack_count_around_the_king_in_the_two_knights() {
+        // 4.Ng5 of the Two Knights: knight and bishop both hit f7, the only
+        // square next to Black's king White reaches
+        let mut board = Board::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p1N1/2B1P3/8/PPPP1PPP/RNBQK2R b KQkq - 5 4").unwrap();
+        let king_zone: Vec<Position> = ["d8", "e8", "f8", "d7", "e7", "f7"].iter()
+            .map(|square| Position::from_algebraic(square).unwrap())
+            .collect();
+        assert_eq!(board.attack_count(&king_zone, Color::White), 1);
+
+        // After 4...h6 5.Nxf7 the knight forks d8 and h8 and the bishop still
+        // covers f7
+        for uci in ["h7h6", "g5f7"] {
+            board.make_move(Move::from_uci(uci, &board).unwrap()).unwrap();
+        }
+        assert_eq!(board.attack_count(&king_zone, Color::White), 2);
+    }
+
+    #[test]
+    fn game_phase_covers_opening_middlegame_and_endgame() {
+        use crate::positions;
+        assert_eq!(positions::start().game_phase(), GamePhase::Opening);
+        // Everything still on, but the minor pieces are all out
+        assert_eq!(positions::kiwipete().game_phase(), GamePhase::Middlegame);
+        // Queens off and a minor piece each traded is still a middlegame
+        let traded = Board::from_fen("r1b1kb1r/pppp1ppp/2n5/4p3/4P3/2N5/PPPP1PPP/R1B1KB1R w KQkq - 0 6").unwrap();
+        assert_eq!(traded.game_phase(), GamePhase::Middlegame);
+        assert_eq!(positions::rook_pawn_endgame().game_phase(), GamePhase::Endgame);
+        assert_eq!(positions::lucena().game_phase(), GamePhase::Endgame);
+    }
+}
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+// Rough stage of the game, see Board::game_phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamePhase {
+    Opening,
+    Middlegame,
+    Endgame,
+}
+
+// Phase weights of the non-pawn pieces. All of them on the board add up to 24.
+pub(crate) const KNIGHT_PHASE: u32 = 1;
+pub(crate) const BISHOP_PHASE: u32 = 1;
+pub(crate) const ROOK_PHASE: u32 = 2;
+pub(crate) const QUEEN_PHASE: u32 = 4;
+pub(crate) const TOTAL_PHASE: u32 = 24;
+
+// At or below this much phase material it's an endgame, e.g. a rook and
+// two minor pieces each
+pub(crate) const ENDGAME_PHASE: u32 = 8;
+// Still the opening while nearly everything is on and most minor pieces
+// haven't left their starting squares
+pub(crate) const OPENING_PHASE: u32 = 22;
+pub(crate) const OPENING_UNDEVELOPED_MINORS: usize = 4;
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use crate::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DrawReason {
+    Stalemate,
+    InsufficientMaterial,
+    ThreefoldRepetition,  // Only once claimed (see Board::can_claim_draw)
+    FiftyMoveRule,        // Likewise
+    SeventyFiveMoveRule,
+    Adjudicated,  // Declared by the caller (e.g. a move limit), never by Board::result
+}
+
+// Final result of a game. Board::result only produces wins by checkmate;
+// callers can also award them for forfeits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw(DrawReason),
+}
+
+impl GameResult {
+    pub fn win_for(color: Color) -> Self {
+        match color {
+            Color::White => GameResult::WhiteWins,
+            Color::Black => GameResult::BlackWins,
+        }
+    }
+
+    pub fn winner(&self) -> Option<Color> {
+        match self {
+            GameResult::WhiteWins => Some(Color::White),
+            GameResult::BlackWins => Some(Color::Black),
+            GameResult::Draw(_) => None,
+        }
+    }
+
+    // Result token used in PGN tags and movetext
+    pub fn to_pgn(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw(_) => "1/2-1/2",
+        }
+    }
+}
@@ -3,9 +3,19 @@ pub mod board;
 pub mod piece;
 pub mod position;
 pub mod moves;
+pub mod move_cache;
+pub mod game_result;
+pub mod game_phase;
+pub mod positions;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+mod zobrist;
 
 // Re-export main types for convenience
-pub use board::Board;
+pub use board::{Board, CastlingRights, PositionError};
 pub use piece::{Piece, Color, PieceType};
-pub use position::Position;
-pub use moves::{Move, MoveType}; 
\ No newline at end of file
+pub use position::{Position, SquareColor};
+pub use moves::{Move, MoveType};
+pub use move_cache::MoveCache;
+pub use game_result::{GameResult, DrawReason};
+pub use game_phase::GamePhase; 
\ No newline at end of file
@@ -0,0 +1,102 @@
+use std::collections::{BTreeMap, HashMap};
+use crate::{Board, Move};
+
+const DEFAULT_CAPACITY: usize = 64;
+
+// Small LRU cache of full legal move lists keyed by the position's Zobrist hash.
+// Entries never go stale: any change to the board changes the key, so old
+// positions simply age out once the cache is full.
+#[derive(Debug, Clone)]
+pub struct MoveCache {
+    capacity: usize,
+    entries: HashMap<u64, (Vec<Move>, u64)>,  // Moves and the tick they were last used at
+    recency: BTreeMap<u64, u64>,  // Tick to key, least recently used first
+    tick: u64,
+}
+
+impl MoveCache {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            entries: HashMap::with_capacity(capacity),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    // Returns the legal moves for the side to move, generating them on a miss
+    pub fn legal_moves(&mut self, board: &Board) -> Vec<Move> {
+        self.with_legal_moves(board, |moves| moves.to_vec())
+    }
+
+    // Hands the cached legal moves to `f` without copying the list, e.g. when
+    // only their number is needed
+    pub fn with_legal_moves<T>(&mut self, board: &Board, f: impl FnOnce(&[Move]) -> T) -> T {
+        let key = board.zobrist_hash();
+        self.tick += 1;
+        let tick = self.tick;
+
+        if let Some((moves, last_used)) = self.entries.get_mut(&key) {
+            self.recency.remove(last_used);
+            self.recency.insert(tick, key);
+            *last_used = tick;
+            return f(moves);
+        }
+
+        if self.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = self.recency.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.recency.insert(tick, key);
+        let (moves, _) = self.entries.entry(key).or_insert((board.legal_moves(), tick));
+        f(moves)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+}
+
+impl Default for MoveCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_the_least_recently_used_position() {
+        let start = Board::new();
+        let after = |uci: &str| start.with_move(Move::from_uci(uci, &start).unwrap()).unwrap();
+        let (after_e4, after_d4) = (after("e2e4"), after("d2d4"));
+
+        let mut cache = MoveCache::new(2);
+        assert_eq!(cache.legal_moves(&start).len(), 20);
+        cache.legal_moves(&after_e4);
+        cache.legal_moves(&start);  // Now after_e4 is the oldest
+        cache.legal_moves(&after_d4);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.entries.contains_key(&start.zobrist_hash()));
+        assert!(!cache.entries.contains_key(&after_e4.zobrist_hash()));
+        assert_eq!(cache.legal_moves(&after_d4), after_d4.legal_moves());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{Position, Piece, piece::{PieceType, Color}, Board};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Move {
     pub from: Position,
     pub to: Position,
@@ -17,7 +18,7 @@ impl PartialEq for Move {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum MoveType {
     Normal,
     Capture,
@@ -53,6 +54,154 @@ impl Move {
         }
     }
 
+    // Whether playing this move from `board` takes a piece. An en passant
+    // capture lands on an empty square, so it's spotted by a pawn moving onto
+    // the en passant square.
+    pub fn is_capture(&self, board: &Board) -> bool {
+        if board.get_piece(self.to).is_some() {
+            return true;
+        }
+        board.en_passant_square() == Some(self.to) &&
+            board.get_piece(self.from).is_some_and(|p| p.piece_type == PieceType::Pawn)
+    }
+
+    // Flagged as castling, or the king's two-square step from its home square.
+    // Only the geometry is checked, so this assumes `from` holds the king.
+    pub fn is_castle(&self) -> bool {
+        if self.move_type == MoveType::Castle {
+            return true;
+        }
+        self.from.file == 5 && (self.from.rank == 1 || self.from.rank == 8) &&
+            self.to.rank == self.from.rank && (self.to.file == 3 || self.to.file == 7)
+    }
+
+    // Long algebraic notation as used by UCI, e.g. e2e4 or e7e8q
+    pub fn to_uci(&self) -> String {
+        let promotion = match self.promotion {
+            Some(PieceType::Queen) => "q",
+            Some(PieceType::Rook) => "r",
+            Some(PieceType::Bishop) => "b",
+            Some(PieceType::Knight) => "n",
+            _ => "",
+        };
+        format!("{}{}{}", self.from.to_algebraic(), self.to.to_algebraic(), promotion)
+    }
+
+    // Standard Algebraic Notation for this move, played from `board`
+    pub fn to_san(&self, board: &Board) -> String {
+        let piece = match board.get_piece(self.from) {
+            Some(p) => *p,
+            None => return format!("{}{}", self.from.to_algebraic(), self.to.to_algebraic()),
+        };
+
+        let mut san = if piece.piece_type == PieceType::King && self.is_castle() {
+            if self.to.file > self.from.file { "O-O".to_string() } else { "O-O-O".to_string() }
+        } else {
+            let is_capture = self.is_capture(board);
+            let mut san = String::new();
+
+            if piece.piece_type == PieceType::Pawn {
+                if is_capture {
+                    san.push((b'a' + (self.from.file - 1)) as char);
+                }
+            } else {
+                san.push(Self::piece_letter(piece.piece_type));
+                san.push_str(&self.disambiguation(&piece, board));
+            }
+
+            if is_capture {
+                san.push('x');
+            }
+            san.push_str(&self.to.to_algebraic());
+
+            if let Some(promotion) = self.promotion {
+                san.push('=');
+                san.push(Self::piece_letter(promotion));
+            }
+            san
+        };
+
+        if let Ok(after) = board.with_move(*self) {
+            if after.is_checkmate() {
+                san.push('#');
+            } else if after.in_check() {
+                san.push('+');
+            }
+        }
+
+        san
+    }
+
+    // Legal move in `board` written in UCI notation, e.g. e2e4 or e7e8q
+    pub fn from_uci(text: &str, board: &Board) -> Option<Move> {
+        if let Some(mv) = board.legal_moves().into_iter().find(|m| m.to_uci() == text) {
+            return Some(mv);
+        }
+
+        // Castling isn't in the legal move list; it's written as the king's two-square step
+        let from = Position::from_algebraic(text.get(0..2)?)?;
+        let to = Position::from_algebraic(text.get(2..)?)?;
+        Self::legal_castle(from, to, board)
+    }
+
+    // Legal move in `board` written in SAN, e.g. Nf3, exd5, e8=Q or O-O.
+    // Check marks and annotations are optional.
+    pub fn from_san(text: &str, board: &Board) -> Option<Move> {
+        let san = text.trim_end_matches(['+', '#', '!', '?']);
+        let rank = if board.current_turn() == Color::White { 1 } else { 8 };
+        let castle_file = match san {
+            "O-O" | "0-0" => Some(7),
+            "O-O-O" | "0-0-0" => Some(3),
+            _ => None,
+        };
+        if let Some(file) = castle_file {
+            return Self::legal_castle(Position { rank, file: 5 }, Position { rank, file }, board);
+        }
+
+        board.legal_moves().into_iter()
+            .find(|mv| mv.to_san(board).trim_end_matches(['+', '#']) == san)
+    }
+
+    fn legal_castle(from: Position, to: Position, board: &Board) -> Option<Move> {
+        let mv = Move::castle(from, to);
+        let is_king = board.get_piece(from).is_some_and(|p| p.piece_type == PieceType::King);
+        (is_king && mv.is_castle() && board.is_legal(mv)).then_some(mv)
+    }
+
+    fn piece_letter(piece_type: PieceType) -> char {
+        match piece_type {
+            PieceType::Pawn => 'P',
+            PieceType::Knight => 'N',
+            PieceType::Bishop => 'B',
+            PieceType::Rook => 'R',
+            PieceType::Queen => 'Q',
+            PieceType::King => 'K',
+        }
+    }
+
+    // File and/or rank needed to tell this move apart from identical pieces
+    // that could also legally reach the destination
+    fn disambiguation(&self, piece: &Piece, board: &Board) -> String {
+        let rivals: Vec<Position> = board.piece_map().iter()
+            .filter(|(&pos, p)| **p == *piece && pos != self.from)
+            .map(|(&pos, _)| pos)
+            .filter(|&pos| {
+                let rival_move = Move::new(pos, self.to);
+                rival_move.is_valid(board) && board.is_legal(rival_move)
+            })
+            .collect();
+
+        if rivals.is_empty() {
+            String::new()
+        } else if rivals.iter().all(|pos| pos.file != self.from.file) {
+            ((b'a' + (self.from.file - 1)) as char).to_string()
+        } else if rivals.iter().all(|pos| pos.rank != self.from.rank) {
+            self.from.rank.to_string()
+        } else {
+            self.from.to_algebraic()
+        }
+    }
+
     pub fn is_valid(&self, board: &Board) -> bool {
         let piece = match board.get_piece(self.from) {
             Some(p) => p,
@@ -75,6 +224,24 @@ impl Move {
         self.is_valid_piece_movement(piece, board)
     }
 
+    // Whether the piece on `from` attacks `to`, regardless of what stands on `to`.
+    // Differs from is_valid for pawns, which only attack diagonally.
+    pub(crate) fn is_attack(&self, piece: &Piece, board: &Board) -> bool {
+        if self.from == self.to {
+            return false;
+        }
+
+        match piece.piece_type {
+            PieceType::Pawn => {
+                let direction = if piece.color == Color::White { 1 } else { -1 };
+                let rank_diff = self.to.rank as i8 - self.from.rank as i8;
+                let file_diff = (self.to.file as i8 - self.from.file as i8).abs();
+                rank_diff == direction && file_diff == 1
+            }
+            _ => self.is_valid_piece_movement(piece, board),
+        }
+    }
+
     fn is_valid_piece_movement(&self, piece: &Piece, board: &Board) -> bool {
         match piece.piece_type {
             PieceType::Pawn => self.is_valid_pawn_move(piece.color, board),
@@ -98,14 +265,14 @@ impl Move {
         // Basic forward movement
         if file_diff == 0 {
             if rank_diff == direction {
-                return board.get_piece(self.to).is_none();
+                return board.is_empty(self.to);
             }
             // Initial two-square move
             if (color == Color::White && self.from.rank == 2) || 
                (color == Color::Black && self.from.rank == 7) {
                 if rank_diff == 2 * direction {
-                    let intermediate = Position::new(self.from.file, (self.from.rank as i8 + direction) as u8).unwrap();
-                    return board.get_piece(intermediate).is_none() && board.get_piece(self.to).is_none();
+                    let intermediate = Position::from_file_rank(self.from.file, (self.from.rank as i8 + direction) as u8).unwrap();
+                    return board.is_empty(intermediate) && board.is_empty(self.to);
                 }
             }
         }
@@ -116,21 +283,10 @@ impl Move {
                 return captured_piece.color != color;
             }
 
-            // En passant capture
-            if let Some(last_move) = board.last_move() {
-                if last_move.from.file == self.to.file {
-                    if let Some(last_piece) = board.get_piece(last_move.to) {
-                        if last_piece.piece_type == PieceType::Pawn {
-                            let last_rank_diff = (last_move.to.rank as i8 - last_move.from.rank as i8).abs();
-                            if last_rank_diff == 2 {
-                                let expected_rank = if color == Color::White { 5 } else { 4 };
-                                if self.from.rank == expected_rank {
-                                    return true;
-                                }
-                            }
-                        }
-                    }
-                }
+            // En passant capture onto the square the enemy pawn skipped
+            let target_rank = if color == Color::White { 6 } else { 3 };
+            if board.en_passant_square() == Some(self.to) && self.to.rank == target_rank {
+                return true;
             }
         }
 
@@ -185,7 +341,7 @@ impl Move {
         while (current_rank != target_rank || current_file != target_file) &&
               current_rank >= 1 && current_rank <= 8 &&
               current_file >= 1 && current_file <= 8 {
-            let pos = Position::new(current_file as u8, current_rank as u8).unwrap();
+            let pos = Position::from_file_rank(current_file as u8, current_rank as u8).unwrap();
             if board.get_piece(pos).is_some() {
                 return false;
             }
@@ -195,4 +351,24 @@ impl Move {
 
         true
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_and_castles_are_recognized() {
+        let board = Board::from_fen(crate::positions::EN_PASSANT_FEN).unwrap();
+        // The en passant capture lands on an empty square
+        let en_passant = Move::from_uci("e5d6", &board).unwrap();
+        assert!(board.get_piece(en_passant.to).is_none());
+        assert!(en_passant.is_capture(&board));
+        assert!(!Move::from_uci("e5e6", &board).unwrap().is_capture(&board));
+        assert!(!Move::from_uci("g1f3", &board).unwrap().is_capture(&board));
+
+        let e1 = Position::from_algebraic("e1").unwrap();
+        assert!(Move::new(e1, Position::from_algebraic("g1").unwrap()).is_castle());
+        assert!(Move::new(e1, Position::from_algebraic("c1").unwrap()).is_castle());
+        assert!(!Move::new(e1, Position::from_algebraic("f1").unwrap()).is_castle());
+    }
+}
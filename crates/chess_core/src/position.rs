@@ -1,18 +1,38 @@
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SquareColor {
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Position {
     pub rank: u8,  // 1-8
     pub file: u8,  // a-h (1-8)
 }
 
 impl Position {
+    // File first, unlike the struct literal which lists rank first. Prefer
+    // from_file_rank or from_rank_file, which say which order they expect.
+    #[deprecated(note = "argument order is easy to mix up; use from_file_rank or from_rank_file")]
     pub fn new(file: u8, rank: u8) -> Option<Self> {
-        if file <= 8 && rank <= 8 {
-            Some(Self { file, rank })
+        Self::from_file_rank(file, rank)
+    }
+
+    // Both coordinates run 1-8 (a-h for the file); anything else is off the board
+    pub fn from_file_rank(file: u8, rank: u8) -> Option<Self> {
+        if (1..=8).contains(&file) && (1..=8).contains(&rank) {
+            Some(Self { rank, file })
         } else {
             None
         }
     }
 
+    pub fn from_rank_file(rank: u8, file: u8) -> Option<Self> {
+        Self::from_file_rank(file, rank)
+    }
+
     pub fn from_algebraic(notation: &str) -> Option<Self> {
         if notation.len() != 2 {
             return None;
@@ -30,4 +50,53 @@ impl Position {
             rank: (rank as u8) - b'0',
         })
     }
-} 
\ No newline at end of file
+
+    // a1 is a dark square, h1 a light one
+    pub fn square_color(&self) -> SquareColor {
+        if (self.file + self.rank) % 2 == 1 {
+            SquareColor::Light
+        } else {
+            SquareColor::Dark
+        }
+    }
+
+    pub fn to_algebraic(&self) -> String {
+        format!("{}{}", (b'a' + (self.file - 1)) as char, self.rank)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constructors_take_their_named_argument_order() {
+        let b7 = Position::from_algebraic("b7").unwrap();
+        assert_eq!(Position::from_file_rank(2, 7), Some(b7));
+        assert_eq!(Position::from_rank_file(7, 2), Some(b7));
+        #[allow(deprecated)]
+        let new = Position::new(2, 7);
+        assert_eq!(new, Some(b7));
+    }
+
+    #[test]
+    fn constructors_reject_squares_off_the_board() {
+        for (file, rank) in [(0, 1), (9, 1), (1, 0), (1, 9), (0, 0)] {
+            assert_eq!(Position::from_file_rank(file, rank), None);
+            assert_eq!(Position::from_rank_file(rank, file), None);
+        }
+        assert_eq!(Position::from_file_rank(8, 8).map(|p| p.to_algebraic()), Some("h8".to_string()));
+        assert_eq!(Position::from_rank_file(1, 1).map(|p| p.to_algebraic()), Some("a1".to_string()));
+    }
+
+    #[test]
+    fn square_colors_alternate_from_a_dark_a1() {
+        let color = |square: &str| Position::from_algebraic(square).unwrap().square_color();
+        assert_eq!(color("a1"), SquareColor::Dark);
+        assert_eq!(color("h1"), SquareColor::Light);
+        assert_eq!(color("a8"), SquareColor::Light);
+        assert_eq!(color("h8"), SquareColor::Dark);
+        assert_eq!(color("d1"), SquareColor::Light);
+        assert_eq!(color("e1"), SquareColor::Dark);
+    }
+}
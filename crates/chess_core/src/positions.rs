@@ -0,0 +1,119 @@
+//! Well-known positions for examples, tests and probing the engine by hand.
+//!
+//! ```
+//! use chess_core::positions;
+//!
+//! // Play into the back rank mate, then find the move that finishes it
+//! let mut board = positions::back_rank_mate_in_two();
+//! for uci in ["e2e8", "c8e8"] {
+//!     let mv = board.legal_moves().into_iter().find(|m| m.to_uci() == uci).unwrap();
+//!     board.make_move(mv).unwrap();
+//! }
+//! let best = board.legal_moves().into_iter().find(|&mv| {
+//!     board.with_move(mv).is_ok_and(|after| after.is_checkmate())
+//! });
+//! assert_eq!(best.map(|mv| mv.to_san(&board)).as_deref(), Some("Qxe8#"));
+//! ```
+use crate::Board;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// Busy middlegame full of pins, checks, promotions and en passant; the usual
+// perft stress test
+pub const KIWIPETE_FEN: &str =
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+// Sparse rook and pawn ending, the third standard perft position
+pub const ROOK_PAWN_ENDGAME_FEN: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+
+// White mates in two: Re8+ Rxe8 Qxe8#
+pub const BACK_RANK_MATE_IN_TWO_FEN: &str = "2r3k1/5ppp/8/8/8/8/4RPPP/4Q1K1 w - - 0 1";
+
+// White mates in two with king and rook against the bare king
+pub const ROOK_MATE_IN_TWO_FEN: &str = "k7/8/2K5/8/8/8/8/1R6 w - - 0 1";
+
+// White mates in two in a crowded middlegame
+pub const MIDDLEGAME_MATE_IN_TWO_FEN: &str =
+    "r1bq2r1/b4pk1/p1pp1p2/1p2pP2/1P2P1PB/3P4/1PPQ2P1/R3K2R w - - 0 1";
+
+// Black is lost but draws by throwing the rook at the white king until it
+// has to be taken: Rh6+ Ka5 Ra6+ Kxa6 is stalemate
+pub const STALEMATE_TRAP_FEN: &str = "k7/2Q5/1K6/8/8/8/8/7r b - - 0 1";
+
+// Lucena position: White wins by building a bridge with the rook
+pub const LUCENA_FEN: &str = "1K1k4/1P6/8/8/8/8/r7/2R5 w - - 0 1";
+
+// Reti's study: the white king chases two targets at once and draws
+pub const RETI_FEN: &str = "7K/8/k1P5/7p/8/8/8/8 w - - 0 1";
+
+// Saavedra position: White wins, underpromoting to a rook to dodge stalemate
+pub const SAAVEDRA_FEN: &str = "8/8/1KP5/3r4/8/8/8/k7 w - - 0 1";
+
+// White wins by underpromoting with check: e8=N+ forks king and queen and
+// Nxc7 follows, while e8=Q loses to Qc1+ Qe1 Qxe1#
+pub const KNIGHT_PROMOTION_FEN: &str = "8/2q1P1k1/8/8/8/8/6PP/7K w - - 0 1";
+
+// King and pawn ending where the opposition decides: drawn as it stands,
+// but with Black to move Black has to give way and White queens
+pub const OPPOSITION_FEN: &str = "8/8/4k3/8/4K3/4P3/8/8 w - - 0 1";
+
+// Black has just played d7-d5 past the white pawn, so exd6 en passant is
+// legal this move only; the FEN alone has to carry that
+pub const EN_PASSANT_FEN: &str = "rnbqkbnr/1pp1pppp/p7/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3";
+
+// All the FENs above are known to parse, so these can't fail
+fn load(fen: &str) -> Board {
+    Board::from_fen(fen).expect("built-in position should be valid FEN")
+}
+
+pub fn start() -> Board {
+    load(START_FEN)
+}
+
+pub fn kiwipete() -> Board {
+    load(KIWIPETE_FEN)
+}
+
+pub fn rook_pawn_endgame() -> Board {
+    load(ROOK_PAWN_ENDGAME_FEN)
+}
+
+pub fn back_rank_mate_in_two() -> Board {
+    load(BACK_RANK_MATE_IN_TWO_FEN)
+}
+
+pub fn rook_mate_in_two() -> Board {
+    load(ROOK_MATE_IN_TWO_FEN)
+}
+
+pub fn middlegame_mate_in_two() -> Board {
+    load(MIDDLEGAME_MATE_IN_TWO_FEN)
+}
+
+pub fn stalemate_trap() -> Board {
+    load(STALEMATE_TRAP_FEN)
+}
+
+pub fn lucena() -> Board {
+    load(LUCENA_FEN)
+}
+
+pub fn reti() -> Board {
+    load(RETI_FEN)
+}
+
+pub fn saavedra() -> Board {
+    load(SAAVEDRA_FEN)
+}
+
+pub fn knight_promotion() -> Board {
+    load(KNIGHT_PROMOTION_FEN)
+}
+
+pub fn opposition() -> Board {
+    load(OPPOSITION_FEN)
+}
+
+pub fn en_passant() -> Board {
+    load(EN_PASSANT_FEN)
+}
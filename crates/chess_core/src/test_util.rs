@@ -0,0 +1,27 @@
+//! Helpers for tests that set positions up from FEN, so a mistyped fixture
+//! fails loudly instead of quietly testing the wrong board.
+//!
+//! Only built for this crate's tests and for crates that enable the
+//! `test-util` feature, as chess_engine does for its dev-dependency.
+use crate::Board;
+
+// Loads a fixture, panicking with the FEN and what's wrong with it
+pub fn board_from_fen_unwrap(fen: &str) -> Board {
+    match Board::from_fen(fen) {
+        Ok(board) => board,
+        Err(e) => panic!("invalid test FEN {:?}: {}", fen, e),
+    }
+}
+
+// Loads a fixture and checks that writing it back out gives the same FEN,
+// which also catches mistakes from_fen lets through. Left-out move counters
+// are compared as their defaults, 0 and 1.
+pub fn assert_fen_roundtrip(fen: &str) -> Board {
+    let board = board_from_fen_unwrap(fen);
+    let mut fields: Vec<&str> = fen.split_whitespace().collect();
+    for default in ["0", "1"].iter().skip(fields.len().saturating_sub(4)) {
+        fields.push(default);
+    }
+    assert_eq!(board.to_fen(), fields.join(" "), "FEN didn't survive a round trip");
+    board
+}
@@ -0,0 +1,87 @@
+use crate::{Piece, Position, piece::{PieceType, Color}};
+
+// Random keys used to build a 64-bit hash of a position. The table is
+// generated at compile time from a fixed seed so hashes are stable across runs.
+pub(crate) struct ZobristKeys {
+    pieces: [[u64; 64]; 12],
+    black_to_move: u64,
+    castling: [u64; 4],
+    en_passant_file: [u64; 8],
+}
+
+pub(crate) static KEYS: ZobristKeys = ZobristKeys::generate();
+
+// SplitMix64 - small, fast and good enough for hash keys
+const fn next_key(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl ZobristKeys {
+    const fn generate() -> Self {
+        let mut state = 0x5EED_C4E5_5EED_C4E5;
+        let mut pieces = [[0; 64]; 12];
+        let mut piece = 0;
+        while piece < 12 {
+            let mut square = 0;
+            while square < 64 {
+                pieces[piece][square] = next_key(&mut state);
+                square += 1;
+            }
+            piece += 1;
+        }
+
+        let black_to_move = next_key(&mut state);
+
+        let mut castling = [0; 4];
+        let mut i = 0;
+        while i < 4 {
+            castling[i] = next_key(&mut state);
+            i += 1;
+        }
+
+        let mut en_passant_file = [0; 8];
+        let mut i = 0;
+        while i < 8 {
+            en_passant_file[i] = next_key(&mut state);
+            i += 1;
+        }
+
+        Self {
+            pieces,
+            black_to_move,
+            castling,
+            en_passant_file,
+        }
+    }
+
+    pub(crate) fn piece(&self, piece: &Piece, pos: Position) -> u64 {
+        let type_index = match piece.piece_type {
+            PieceType::Pawn => 0,
+            PieceType::Knight => 1,
+            PieceType::Bishop => 2,
+            PieceType::Rook => 3,
+            PieceType::Queen => 4,
+            PieceType::King => 5,
+        };
+        let color_offset = if piece.color == Color::White { 0 } else { 6 };
+        let square = ((pos.rank - 1) * 8 + (pos.file - 1)) as usize;
+        self.pieces[type_index + color_offset][square]
+    }
+
+    pub(crate) fn black_to_move(&self) -> u64 {
+        self.black_to_move
+    }
+
+    // Index order: white kingside, white queenside, black kingside, black queenside
+    pub(crate) fn castling(&self, index: usize) -> u64 {
+        self.castling[index]
+    }
+
+    pub(crate) fn en_passant_file(&self, file: u8) -> u64 {
+        self.en_passant_file[(file - 1) as usize]
+    }
+}
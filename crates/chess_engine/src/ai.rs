@@ -1,19 +1,125 @@
-use chess_core::{Board, Move};
-use crate::search::search_best_move;
+use chess_core::{Board, Color, Move};
+use crate::search::{search_best_move, search_deterministic, search_progress, search_to_depth, set_skill_level, set_variety_margin, stop_search, SearchResult, TimeControl, MAX_SKILL_LEVEL};
+use crate::evaluation::{set_eval_params, set_evaluator, set_move_cache_size, ClassicalEval, EvalParams, Evaluator};
+use crate::opening_book::OpeningBook;
 use std::time::{Duration, Instant};
 use std::collections::HashSet;
+use std::sync::Arc;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 
 const MAX_THINK_TIME: Duration = Duration::from_secs(3);
 const MIN_DEPTH: u8 = 1;  // Start from depth 1 for iterative deepening
 const MAX_DEPTH: u8 = 6;  // Reduced from 12 to 6 for faster moves
+const DEFAULT_DEPTH: u8 = 4;
 const DEFAULT_MOVES_LEFT: u32 = 30;
 const MAX_RETRIES: usize = 3;
+const MAX_ANALYSIS_TIME: Duration = Duration::from_secs(15);
+const DEFAULT_SEED: u64 = 0x00C0_FFEE;
+
+// Opening variety (see set_opening_variety): how far behind the best a move
+// may score and still be played, how many moves are in the running, and how
+// much likelier the best of them is than each of the others
+const OPENING_VARIETY_MARGIN: i32 = 40;
+const OPENING_VARIETY_MOVES: usize = 3;
+const OPENING_BEST_MOVE_WEIGHT: u32 = 2;
+
+// Rough playing strength for each search depth, node budget and thinking
+// time per move (in milliseconds), read as (at least this much, Elo). Only
+// estimates from self-play - recalibrate by editing the tables.
+const ELO_BY_DEPTH: [(u64, u32); 6] = [
+    (1, 800), (2, 1000), (3, 1200), (4, 1400), (5, 1550), (6, 1700),
+];
+const ELO_BY_NODES: [(u64, u32); 5] = [
+    (1_000, 900), (10_000, 1150), (100_000, 1400), (1_000_000, 1650), (10_000_000, 1800),
+];
+const ELO_BY_TIME_MS: [(u64, u32); 6] = [
+    (10, 800), (50, 1000), (200, 1200), (500, 1400), (1_000, 1550), (3_000, 1700),
+];
+const ELO_LOST_PER_BLUNDER_RATE: f32 = 1000.0;  // So 10% random moves cost about 100 Elo
+const ELO_LOST_PER_SKILL_LEVEL: u32 = 50;       // Below full strength
+const MIN_ELO: u32 = 400;
+
+// Where a move returned by get_move came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveSource {
+    Book,                // Opening book
+    TranspositionTable,  // Already searched deeply enough earlier
+    Search,              // Calculated by the search
+    Random,              // Deliberate blunder (see set_blunder_probability)
+    Fallback,            // First legal move found after the search failed
+}
+
+// Playing style: a set of evaluation weights plus how much the AI avoids draws
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Personality {
+    #[default]
+    Balanced,
+    Aggressive,  // Goes after the king and plays on rather than settling for a draw
+    Positional,  // Cares most about pawn structure and piece activity
+    Defensive,   // Keeps its king sheltered and doesn't mind a draw
+}
+
+impl Personality {
+    pub const ALL: [Personality; 4] = [
+        Personality::Balanced,
+        Personality::Aggressive,
+        Personality::Positional,
+        Personality::Defensive,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Personality::Balanced => "Balanced",
+            Personality::Aggressive => "Aggressive",
+            Personality::Positional => "Positional",
+            Personality::Defensive => "Defensive",
+        }
+    }
+
+    // The one after this in ALL, wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&p| p == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    fn eval_params(self) -> EvalParams {
+        match self {
+            Personality::Balanced => EvalParams::BALANCED,
+            Personality::Aggressive => EvalParams::AGGRESSIVE,
+            Personality::Positional => EvalParams::POSITIONAL,
+            Personality::Defensive => EvalParams::DEFENSIVE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AiMove {
+    pub chess_move: Move,
+    pub source: MoveSource,
+}
 
 #[derive(Clone)]
 pub struct ChessAI {
     max_depth: u8,
     max_time: Duration,
     invalid_moves: HashSet<String>, // Track moves by their string representation
+    last_search: Option<SearchResult>, // Details of the search behind the last returned move
+    blunder_probability: f32, // Chance per move of playing a random legal move instead of searching
+    max_nodes: Option<u64>,   // Node limit per search, on top of the time limit
+    depth_limit: Option<u8>,  // Depth limit per search, on top of the time limit
+    rng: StdRng,
+    seed: u64,
+    seeded: bool,             // Set by set_seed: searches then start from an empty table
+    deterministic: bool,      // Fixed-depth searches with fresh tables, see new_deterministic
+    personality: Personality,
+    evaluator: Arc<dyn Evaluator>,
+    book: OpeningBook,
+    use_book: bool,           // Whether get_move plays book moves before searching
+    variety_margin: i32,      // Centipawns a move may trail the best by and still be picked
+    skill: u8,                // 0 to MAX_SKILL_LEVEL, see set_skill
+    time_control: Option<TimeControl>,  // None spreads max_time over DEFAULT_MOVES_LEFT moves
+    opening_variety: u32,     // Plies from the start of the game played with a wider choice of moves
+    move_cache_size: usize,   // Positions whose legal moves the evaluation caches, 0 for none
 }
 
 impl ChessAI {
@@ -22,6 +128,34 @@ impl ChessAI {
             max_depth: depth.clamp(MIN_DEPTH, MAX_DEPTH),
             max_time: MAX_THINK_TIME,
             invalid_moves: HashSet::new(),
+            last_search: None,
+            blunder_probability: 0.0,
+            max_nodes: None,
+            depth_limit: None,
+            rng: StdRng::seed_from_u64(DEFAULT_SEED),
+            seed: DEFAULT_SEED,
+            seeded: false,
+            deterministic: false,
+            personality: Personality::default(),
+            evaluator: Arc::new(ClassicalEval),
+            book: OpeningBook::new(),
+            use_book: true,
+            variety_margin: 0,
+            skill: MAX_SKILL_LEVEL,
+            time_control: None,
+            opening_variety: 0,
+            move_cache_size: 0,
+        }
+    }
+
+    // An AI that always answers the same position with the same move: it
+    // searches to its fixed depth instead of against the clock, starts every
+    // search from an empty transposition table, and replays its RNG from the
+    // seed on each call. Meant for reproducible tests.
+    pub fn new_deterministic() -> Self {
+        ChessAI {
+            deterministic: true,
+            ..Self::default()
         }
     }
 
@@ -31,17 +165,75 @@ impl ChessAI {
             mv.to.file, mv.to.rank)
     }
 
-    pub fn get_move(&mut self, board: &Board) -> Option<Move> {
+    // Best move alone, for callers that don't care where it came from
+    pub fn best_move_only(&mut self, board: &Board) -> Option<Move> {
+        self.get_move(board).map(|ai_move| ai_move.chess_move)
+    }
+
+    pub fn get_move(&mut self, board: &Board) -> Option<AiMove> {
         let start_time = Instant::now();
         let mut retries = 0;
+        self.last_search = None;
+        if self.deterministic {
+            self.rng = StdRng::seed_from_u64(self.seed);
+        }
+
+        if self.use_book {
+            if let Some(mv) = self.book.get_book_move(board, &mut self.rng) {
+                return Some(AiMove { chess_move: mv, source: MoveSource::Book });
+            }
+        }
+
+        // Weak bots occasionally just play something random
+        if self.blunder_probability > 0.0 && self.rng.gen::<f32>() < self.blunder_probability {
+            let moves = board.legal_moves();
+            if let Some(&mv) = moves.choose(&mut self.rng) {
+                return Some(AiMove { chess_move: mv, source: MoveSource::Random });
+            }
+        }
         
+        self.prepare_search();
+        set_skill_level(self.skill, self.rng.gen());
+        let in_opening = self.in_opening_variety(board);
+        if in_opening {
+            set_variety_margin(self.variety_margin.max(OPENING_VARIETY_MARGIN));
+        }
         while retries < MAX_RETRIES {
             let remaining_time = self.max_time.saturating_sub(start_time.elapsed());
             if remaining_time < Duration::from_millis(100) {
                 break;
             }
 
-            if let Some(mv) = search_best_move(board, remaining_time, Some(DEFAULT_MOVES_LEFT)) {
+            let result = if self.deterministic {
+                search_deterministic(board, self.max_depth)
+            } else {
+                let time_control = self.time_control.unwrap_or(TimeControl::MovesToGo {
+                    remaining: remaining_time,
+                    moves_left: Some(DEFAULT_MOVES_LEFT),
+                });
+                search_best_move(board, time_control, self.max_nodes, self.depth_limit, self.seeded)
+            };
+            if let Some(mut result) = result {
+                // Vary the play between moves that are about as good
+                if !result.near_best.is_empty() {
+                    let candidates: Vec<Move> = std::iter::once(result.best_move)
+                        .chain(result.near_best.iter().copied())
+                        .collect();
+                    let chosen = if in_opening {
+                        // Only the top few, and the best one more often than the rest
+                        let top = &candidates[..candidates.len().min(OPENING_VARIETY_MOVES)];
+                        *top.choose_weighted(&mut self.rng, |&mv| {
+                            if mv == result.best_move { OPENING_BEST_MOVE_WEIGHT } else { 1 }
+                        }).unwrap()
+                    } else {
+                        *candidates.choose(&mut self.rng).unwrap()
+                    };
+                    if chosen != result.best_move {
+                        result.best_move = chosen;
+                        result.pv = vec![chosen];
+                    }
+                }
+                let mv = result.best_move;
                 // Skip moves we know are invalid
                 let move_str = Self::move_to_string(&mv);
                 if self.invalid_moves.contains(&move_str) {
@@ -49,10 +241,15 @@ impl ChessAI {
                     continue;
                 }
 
-                // Try the move on a clone of the board first
-                let mut test_board = board.clone();
-                if test_board.make_move(mv).is_ok() {
-                    return Some(mv);
+                // Try the move on a copy of the board first
+                if board.is_legal(mv) {
+                    let source = if result.from_tt {
+                        MoveSource::TranspositionTable
+                    } else {
+                        MoveSource::Search
+                    };
+                    self.last_search = Some(result);
+                    return Some(AiMove { chess_move: mv, source });
                 } else {
                     // Move was invalid, remember it and try again
                     self.invalid_moves.insert(move_str);
@@ -69,11 +266,8 @@ impl ChessAI {
                 if piece.color == board.current_turn() {
                     for mv in board.get_valid_moves(pos) {
                         let move_str = Self::move_to_string(&mv);
-                        if !self.invalid_moves.contains(&move_str) {
-                            let mut test_board = board.clone();
-                            if test_board.make_move(mv).is_ok() {
-                                return Some(mv);
-                            }
+                        if !self.invalid_moves.contains(&move_str) && board.is_legal(mv) {
+                            return Some(AiMove { chess_move: mv, source: MoveSource::Fallback });
                         }
                     }
                 }
@@ -83,10 +277,215 @@ impl ChessAI {
         None
     }
 
+    // Search details (score, depth, principal variation) behind the last move from get_move.
+    // None if that move came from the fallback scan rather than a search.
+    pub fn last_search(&self) -> Option<&SearchResult> {
+        self.last_search.as_ref()
+    }
+
+    // Searches the position to the given depth without making a move, for analysis
+    pub fn analyze(&self, board: &Board, depth: u8) -> Option<SearchResult> {
+        self.prepare_search();
+        search_to_depth(board, depth, MAX_ANALYSIS_TIME)
+    }
+
+    // Stops whichever search is currently running; it returns its best move so far
+    pub fn stop_search() {
+        stop_search();
+    }
+
+    // How far the running search has got, for showing progress while it thinks
+    pub fn search_progress() -> Option<SearchResult> {
+        search_progress()
+    }
+
+    // Static evaluation in centipawns, normalized to White's point of view
+    // (positive means White is better regardless of whose turn it is)
+    pub fn evaluate(&self, board: &Board) -> i32 {
+        let score = self.evaluator.evaluate_with(board, &self.personality.eval_params());
+        if board.current_turn() == Color::White {
+            score
+        } else {
+            -score
+        }
+    }
+
     pub fn set_max_time(&mut self, duration: Duration) {
         self.max_time = duration;
     }
 
+    // How each search budgets its time, e.g. a fixed time per move or extra
+    // time in sharp positions. max_time still caps the whole of get_move.
+    pub fn set_time_control(&mut self, time_control: TimeControl) {
+        self.time_control = Some(time_control);
+    }
+
+    // Probability (0.0 to 1.0) of playing a random legal move instead of the best one
+    pub fn set_blunder_probability(&mut self, probability: f32) {
+        self.blunder_probability = probability.clamp(0.0, 1.0);
+    }
+
+    pub fn blunder_probability(&self) -> f32 {
+        self.blunder_probability
+    }
+
+    // Caps each search at this many nodes, so strength doesn't depend on how
+    // fast the machine is. The time limit still applies.
+    pub fn set_max_nodes(&mut self, nodes: u64) {
+        self.max_nodes = Some(nodes);
+    }
+
+    pub fn max_nodes(&self) -> Option<u64> {
+        self.max_nodes
+    }
+
+    // Stops each search once it has finished this depth, however much time
+    // is left. Deterministic mode searches to its own fixed depth instead.
+    pub fn set_depth_limit(&mut self, depth: u8) {
+        self.depth_limit = Some(depth);
+    }
+
+    pub fn depth_limit(&self) -> Option<u8> {
+        self.depth_limit
+    }
+
+    // Reseeds the RNG behind every random choice - book moves and blunders -
+    // so games can be reproduced. A seeded AI also searches without the
+    // shared transposition table, which would carry over what earlier
+    // searches (its own or anyone else's) found.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.seeded = true;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.set_seed(seed);
+        self
+    }
+
+    // Replaces the hand-written evaluation the search uses, e.g. with a
+    // trained network. Personalities only affect ClassicalEval's weights.
+    pub fn set_evaluator(&mut self, evaluator: impl Evaluator + 'static) {
+        self.evaluator = Arc::new(evaluator);
+    }
+
+    // Hands this AI's evaluation settings to the search, which reads them
+    // globally. Analysis always gets full strength; get_move applies the
+    // skill level itself.
+    fn prepare_search(&self) {
+        set_eval_params(self.personality.eval_params());
+        set_evaluator(self.evaluator.clone());
+        set_variety_margin(self.variety_margin);
+        set_skill_level(MAX_SKILL_LEVEL, 0);
+        set_move_cache_size(self.move_cache_size);
+    }
+
+    // Has the evaluation's mobility term cache the legal moves of up to
+    // `positions` positions instead of regenerating them every time it meets
+    // one again. Off (0) by default, as the lists take memory.
+    pub fn set_move_cache_size(&mut self, positions: usize) {
+        self.move_cache_size = positions;
+    }
+
+    pub fn move_cache_size(&self) -> usize {
+        self.move_cache_size
+    }
+
+    // Picks at random (with the seeded RNG) among the moves that score within
+    // `margin` centipawns of the best, so the same position doesn't always get
+    // the same answer. 0, the default, always plays the best move. Costs an
+    // extra shallow look at every root move.
+    pub fn set_variety_margin(&mut self, margin: i32) {
+        self.variety_margin = margin.max(0);
+    }
+
+    pub fn variety_margin(&self) -> i32 {
+        self.variety_margin
+    }
+
+    // For the first `plies` plies of the game (counted from the move number,
+    // so a game set up later in the opening gets fewer), searched moves are
+    // picked among the top few within OPENING_VARIETY_MARGIN of the best, the
+    // best one favoured. Unlike the variety margin this only widens the choice
+    // early on, so repeated games go their own ways without the middlegame
+    // play getting any looser. Book moves are already picked by weight.
+    // 0, the default, turns it off.
+    pub fn set_opening_variety(&mut self, plies: u32) {
+        self.opening_variety = plies;
+    }
+
+    pub fn opening_variety(&self) -> u32 {
+        self.opening_variety
+    }
+
+    fn in_opening_variety(&self, board: &Board) -> bool {
+        let ply = (board.fullmove_number() - 1) * 2 + (board.current_turn() == Color::Black) as u32;
+        ply < self.opening_variety
+    }
+
+    // Playing strength from 0 to 20 (the default, full strength). Lower
+    // levels leave some of the moves out of each search and blur the
+    // evaluation, so the AI misses things the way a weaker player would
+    // rather than playing random blunders. The choices come from the seeded RNG.
+    pub fn set_skill(&mut self, skill: u8) {
+        self.skill = skill.min(MAX_SKILL_LEVEL);
+    }
+
+    pub fn skill(&self) -> u8 {
+        self.skill
+    }
+
+    // With the book off every move is searched, from the first one on
+    pub fn set_use_book(&mut self, use_book: bool) {
+        self.use_book = use_book;
+    }
+
+    pub fn uses_book(&self) -> bool {
+        self.use_book
+    }
+
+    pub fn set_personality(&mut self, personality: Personality) {
+        self.personality = personality;
+    }
+
+    pub fn personality(&self) -> Personality {
+        self.personality
+    }
+
+    // Ballpark rating for the limits get_move searches under - the fixed
+    // depth in deterministic mode, otherwise the time per move capped by any
+    // depth and node limits - less the skill level and blunder rate.
+    // Purely informational; the personality isn't counted.
+    pub fn estimated_elo(&self) -> u32 {
+        let mut elo = if self.deterministic {
+            elo_from_table(&ELO_BY_DEPTH, self.max_depth as u64)
+        } else {
+            let think_time = self.think_time_per_move().as_millis() as u64;
+            let mut elo = elo_from_table(&ELO_BY_TIME_MS, think_time);
+            if let Some(depth) = self.depth_limit {
+                elo = elo.min(elo_from_table(&ELO_BY_DEPTH, depth as u64));
+            }
+            elo
+        };
+        if let Some(nodes) = self.max_nodes {
+            elo = elo.min(elo_from_table(&ELO_BY_NODES, nodes));
+        }
+        let skill_penalty = (MAX_SKILL_LEVEL - self.skill) as u32 * ELO_LOST_PER_SKILL_LEVEL;
+        let blunder_penalty = (self.blunder_probability * ELO_LOST_PER_BLUNDER_RATE) as u32;
+        elo.saturating_sub(skill_penalty + blunder_penalty).max(MIN_ELO)
+    }
+
+    // What a typical move gets to think: the time control's share for a quiet
+    // position, within the max_time that caps all of get_move
+    fn think_time_per_move(&self) -> Duration {
+        let time_control = self.time_control.unwrap_or(TimeControl::MovesToGo {
+            remaining: self.max_time,
+            moves_left: Some(DEFAULT_MOVES_LEFT),
+        });
+        time_control.allocate(&Board::new()).min(self.max_time)
+    }
+
     pub fn clear_invalid_moves(&mut self) {
         self.invalid_moves.clear();
     }
@@ -94,10 +493,251 @@ impl ChessAI {
 
 impl Default for ChessAI {
     fn default() -> Self {
-        ChessAI { 
-            max_depth: MIN_DEPTH + 3,
-            max_time: MAX_THINK_TIME,
-            invalid_moves: HashSet::new(),
+        Self::new(DEFAULT_DEPTH)
+    }
+}
+
+// Rating of the last table entry the value reaches, or the first entry below that
+fn elo_from_table(table: &[(u64, u32)], value: u64) -> u32 {
+    table.iter()
+        .take_while(|&&(threshold, _)| threshold <= value)
+        .last()
+        .or(table.first())
+        .map_or(MIN_ELO, |&(_, elo)| elo)
+}
+
+// The settings prepare_search hands over are global, so tests that search or
+// evaluate take turns through this lock, each starting from the defaults
+#[cfg(test)]
+pub(crate) fn lock_engine_for_test() -> std::sync::MutexGuard<'static, ()> {
+    static ENGINE: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let guard = ENGINE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    ChessAI::default().prepare_search();
+    crate::search::clear_search_limits();
+    guard
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess_core::piece::PieceType;
+
+    #[test]
+    fn estimated_elo_follows_the_search_limits() {
+        let mut ai = ChessAI::new(6);
+        ai.set_time_control(TimeControl::FixedPerMove(Duration::from_millis(50)));
+        let quick = ai.estimated_elo();
+        ai.set_time_control(TimeControl::FixedPerMove(Duration::from_secs(1)));
+        let slow = ai.estimated_elo();
+        assert!(quick < slow, "{} vs {}", quick, slow);
+
+        ai.set_depth_limit(2);
+        assert_eq!(ai.estimated_elo(), elo_from_table(&ELO_BY_DEPTH, 2));
+        ai.set_skill(MAX_SKILL_LEVEL - 4);
+        assert_eq!(ai.estimated_elo(), elo_from_table(&ELO_BY_DEPTH, 2) - 4 * ELO_LOST_PER_SKILL_LEVEL);
+
+        // Deterministic mode ignores the clock and searches to its depth
+        let deterministic = ChessAI::new_deterministic();
+        assert_eq!(deterministic.estimated_elo(), elo_from_table(&ELO_BY_DEPTH, deterministic.max_depth as u64));
+    }
+
+    #[test]
+    fn deterministic_ai_repeats_its_move() {
+        let _engine = crate::ai::lock_engine_for_test();
+        let board = Board::from_fen(chess_core::positions::KIWIPETE_FEN).unwrap();
+        let mut ai = ChessAI::new_deterministic();
+        let first = ai.best_move_only(&board).map(|mv| mv.to_uci());
+        let second = ai.best_move_only(&board).map(|mv| mv.to_uci());
+        assert_eq!(first.as_deref(), Some("e2a6"));
+        assert_eq!(second, first);
+    }
+
+    #[test]
+    fn same_seed_plays_the_same_game() {
+        let _engine = crate::ai::lock_engine_for_test();
+        let play = || {
+            let mut ai = ChessAI::new(3).with_seed(42);
+            ai.set_depth_limit(3);
+            ai.set_max_time(Duration::from_secs(60));
+            ai.set_variety_margin(30);
+            let mut board = Board::new();
+            let mut moves = Vec::new();
+            for _ in 0..16 {
+                let mv = ai.best_move_only(&board).unwrap();
+                board.make_move(mv).unwrap();
+                moves.push(mv.to_uci());
+            }
+            moves
+        };
+        assert_eq!(play(), play());
+    }
+
+    // Counts material and nothing else
+    struct MaterialOnly;
+
+    impl Evaluator for MaterialOnly {
+        fn evaluate(&self, board: &Board) -> i32 {
+            let material: i32 = board.occupied()
+                .map(|(_, piece)| {
+                    let value = match piece.piece_type {
+                        PieceType::Pawn => 100,
+                        PieceType::Knight | PieceType::Bishop => 300,
+                        PieceType::Rook => 500,
+                        PieceType::Queen => 900,
+                        PieceType::King => 0,
+                    };
+                    if piece.color == Color::White { value } else { -value }
+                })
+                .sum();
+            if board.current_turn() == Color::White { material } else { -material }
         }
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn plugged_in_evaluator_still_gives_legal_moves() {
+        let _engine = lock_engine_for_test();
+        let mut ai = ChessAI::new_deterministic();
+        ai.set_use_book(false);
+        ai.set_evaluator(MaterialOnly);
+        for board in [chess_core::positions::start(), chess_core::positions::kiwipete(), chess_core::positions::en_passant()] {
+            let mv = ai.best_move_only(&board).unwrap();
+            assert!(board.is_legal(mv), "{} in {}", mv.to_uci(), board.to_fen());
+        }
+    }
+
+    #[test]
+    fn skill_zero_plays_weaker_than_full_skill() {
+        let _engine = lock_engine_for_test();
+        let player = |skill| {
+            let mut ai = ChessAI::new(2).with_seed(7);
+            ai.set_use_book(false);
+            ai.set_depth_limit(2);
+            ai.set_max_time(Duration::from_secs(60));
+            ai.set_skill(skill);
+            ai
+        };
+        // A game with each color, scored by the material the full-skill side
+        // is up at the end, or by the result if it's over before then
+        for strong_color in [Color::White, Color::Black] {
+            let (mut strong, mut weak) = (player(MAX_SKILL_LEVEL), player(0));
+            let mut board = Board::new();
+            for _ in 0..40 {
+                if board.is_game_over() {
+                    break;
+                }
+                let ai = if board.current_turn() == strong_color { &mut strong } else { &mut weak };
+                let mv = ai.best_move_only(&board).unwrap();
+                board.make_move(mv).unwrap();
+            }
+            match board.result() {
+                Some(result) => assert_eq!(result, chess_core::GameResult::win_for(strong_color)),
+                None => {
+                    let material = MaterialOnly.evaluate(&board);
+                    let lead = if board.current_turn() == strong_color { material } else { -material };
+                    assert!(lead >= 300, "{:?} only {} up: {}", strong_color, lead, board.to_fen());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn queen_and_king_mate_the_lone_king() {
+        let _engine = lock_engine_for_test();
+        let mut ai = ChessAI::new_deterministic();
+        ai.set_use_book(false);
+        let mut board = Board::from_fen("8/8/8/4k3/8/8/8/KQ6 w - - 0 1").unwrap();
+        for _ in 0..80 {
+            if board.is_game_over() {
+                break;
+            }
+            let mv = ai.best_move_only(&board).unwrap();
+            board.make_move(mv).unwrap();
+        }
+        assert_eq!(board.result(), Some(chess_core::GameResult::WhiteWins), "{}", board.to_fen());
+    }
+
+    #[test]
+    fn start_position_is_answered_from_the_book() {
+        let _engine = lock_engine_for_test();
+        let mut ai = ChessAI::new(DEFAULT_DEPTH);
+        let ai_move = ai.get_move(&Board::new()).unwrap();
+        assert_eq!(ai_move.source, MoveSource::Book);
+        assert!(Board::new().is_legal(ai_move.chess_move));
+    }
+
+    #[test]
+    fn without_the_book_the_first_move_is_searched() {
+        let _engine = lock_engine_for_test();
+        let mut ai = ChessAI::new_deterministic();
+        ai.set_use_book(false);
+        let ai_move = ai.get_move(&Board::new()).unwrap();
+        assert_eq!(ai_move.source, MoveSource::Search);
+        assert!(ai.last_search().is_some());
+    }
+
+    #[test]
+    fn personalities_choose_different_moves() {
+        let _engine = lock_engine_for_test();
+        let positions = [
+            chess_core::positions::START_FEN,
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        ];
+        let choices = |personality| -> Vec<Option<Move>> {
+            let mut ai = ChessAI::new_deterministic();
+            ai.set_use_book(false);
+            ai.set_personality(personality);
+            positions.iter().map(|fen| ai.best_move_only(&Board::from_fen(fen).unwrap())).collect()
+        };
+        // Each style departs from the balanced choice somewhere
+        let balanced = choices(Personality::Balanced);
+        for personality in [Personality::Aggressive, Personality::Positional, Personality::Defensive] {
+            assert_ne!(choices(personality), balanced, "{}", personality.name());
+        }
+    }
+
+    // An AI for the variety tests: bookless and quick, so only the seed
+    // decides between its choices
+    fn quick_ai(seed: u64) -> ChessAI {
+        let mut ai = ChessAI::new(2).with_seed(seed);
+        ai.set_use_book(false);
+        ai.set_depth_limit(2);
+        ai.set_max_time(Duration::from_secs(60));
+        ai
+    }
+
+    #[test]
+    fn variety_margin_varies_the_first_move() {
+        let _engine = lock_engine_for_test();
+        let board = Board::new();
+        let first_moves: HashSet<String> = (0..8)
+            .map(|seed| {
+                let mut ai = quick_ai(seed);
+                ai.set_variety_margin(30);
+                ai.best_move_only(&board).unwrap().to_uci()
+            })
+            .collect();
+        assert!(first_moves.len() > 1, "{:?}", first_moves);
+
+        // Without the margin every seed plays the same
+        let best: HashSet<String> = (0..3).map(|seed| quick_ai(seed).best_move_only(&board).unwrap().to_uci()).collect();
+        assert_eq!(best.len(), 1);
+    }
+
+    #[test]
+    fn opening_variety_varies_the_third_move() {
+        let _engine = lock_engine_for_test();
+        let third_moves: HashSet<String> = (0..8)
+            .map(|seed| {
+                let mut ai = quick_ai(seed);
+                ai.set_opening_variety(6);
+                let mut board = Board::new();
+                for _ in 0..4 {
+                    let mv = ai.best_move_only(&board).unwrap();
+                    board.make_move(mv).unwrap();
+                }
+                ai.best_move_only(&board).unwrap().to_uci()
+            })
+            .collect();
+        assert!(third_moves.len() > 1, "{:?}", third_moves);
+    }
+}
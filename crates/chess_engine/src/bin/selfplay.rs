@@ -0,0 +1,257 @@
+// Headless self-play runner: plays engine games without the UI and writes them as PGN
+//
+// --depth caps each search at depth D; the time limit still applies.
+// --opponent-skill weakens the engine opponent to skill level L (0-20), to
+// check that lower levels really play worse.
+// --opening-variety has the engines pick among their top few moves for the
+// first P plies, so the games don't all follow the same line.
+// With --seed the book choices and the random opponent repeat from run to
+// run; searches against the clock can still vary.
+use std::fs::File;
+use std::io::{self, Write};
+use std::time::Duration;
+use chess_core::{Board, Color, Move, GameResult, DrawReason};
+use chess_engine::ChessAI;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+const DEFAULT_GAMES: u32 = 10;
+const DEFAULT_DEPTH: u8 = 4;
+const DEFAULT_TIME_MS: u64 = 3000;
+const DEFAULT_MAX_MOVES: u32 = 200;  // Full moves before a game is adjudicated a draw
+const DEFAULT_PGN_PATH: &str = "selfplay.pgn";
+
+const USAGE: &str = "\
+Usage: selfplay [--games N] [--depth D] [--time-ms T] [--max-moves M]
+                [--opponent engine|random] [--pgn FILE] [--seed S]
+                [--opponent-skill L] [--opening-variety P]";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Opponent {
+    Engine,
+    Random,
+}
+
+struct Config {
+    games: u32,
+    depth: u8,
+    time: Duration,
+    max_moves: u32,
+    opponent: Opponent,
+    pgn_path: String,
+    seed: Option<u64>,
+    opponent_skill: Option<u8>,
+    opening_variety: u32,
+}
+
+struct Player {
+    name: String,
+    ai: Option<ChessAI>,  // None plays random legal moves
+    rng: StdRng,
+}
+
+impl Player {
+    fn new(kind: Opponent, config: &Config, skill: Option<u8>) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        match kind {
+            Opponent::Engine => {
+                let mut ai = ChessAI::new(config.depth);
+                ai.set_depth_limit(config.depth);
+                ai.set_max_time(config.time);
+                ai.set_opening_variety(config.opening_variety);
+                if let Some(seed) = config.seed {
+                    ai.set_seed(seed);
+                }
+                let name = match skill {
+                    Some(skill) => {
+                        ai.set_skill(skill);
+                        format!("Engine (skill {})", ai.skill())
+                    }
+                    None => "Engine".to_string(),
+                };
+                Player { name, ai: Some(ai), rng }
+            }
+            Opponent::Random => Player { name: "Random".to_string(), ai: None, rng },
+        }
+    }
+
+    fn choose_move(&mut self, board: &Board) -> Option<Move> {
+        match self.ai.as_mut() {
+            Some(ai) => ai.best_move_only(board),
+            None => board.legal_moves().choose(&mut self.rng).copied(),
+        }
+    }
+}
+
+// None when the usage was asked for
+fn parse_args() -> Result<Option<Config>, String> {
+    let mut config = Config {
+        games: DEFAULT_GAMES,
+        depth: DEFAULT_DEPTH,
+        time: Duration::from_millis(DEFAULT_TIME_MS),
+        max_moves: DEFAULT_MAX_MOVES,
+        opponent: Opponent::Engine,
+        pgn_path: DEFAULT_PGN_PATH.to_string(),
+        seed: None,
+        opponent_skill: None,
+        opening_variety: 0,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        if flag == "--help" || flag == "-h" {
+            return Ok(None);
+        }
+        let value = args.next().ok_or_else(|| format!("Missing value for {}", flag))?;
+        let invalid = |_| format!("Invalid value for {}: {}", flag, value);
+        match flag.as_str() {
+            "--games" => config.games = value.parse().map_err(invalid)?,
+            "--depth" => config.depth = value.parse().map_err(invalid)?,
+            "--time-ms" => config.time = Duration::from_millis(value.parse().map_err(invalid)?),
+            "--max-moves" => config.max_moves = value.parse().map_err(invalid)?,
+            "--opponent" => config.opponent = match value.as_str() {
+                "engine" => Opponent::Engine,
+                "random" => Opponent::Random,
+                _ => return Err(format!("Unknown opponent: {}", value)),
+            },
+            "--pgn" => config.pgn_path = value,
+            "--seed" => config.seed = Some(value.parse().map_err(invalid)?),
+            "--opponent-skill" => config.opponent_skill = Some(value.parse().map_err(invalid)?),
+            "--opening-variety" => config.opening_variety = value.parse().map_err(invalid)?,
+            _ => return Err(format!("Unknown argument: {}", flag)),
+        }
+    }
+
+    Ok(Some(config))
+}
+
+// Plays a single game, returning the result and the SAN move list
+fn play_game(white: &mut Player, black: &mut Player, max_moves: u32) -> (GameResult, Vec<String>) {
+    let mut board = Board::new();
+    let mut moves = Vec::new();
+
+    loop {
+        if let Some(result) = board.result() {
+            return (result, moves);
+        }
+        if moves.len() as u32 >= max_moves * 2 {
+            return (GameResult::Draw(DrawReason::Adjudicated), moves);
+        }
+
+        let player = match board.current_turn() {
+            Color::White => &mut *white,
+            Color::Black => &mut *black,
+        };
+        let chess_move = match player.choose_move(&board) {
+            Some(mv) => mv,
+            None => return (GameResult::Draw(DrawReason::Adjudicated), moves),
+        };
+
+        let san = chess_move.to_san(&board);
+        if board.make_move(chess_move).is_err() {
+            // An illegal move forfeits the game
+            let winner = match board.current_turn() {
+                Color::White => Color::Black,
+                Color::Black => Color::White,
+            };
+            return (GameResult::win_for(winner), moves);
+        }
+        moves.push(san);
+    }
+}
+
+fn write_pgn(
+    out: &mut impl Write,
+    round: u32,
+    white: &str,
+    black: &str,
+    result: GameResult,
+    moves: &[String],
+) -> io::Result<()> {
+    writeln!(out, "[Event \"Self-play\"]")?;
+    writeln!(out, "[Site \"?\"]")?;
+    writeln!(out, "[Date \"????.??.??\"]")?;
+    writeln!(out, "[Round \"{}\"]", round)?;
+    writeln!(out, "[White \"{}\"]", white)?;
+    writeln!(out, "[Black \"{}\"]", black)?;
+    writeln!(out, "[Result \"{}\"]", result.to_pgn())?;
+    writeln!(out)?;
+
+    // Movetext, wrapped to stay under 80 columns
+    let mut line = String::new();
+    let tokens = moves.chunks(2).enumerate()
+        .flat_map(|(i, pair)| {
+            std::iter::once(format!("{}.", i + 1)).chain(pair.iter().cloned())
+        })
+        .chain(std::iter::once(result.to_pgn().to_string()));
+    for token in tokens {
+        if !line.is_empty() && line.len() + token.len() + 1 > 79 {
+            writeln!(out, "{}", line)?;
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    writeln!(out, "{}", line)?;
+    writeln!(out)
+}
+
+fn main() {
+    let config = match parse_args() {
+        Ok(Some(config)) => config,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let mut pgn = match File::create(&config.pgn_path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Could not create {}: {}", config.pgn_path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut engine = Player::new(Opponent::Engine, &config, None);
+    let mut opponent = Player::new(config.opponent, &config, config.opponent_skill);
+
+    // Tally from the engine's point of view (White's in pure self-play)
+    let (mut wins, mut draws, mut losses) = (0, 0, 0);
+
+    for round in 1..=config.games {
+        // Alternate colors against a different opponent so both sides get tested
+        let mirror_match = config.opponent == Opponent::Engine && config.opponent_skill.is_none();
+        let engine_is_white = mirror_match || round % 2 == 1;
+        let (white, black) = if engine_is_white {
+            (&mut engine, &mut opponent)
+        } else {
+            (&mut opponent, &mut engine)
+        };
+
+        let (white_name, black_name) = (white.name.clone(), black.name.clone());
+        let (result, moves) = play_game(white, black, config.max_moves);
+
+        match (result, engine_is_white) {
+            (GameResult::Draw(_), _) => draws += 1,
+            (GameResult::WhiteWins, true) | (GameResult::BlackWins, false) => wins += 1,
+            _ => losses += 1,
+        }
+
+        if let Err(e) = write_pgn(&mut pgn, round, &white_name, &black_name, result, &moves) {
+            eprintln!("Failed to write PGN: {}", e);
+            std::process::exit(1);
+        }
+        eprintln!("Game {}: {} ({} plies)", round, result.to_pgn(), moves.len());
+    }
+
+    eprintln!("Result: +{} ={} -{} (written to {})", wins, draws, losses, config.pgn_path);
+}
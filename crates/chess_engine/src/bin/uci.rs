@@ -0,0 +1,166 @@
+// Minimal UCI front end, so the engine can be driven from a chess GUI
+//
+// Supports: uci, isready, ucinewgame, position [startpos | fen <fen>] [moves ...],
+//           go [infinite | depth D | movetime T | nodes N | wtime/btime ...], stop, quit
+//
+// Search diagnostics go to stderr; stdout only carries protocol output.
+use std::io::{self, BufRead};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use chess_core::{Board, Color, Move};
+use chess_engine::search::{search_best_move, search_infinite, search_to_depth, stop_search};
+use chess_engine::{SearchResult, TimeControl};
+
+const ENGINE_NAME: &str = "rust-chess-engine";
+const DEFAULT_MOVETIME_MS: u64 = 3000;
+const FIXED_SEARCH_TIME: Duration = Duration::from_secs(60);  // Cap for depth/node limited searches
+
+// Search running in the background. Infinite searches leave printing
+// bestmove to the stop command; all others print it when they finish.
+struct RunningSearch {
+    handle: JoinHandle<Option<SearchResult>>,
+    infinite: bool,
+}
+
+enum SearchLimit {
+    Infinite,
+    Depth(u8),
+    Nodes(u64),
+    Time(TimeControl),
+}
+
+fn parse_position(tokens: &[&str]) -> Result<Board, String> {
+    let moves_at = tokens.iter().position(|&t| t == "moves").unwrap_or(tokens.len());
+    let mut board = match tokens.first() {
+        Some(&"startpos") => Board::new(),
+        Some(&"fen") => Board::from_fen(&tokens[1..moves_at].join(" ")).map_err(|e| e.to_string())?,
+        _ => return Err("expected startpos or fen".to_string()),
+    };
+
+    for &text in tokens.iter().skip(moves_at + 1) {
+        let mv = Move::from_uci(text, &board).ok_or_else(|| format!("bad move {}", text))?;
+        board.make_move(mv).map_err(|_| format!("illegal move {}", text))?;
+    }
+    Ok(board)
+}
+
+fn parse_go(tokens: &[&str], side: Color) -> SearchLimit {
+    let value = |name: &str| -> Option<u64> {
+        let i = tokens.iter().position(|&t| t == name)?;
+        tokens.get(i + 1)?.parse().ok()
+    };
+
+    if tokens.contains(&"infinite") {
+        return SearchLimit::Infinite;
+    }
+    if let Some(depth) = value("depth") {
+        return SearchLimit::Depth(depth.min(u8::MAX as u64) as u8);
+    }
+    if let Some(nodes) = value("nodes") {
+        return SearchLimit::Nodes(nodes);
+    }
+    if let Some(ms) = value("movetime") {
+        return SearchLimit::Time(TimeControl::FixedPerMove(Duration::from_millis(ms)));
+    }
+
+    let clock = match side {
+        Color::White => value("wtime"),
+        Color::Black => value("btime"),
+    };
+    match clock {
+        Some(ms) => SearchLimit::Time(TimeControl::MovesToGo {
+            remaining: Duration::from_millis(ms),
+            moves_left: value("movestogo").map(|n| n.max(1) as u32),
+        }),
+        None => SearchLimit::Time(TimeControl::FixedPerMove(Duration::from_millis(DEFAULT_MOVETIME_MS))),
+    }
+}
+
+fn print_result(result: Option<&SearchResult>) {
+    match result {
+        Some(result) => {
+            let pv: Vec<String> = result.pv.iter().map(|m| m.to_uci()).collect();
+            println!("info depth {} score cp {} pv {}", result.depth, result.score, pv.join(" "));
+            println!("bestmove {}", result.best_move.to_uci());
+        }
+        // No legal moves - UCI still wants an answer
+        None => println!("bestmove 0000"),
+    }
+}
+
+fn start_search(board: Board, limit: SearchLimit) -> RunningSearch {
+    let infinite = matches!(limit, SearchLimit::Infinite);
+    let handle = thread::spawn(move || {
+        let result = match limit {
+            SearchLimit::Infinite => search_infinite(&board),
+            SearchLimit::Depth(depth) => search_to_depth(&board, depth, FIXED_SEARCH_TIME),
+            SearchLimit::Nodes(nodes) => {
+                search_best_move(&board, TimeControl::FixedPerMove(FIXED_SEARCH_TIME), Some(nodes), None, false)
+            }
+            SearchLimit::Time(time_control) => search_best_move(&board, time_control, None, None, false),
+        };
+        if !infinite {
+            print_result(result.as_ref());
+        }
+        result
+    });
+    RunningSearch { handle, infinite }
+}
+
+// Stops the running search, if any, and waits for it to finish
+fn finish_search(search: Option<RunningSearch>) {
+    let Some(search) = search else {
+        return;
+    };
+
+    // The search clears the stop flag as it starts, so keep asking until it's done
+    while !search.handle.is_finished() {
+        stop_search();
+        thread::sleep(Duration::from_millis(1));
+    }
+    let result = search.handle.join().ok().flatten();
+    if search.infinite {
+        print_result(result.as_ref());
+    }
+}
+
+fn main() {
+    let mut board = Board::new();
+    let mut search: Option<RunningSearch> = None;
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first().copied() {
+            Some("uci") => {
+                println!("id name {}", ENGINE_NAME);
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("ucinewgame") => {
+                finish_search(search.take());
+                board = Board::new();
+            }
+            Some("position") => {
+                finish_search(search.take());
+                match parse_position(&tokens[1..]) {
+                    Ok(parsed) => board = parsed,
+                    Err(message) => println!("info string {}", message),
+                }
+            }
+            Some("go") => {
+                finish_search(search.take());
+                let limit = parse_go(&tokens[1..], board.current_turn());
+                search = Some(start_search(board.clone(), limit));
+            }
+            Some("stop") => finish_search(search.take()),
+            Some("quit") => {
+                finish_search(search.take());
+                break;
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use chess_core::{Board, Position, Color, PieceType};
+use chess_core::{Board, Position, Color, PieceType, GamePhase, MoveCache};
+use std::sync::{Arc, Mutex, RwLock};
+use once_cell::sync::Lazy;
 
 const PAWN_VALUE: i32 = 100;
 const KNIGHT_VALUE: i32 = 320;
@@ -11,23 +13,190 @@ const DOUBLED_PAWN_PENALTY: i32 = -10;
 const ISOLATED_PAWN_PENALTY: i32 = -20;
 const PASSED_PAWN_BONUS: i32 = 30;
 const BISHOP_PAIR_BONUS: i32 = 30;
-const MOBILITY_MULTIPLIER: i32 = 5;
+const OPEN_FILE_BATTERY_BONUS: i32 = 25;       // Doubled rooks or rook + queen, no pawns on the file
+const HALF_OPEN_FILE_BATTERY_BONUS: i32 = 15;  // Same, with only enemy pawns on the file
+
+// Pure opposite-colored bishop endings are notoriously drawish
+const OPPOSITE_BISHOPS_SCALE_PERCENT: i32 = 25;
+
+// Material lead needed in an endgame before driving the enemy king to the edge
+const MOP_UP_MARGIN: i32 = 400;
+const MOP_UP_CENTER_WEIGHT: i32 = 20;  // Per step the losing king is from the center
+const MOP_UP_PROXIMITY_WEIGHT: i32 = 10;  // Per step closer the winning king gets
+
+// King and pawn against king: a won ending is worth nearly a queen, plus a
+// little per rank so the search keeps pushing; a drawn one is worth nothing
+const KPK_WIN_SCORE: i32 = 700;
+const KPK_ADVANCE_BONUS: i32 = 20;
+
+// Pieces this close to the enemy king (in king moves) count as attacking it
+const KING_ATTACK_DISTANCE: u8 = 2;
+
+// Tunable weights. The presets are what give the AI a personality.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalParams {
+    pub pawn_structure_percent: i32,  // Scales the doubled/isolated/passed pawn terms
+    pub mobility_multiplier: i32,     // Per legal move of the side to move
+    pub king_attack_bonus: i32,       // Per minor or heavy piece near the enemy king
+    pub king_shield_bonus: i32,       // Per pawn right in front of our own king
+    pub contempt: i32,                // What a draw costs the side the AI plays, in centipawns
+}
+
+impl EvalParams {
+    pub const BALANCED: EvalParams = EvalParams {
+        pawn_structure_percent: 100,
+        mobility_multiplier: 5,
+        king_attack_bonus: 0,
+        king_shield_bonus: 0,
+        contempt: 0,
+    };
+
+    pub const AGGRESSIVE: EvalParams = EvalParams {
+        pawn_structure_percent: 80,
+        mobility_multiplier: 7,
+        king_attack_bonus: 15,
+        king_shield_bonus: 0,
+        contempt: 30,
+    };
+
+    pub const POSITIONAL: EvalParams = EvalParams {
+        pawn_structure_percent: 150,
+        mobility_multiplier: 8,
+        king_attack_bonus: 0,
+        king_shield_bonus: 5,
+        contempt: 10,
+    };
+
+    pub const DEFENSIVE: EvalParams = EvalParams {
+        pawn_structure_percent: 100,
+        mobility_multiplier: 4,
+        king_attack_bonus: 0,
+        king_shield_bonus: 20,
+        contempt: -15,
+    };
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self::BALANCED
+    }
+}
+
+// Weights used by evaluate_position. Like the search's node limit this is
+// global, so whoever searches sets it first.
+static EVAL_PARAMS: RwLock<EvalParams> = RwLock::new(EvalParams::BALANCED);
+
+pub fn set_eval_params(params: EvalParams) {
+    *EVAL_PARAMS.write().unwrap() = params;
+}
+
+pub fn eval_params() -> EvalParams {
+    *EVAL_PARAMS.read().unwrap()
+}
+
+// Legal move lists the mobility term looks up instead of regenerating, for
+// positions the search keeps coming back to. Off (None) unless asked for.
+static MOVE_CACHE: Mutex<Option<MoveCache>> = Mutex::new(None);
+
+// Caches the move lists of up to `capacity` positions; 0 turns the cache off.
+// Keeps what's cached when the capacity doesn't change.
+pub fn set_move_cache_size(capacity: usize) {
+    let mut cache = MOVE_CACHE.lock().unwrap();
+    match capacity {
+        0 => *cache = None,
+        _ if cache.as_ref().is_some_and(|c| c.capacity() == capacity) => {}
+        _ => *cache = Some(MoveCache::new(capacity)),
+    }
+}
+
+// Anything that can score a position: the hand-written evaluation below, or
+// something else plugged into ChessAI::set_evaluator (a trained network, say)
+pub trait Evaluator: Send + Sync {
+    // Centipawns from the side to move's point of view
+    fn evaluate(&self, board: &Board) -> i32;
+
+    // The same with a given set of weights rather than the global ones.
+    // Evaluators that don't use EvalParams can leave this as it is.
+    fn evaluate_with(&self, board: &Board, _params: &EvalParams) -> i32 {
+        self.evaluate(board)
+    }
+}
+
+// The built-in evaluation: material, pawn structure, mobility and the rest,
+// weighted by the current EvalParams
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClassicalEval;
+
+impl Evaluator for ClassicalEval {
+    fn evaluate(&self, board: &Board) -> i32 {
+        evaluate_position(board)
+    }
+
+    fn evaluate_with(&self, board: &Board, params: &EvalParams) -> i32 {
+        evaluate_position_with(board, params)
+    }
+}
+
+// Evaluator the search scores positions with. Global like EVAL_PARAMS, and
+// set the same way before each search.
+static EVALUATOR: Lazy<RwLock<Arc<dyn Evaluator>>> = Lazy::new(|| RwLock::new(Arc::new(ClassicalEval)));
+
+pub fn set_evaluator(evaluator: Arc<dyn Evaluator>) {
+    *EVALUATOR.write().unwrap() = evaluator;
+}
+
+// Scores a position with the current evaluator
+pub fn evaluate(board: &Board) -> i32 {
+    EVALUATOR.read().unwrap().evaluate(board)
+}
 
 pub fn evaluate_position(board: &Board) -> i32 {
+    evaluate_position_with(board, &eval_params())
+}
+
+pub fn evaluate_position_with(board: &Board, params: &EvalParams) -> i32 {
     let mut score = 0;
     
+    // However much material is left, nobody can win these
+    if is_known_draw(board) {
+        return 0;
+    }
+    
+    // King and pawn against king follows exact rules instead
+    if board.piece_map().len() == 3 &&
+       matches!(board.material_signature().as_str(), "KPvK" | "KvKP") {
+        let score = eval_kpk(board);
+        return if board.current_turn() == Color::White { score } else { -score };
+    }
+    
     // Material and basic positional evaluation
     score += evaluate_material(board);
     
     // Pawn structure
-    score += evaluate_pawn_structure(board);
+    score += evaluate_pawn_structure(board) * params.pawn_structure_percent / 100;
     
     // Piece mobility
-    score += evaluate_mobility(board);
+    score += evaluate_mobility(board, params.mobility_multiplier);
+    
+    // King attack and shelter, only for personalities that weigh them
+    if params.king_attack_bonus != 0 || params.king_shield_bonus != 0 {
+        score += evaluate_king_safety(board, params);
+    }
     
     // Bishop pair bonus
     score += evaluate_bishop_pair(board);
     
+    // Heavy pieces lined up on open files
+    score += evaluate_batteries(board);
+    
+    // Herd a lone defender towards mate in won endgames
+    score += mop_up_eval(board);
+    
+    // Drawish endgames
+    if is_opposite_colored_bishop_ending(board) {
+        score = score * OPPOSITE_BISHOPS_SCALE_PERCENT / 100;
+    }
+    
     // Return score relative to current player
     if board.current_turn() == Color::White {
         score
@@ -36,28 +205,33 @@ pub fn evaluate_position(board: &Board) -> i32 {
     }
 }
 
+// Positions that are drawn no matter how they're played: insufficient material,
+// plus K+N+N vs K, where mate exists but can't be forced
+pub fn is_known_draw(board: &Board) -> bool {
+    if board.has_insufficient_material() {
+        return true;
+    }
+    
+    matches!(board.material_signature().as_str(), "KNNvK" | "KvKNN")
+}
+
 fn evaluate_material(board: &Board) -> i32 {
     let mut score = 0;
     
-    for rank in 1..=8 {
-        for file in 1..=8 {
-            let pos = Position { rank, file };
-            if let Some(piece) = board.get_piece(pos) {
-                let piece_value = match piece.piece_type {
-                    PieceType::Pawn => PAWN_VALUE,
-                    PieceType::Knight => KNIGHT_VALUE,
-                    PieceType::Bishop => BISHOP_VALUE,
-                    PieceType::Rook => ROOK_VALUE,
-                    PieceType::Queen => QUEEN_VALUE,
-                    PieceType::King => 0, // King's value not counted in material
-                };
-                
-                if piece.color == Color::White {
-                    score += piece_value;
-                } else {
-                    score -= piece_value;
-                }
-            }
+    for (_, piece) in board.occupied() {
+        let piece_value = match piece.piece_type {
+            PieceType::Pawn => PAWN_VALUE,
+            PieceType::Knight => KNIGHT_VALUE,
+            PieceType::Bishop => BISHOP_VALUE,
+            PieceType::Rook => ROOK_VALUE,
+            PieceType::Queen => QUEEN_VALUE,
+            PieceType::King => 0, // King's value not counted in material
+        };
+        
+        if piece.color == Color::White {
+            score += piece_value;
+        } else {
+            score -= piece_value;
         }
     }
     
@@ -159,22 +333,57 @@ fn evaluate_pawn_structure(board: &Board) -> i32 {
     score
 }
 
-fn evaluate_mobility(board: &Board) -> i32 {
+// Legal moves of the side to move, from the move cache when it's on
+fn evaluate_mobility(board: &Board, multiplier: i32) -> i32 {
+    let cached = MOVE_CACHE.lock().unwrap().as_mut()
+        .map(|cache| cache.with_legal_moves(board, |moves| moves.len()));
+    let moves = cached.unwrap_or_else(|| board.legal_moves().len());
+    let mobility = moves as i32 * multiplier;
+    
+    if board.current_turn() == Color::White {
+        mobility
+    } else {
+        -mobility
+    }
+}
+
+// Pieces closing in on the enemy king, and pawns sheltering our own
+fn evaluate_king_safety(board: &Board, params: &EvalParams) -> i32 {
+    let kings: Vec<(Position, Color)> = board.occupied()
+        .filter(|(_, p)| p.piece_type == PieceType::King)
+        .map(|(pos, p)| (pos, p.color))
+        .collect();
     let mut score = 0;
     
-    for rank in 1..=8 {
-        for file in 1..=8 {
-            let pos = Position { rank, file };
-            if let Some(piece) = board.get_piece(pos) {
-                let moves = board.get_valid_moves(pos);
-                let mobility = (moves.len() as i32) * MOBILITY_MULTIPLIER;
-                
-                if piece.color == Color::White {
-                    score += mobility;
-                } else {
-                    score -= mobility;
-                }
-            }
+    for &(king_pos, king_color) in &kings {
+        let mut side_score = 0;
+        
+        // Enemy pieces near this king count for the attacker
+        let attackers = board.occupied()
+            .filter(|(pos, p)| {
+                p.color != king_color &&
+                    !matches!(p.piece_type, PieceType::Pawn | PieceType::King) &&
+                    pos.rank.abs_diff(king_pos.rank).max(pos.file.abs_diff(king_pos.file)) <= KING_ATTACK_DISTANCE
+            })
+            .count() as i32;
+        side_score -= attackers * params.king_attack_bonus;
+        
+        // Our own pawns on the three squares in front of the king
+        let forward = if king_color == Color::White { king_pos.rank + 1 } else { king_pos.rank.wrapping_sub(1) };
+        if (1..=8).contains(&forward) {
+            let shield = (king_pos.file.saturating_sub(1).max(1)..=(king_pos.file + 1).min(8))
+                .filter(|&file| {
+                    board.get_piece(Position { rank: forward, file })
+                        .is_some_and(|p| p.piece_type == PieceType::Pawn && p.color == king_color)
+                })
+                .count() as i32;
+            side_score += shield * params.king_shield_bonus;
+        }
+        
+        if king_color == Color::White {
+            score += side_score;
+        } else {
+            score -= side_score;
         }
     }
     
@@ -185,17 +394,12 @@ fn evaluate_bishop_pair(board: &Board) -> i32 {
     let mut white_bishops = 0;
     let mut black_bishops = 0;
     
-    for rank in 1..=8 {
-        for file in 1..=8 {
-            let pos = Position { rank, file };
-            if let Some(piece) = board.get_piece(pos) {
-                if piece.piece_type == PieceType::Bishop {
-                    if piece.color == Color::White {
-                        white_bishops += 1;
-                    } else {
-                        black_bishops += 1;
-                    }
-                }
+    for (_, piece) in board.occupied() {
+        if piece.piece_type == PieceType::Bishop {
+            if piece.color == Color::White {
+                white_bishops += 1;
+            } else {
+                black_bishops += 1;
             }
         }
     }
@@ -209,4 +413,299 @@ fn evaluate_bishop_pair(board: &Board) -> i32 {
     }
     
     score
-} 
\ No newline at end of file
+}
+
+// In a won endgame, rewards pushing the losing king to the edge and bringing
+// the winning king up to help, so the search can find its way to mate instead
+// of shuffling. Zero unless it's an endgame and one side is well ahead.
+pub fn mop_up_eval(board: &Board) -> i32 {
+    if board.game_phase() != GamePhase::Endgame {
+        return 0;
+    }
+    let material = evaluate_material(board);
+    if material.abs() < MOP_UP_MARGIN {
+        return 0;
+    }
+    let (winner, loser) = if material > 0 {
+        (Color::White, Color::Black)
+    } else {
+        (Color::Black, Color::White)
+    };
+    
+    let king_of = |color: Color| {
+        board.occupied()
+            .find(|(_, p)| p.piece_type == PieceType::King && p.color == color)
+            .map(|(pos, _)| pos)
+    };
+    let (Some(winning_king), Some(losing_king)) = (king_of(winner), king_of(loser)) else {
+        return 0;
+    };
+    
+    // 0 on the four center squares up to 6 in the corners
+    let center_distance = |pos: Position| {
+        let from_center = |x: u8| (4 - x as i32).max(x as i32 - 5);
+        from_center(pos.file) + from_center(pos.rank)
+    };
+    let kings_distance = winning_king.file.abs_diff(losing_king.file) as i32 +
+        winning_king.rank.abs_diff(losing_king.rank) as i32;
+    
+    let score = center_distance(losing_king) * MOP_UP_CENTER_WEIGHT +
+        (14 - kings_distance) * MOP_UP_PROXIMITY_WEIGHT;
+    if winner == Color::White { score } else { -score }
+}
+
+// King and pawn against king, by the rule of the square, key squares and the
+// opposition. Positive when White is winning; zero if it's a draw or the
+// material isn't KPvK.
+pub fn eval_kpk(board: &Board) -> i32 {
+    let Some((pawn, strong)) = board.occupied()
+        .find(|(_, p)| p.piece_type == PieceType::Pawn)
+        .map(|(pos, p)| (pos, p.color)) else {
+        return 0;
+    };
+    let king_of = |color: Color| {
+        board.occupied()
+            .find(|(_, p)| p.piece_type == PieceType::King && p.color == color)
+            .map(|(pos, _)| pos)
+    };
+    let weak = if strong == Color::White { Color::Black } else { Color::White };
+    let (Some(strong_king), Some(weak_king)) = (king_of(strong), king_of(weak)) else {
+        return 0;
+    };
+    
+    // Look at it from White's side, with the pawn running up the board
+    let normalize = |pos: Position| {
+        if strong == Color::White { pos } else { Position { rank: 9 - pos.rank, file: pos.file } }
+    };
+    let (pawn, strong_king, weak_king) = (normalize(pawn), normalize(strong_king), normalize(weak_king));
+    
+    if !kpk_is_won(pawn, strong_king, weak_king, board.current_turn() == strong) {
+        return 0;
+    }
+    let score = KPK_WIN_SCORE + pawn.rank as i32 * KPK_ADVANCE_BONUS;
+    if strong == Color::White { score } else { -score }
+}
+
+fn king_distance(a: Position, b: Position) -> u8 {
+    a.rank.abs_diff(b.rank).max(a.file.abs_diff(b.file))
+}
+
+// Squares that win for the attacker as soon as its king stands on one
+fn kpk_key_squares(pawn: Position) -> Vec<Position> {
+    // A rook pawn only queens if the king gets to the corner's neighbour file
+    if pawn.file == 1 || pawn.file == 8 {
+        let file = if pawn.file == 1 { 2 } else { 7 };
+        return vec![Position { rank: 7, file }, Position { rank: 8, file }];
+    }
+    // Two ranks ahead of the pawn, and from the fifth rank on one rank ahead too
+    let ranks = if pawn.rank <= 4 { pawn.rank + 2..=pawn.rank + 2 } else { pawn.rank + 1..=8.min(pawn.rank + 2) };
+    ranks
+        .flat_map(|rank| (pawn.file - 1..=pawn.file + 1).map(move |file| Position { rank, file }))
+        .collect()
+}
+
+// With the attacker as White and its pawn heading for the eighth rank
+fn kpk_is_won(pawn: Position, strong_king: Position, weak_king: Position, strong_to_move: bool) -> bool {
+    let guarded = |pos: Position| king_distance(pos, weak_king) <= 1;
+    
+    // The defender takes a pawn its king isn't guarding
+    if !strong_to_move && guarded(pawn) && king_distance(pawn, strong_king) > 1 {
+        return false;
+    }
+    
+    // Rule of the square: the pawn outruns the defending king on its own
+    let queening_square = Position { rank: 8, file: pawn.file };
+    let pawn_moves = if pawn.rank == 2 { 5 } else { 8 - pawn.rank };
+    let in_the_way = strong_king.file == pawn.file && strong_king.rank > pawn.rank;
+    let weak_moves = king_distance(weak_king, queening_square) as i32 - if strong_to_move { 0 } else { 1 };
+    if !in_the_way && weak_moves > pawn_moves as i32 {
+        return true;
+    }
+    
+    let key_squares = kpk_key_squares(pawn);
+    if key_squares.contains(&strong_king) {
+        return true;
+    }
+    let reachable_key_square = |weak_king: Position| {
+        key_squares.iter().any(|&key| {
+            key != pawn && king_distance(key, strong_king) == 1 && king_distance(key, weak_king) > 1
+        })
+    };
+    
+    if strong_to_move {
+        if reachable_key_square(weak_king) {
+            return true;
+        }
+    } else {
+        // Having to move, the defender gives up the opposition if every king
+        // move lets the attacker onto a key square
+        let replies: Vec<Position> = (1..=8)
+            .flat_map(|rank| (1..=8).map(move |file| Position { rank, file }))
+            .filter(|&pos| {
+                let pawn_attacks = pos.rank == pawn.rank + 1 && pos.file.abs_diff(pawn.file) == 1;
+                king_distance(pos, weak_king) == 1 && king_distance(pos, strong_king) > 1 &&
+                    pos != pawn && !pawn_attacks
+            })
+            .collect();
+        if !replies.is_empty() && replies.iter().all(|&reply| reachable_key_square(reply)) {
+            return true;
+        }
+    }
+    
+    // Otherwise it's a race to the nearest key square: the attacker has to
+    // get there before the defender's king can cover it
+    key_squares.iter().any(|&key| {
+        let strong_distance = king_distance(key, strong_king) as i32;
+        let weak_distance = king_distance(key, weak_king) as i32;
+        if strong_to_move {
+            strong_distance < weak_distance
+        } else {
+            strong_distance < weak_distance - 1
+        }
+    })
+}
+
+// Two rooks, or a rook and queen, stacked on a file with none of their own
+// pawns and nothing standing between them
+fn evaluate_batteries(board: &Board) -> i32 {
+    let mut score = 0;
+    
+    for file in 1..=8 {
+        let pieces: Vec<_> = (1..=8)
+            .filter_map(|rank| board.get_piece(Position { rank, file }))
+            .collect();
+        let has_pawn = |color: Color| {
+            pieces.iter().any(|p| p.piece_type == PieceType::Pawn && p.color == color)
+        };
+        
+        for (color, enemy) in [(Color::White, Color::Black), (Color::Black, Color::White)] {
+            if has_pawn(color) {
+                continue;
+            }
+            let battery = pieces.windows(2).any(|pair| {
+                pair.iter().all(|p| {
+                    p.color == color && matches!(p.piece_type, PieceType::Rook | PieceType::Queen)
+                }) && pair.iter().any(|p| p.piece_type == PieceType::Rook)
+            });
+            if !battery {
+                continue;
+            }
+            
+            let bonus = if has_pawn(enemy) {
+                HALF_OPEN_FILE_BATTERY_BONUS
+            } else {
+                OPEN_FILE_BATTERY_BONUS
+            };
+            if color == Color::White {
+                score += bonus;
+            } else {
+                score -= bonus;
+            }
+        }
+    }
+    
+    score
+}
+
+// Each side has a lone bishop (plus king and pawns) and the bishops travel on
+// different colored squares, so neither can contest the other's squares
+fn is_opposite_colored_bishop_ending(board: &Board) -> bool {
+    let mut white_bishops = Vec::new();
+    let mut black_bishops = Vec::new();
+    
+    for (pos, piece) in board.occupied() {
+        match piece.piece_type {
+            PieceType::Bishop => {
+                if piece.color == Color::White {
+                    white_bishops.push(pos);
+                } else {
+                    black_bishops.push(pos);
+                }
+            }
+            PieceType::Knight | PieceType::Rook | PieceType::Queen => return false,
+            PieceType::Pawn | PieceType::King => {}
+        }
+    }
+    
+    match (white_bishops.as_slice(), black_bishops.as_slice()) {
+        ([white], [black]) => white.square_color() != black.square_color(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    #[test]
+    fn two_knights_cannot_force_mate() {
+        let _engine = crate::ai::lock_engine_for_test();
+        let knights = board("8/8/8/4k3/8/8/8/2NNK3 w - - 0 1");
+        assert!(is_known_draw(&knights));
+        assert_eq!(evaluate_position(&knights), 0);
+    }
+
+    #[test]
+    fn bishop_and_knight_can_force_mate() {
+        let _engine = crate::ai::lock_engine_for_test();
+        let bishop_knight = board("8/8/8/4k3/8/8/8/2BNK3 w - - 0 1");
+        assert!(!is_known_draw(&bishop_knight));
+        assert!(evaluate_position(&bishop_knight) > 0);
+    }
+
+    #[test]
+    fn kpk_king_on_a_key_square_wins() {
+        let _engine = crate::ai::lock_engine_for_test();
+        let white = board("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1");
+        assert!(eval_kpk(&white) >= KPK_WIN_SCORE);
+
+        // The same ending mirrored for Black, who is to move and winning
+        let black = board("8/8/8/8/4p3/4k3/8/4K3 b - - 0 1");
+        assert!(eval_kpk(&black) <= -KPK_WIN_SCORE);
+        assert!(evaluate_position(&black) >= KPK_WIN_SCORE);
+    }
+
+    #[test]
+    fn kpk_opposition_decides() {
+        let white_to_move = board(chess_core::positions::OPPOSITION_FEN);
+        assert_eq!(eval_kpk(&white_to_move), 0);
+
+        let black_to_move = board(&chess_core::positions::OPPOSITION_FEN.replace(" w ", " b "));
+        assert!(eval_kpk(&black_to_move) >= KPK_WIN_SCORE);
+    }
+
+    #[test]
+    fn kpk_rook_pawn_with_the_defender_in_the_corner_is_drawn() {
+        let _engine = crate::ai::lock_engine_for_test();
+        let rook_pawn = board("k7/8/8/8/8/1K6/P7/8 w - - 0 1");
+        assert_eq!(eval_kpk(&rook_pawn), 0);
+        assert_eq!(evaluate_position(&rook_pawn), 0);
+    }
+
+    #[test]
+    fn opposite_colored_bishops_make_an_extra_pawn_nearly_a_draw() {
+        // The white bishop on f1 is light squared; Black's is dark on d6 and
+        // light on d5
+        let opposite = board("4k3/8/3b4/8/8/8/4P3/4KB2 w - - 0 1");
+        let same = board("4k3/8/8/3b4/8/8/4P3/4KB2 w - - 0 1");
+        let opposite_score = evaluate_position_with(&opposite, &EvalParams::BALANCED);
+        let same_score = evaluate_position_with(&same, &EvalParams::BALANCED);
+        assert!((0..PAWN_VALUE / 2).contains(&opposite_score), "{}", opposite_score);
+        assert!(same_score >= PAWN_VALUE, "{}", same_score);
+    }
+
+    #[test]
+    fn doubled_rooks_on_the_d_file_form_a_battery() {
+        assert_eq!(evaluate_batteries(&board("4k3/8/8/8/8/8/3R4/3RK3 w - - 0 1")), OPEN_FILE_BATTERY_BONUS);
+        // A single rook on the file, or one with a knight in between, is no battery
+        assert_eq!(evaluate_batteries(&board("4k3/8/8/8/8/8/R7/3RK3 w - - 0 1")), 0);
+        assert_eq!(evaluate_batteries(&board("4k3/8/8/8/3R4/3N4/8/3RK3 w - - 0 1")), 0);
+        // Half-open with an enemy pawn on the file, and the same for Black
+        assert_eq!(evaluate_batteries(&board("4k3/3p4/8/8/8/8/3R4/3RK3 w - - 0 1")), HALF_OPEN_FILE_BATTERY_BONUS);
+        assert_eq!(evaluate_batteries(&board("3rk3/3q4/8/8/8/8/8/4K3 w - - 0 1")), -OPEN_FILE_BATTERY_BONUS);
+    }
+}
@@ -1,9 +1,12 @@
-pub mod evaluation;
+mod evaluation;
+mod opening_book;
 pub mod search;
 pub mod ai;
 
 // Re-export only the public interface
-pub use ai::ChessAI;
+pub use ai::{ChessAI, AiMove, MoveSource, Personality};
+pub use search::{SearchResult, TimeControl, TtStats};
+pub use evaluation::{Evaluator, ClassicalEval, EvalParams};
 
 // These are internal implementation details
 pub(crate) use evaluation::evaluate_position;
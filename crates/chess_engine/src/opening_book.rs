@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use chess_core::{moves::Move, Board, piece::Color, position::Position};
+use rand::Rng;
 
 #[derive(Clone)]
 pub struct OpeningBook {
@@ -12,6 +13,12 @@ struct BookMove {
     weight: u32,  // Higher weight means more likely to be played
 }
 
+impl Default for OpeningBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl OpeningBook {
     pub fn new() -> Self {
         let mut book = Self {
@@ -22,7 +29,7 @@ impl OpeningBook {
     }
 
     fn initialize_common_openings(&mut self) {
-        let mut board = Board::new();
+        let board = Board::new();
         
         // 1. e4 lines
         let e4_move = Move::new(
@@ -32,8 +39,7 @@ impl OpeningBook {
         self.add_line(&board, e4_move, 100);  // King's Pawn Opening
         
         // 1...e5 (Open Game)
-        let mut e4_board = board.clone();
-        e4_board.make_move(e4_move).unwrap();
+        let e4_board = board.with_move(e4_move).unwrap();
         let e5_move = Move::new(
             Position { rank: 7, file: 5 },
             Position { rank: 5, file: 5 }
@@ -41,8 +47,7 @@ impl OpeningBook {
         self.add_line(&e4_board, e5_move, 100);  // 1...e5
         
         // After 1. e4 e5, add main responses
-        let mut open_game_board = e4_board.clone();
-        open_game_board.make_move(e5_move).unwrap();
+        let open_game_board = e4_board.with_move(e5_move).unwrap();
         
         // 2. Nf3 (Ruy Lopez/Italian Game setup)
         let nf3_move = Move::new(
@@ -52,8 +57,7 @@ impl OpeningBook {
         self.add_line(&open_game_board, nf3_move, 100);  // 2. Nf3
         
         // After 2. Nf3, add 2...Nc6
-        let mut ruy_board = open_game_board.clone();
-        ruy_board.make_move(nf3_move).unwrap();
+        let ruy_board = open_game_board.with_move(nf3_move).unwrap();
         let nc6_move = Move::new(
             Position { rank: 8, file: 2 },
             Position { rank: 6, file: 3 }
@@ -61,8 +65,7 @@ impl OpeningBook {
         self.add_line(&ruy_board, nc6_move, 100);  // 2...Nc6
         
         // After 2...Nc6, add main variations
-        let mut nc6_board = ruy_board.clone();
-        nc6_board.make_move(nc6_move).unwrap();
+        let nc6_board = ruy_board.with_move(nc6_move).unwrap();
         
         // 3. Bb5 (Ruy Lopez)
         self.add_line(&nc6_board, Move::new(
@@ -84,8 +87,7 @@ impl OpeningBook {
         self.add_line(&e4_board, c5_move, 90);  // 1...c5
         
         // After 1. e4 c5, add main responses
-        let mut sicilian_board = e4_board.clone();
-        sicilian_board.make_move(c5_move).unwrap();
+        let sicilian_board = e4_board.with_move(c5_move).unwrap();
         
         // 2. Nf3 (Open Sicilian)
         let nf3_sicilian = Move::new(
@@ -95,8 +97,7 @@ impl OpeningBook {
         self.add_line(&sicilian_board, nf3_sicilian, 100);  // 2. Nf3
         
         // After 2. Nf3, add main responses
-        let mut open_sicilian = sicilian_board.clone();
-        open_sicilian.make_move(nf3_sicilian).unwrap();
+        let open_sicilian = sicilian_board.with_move(nf3_sicilian).unwrap();
         
         // 2...d6 (Najdorf setup)
         self.add_line(&open_sicilian, Move::new(
@@ -118,8 +119,7 @@ impl OpeningBook {
         self.add_line(&board, d4_move, 90);  // Queen's Pawn Opening
         
         // 1...d5 (Closed Game)
-        let mut d4_board = board.clone();
-        d4_board.make_move(d4_move).unwrap();
+        let d4_board = board.with_move(d4_move).unwrap();
         let d5_move = Move::new(
             Position { rank: 7, file: 4 },
             Position { rank: 5, file: 4 }
@@ -127,8 +127,7 @@ impl OpeningBook {
         self.add_line(&d4_board, d5_move, 100);  // 1...d5
         
         // After 1. d4 d5, add Queen's Gambit lines
-        let mut qg_board = d4_board.clone();
-        qg_board.make_move(d5_move).unwrap();
+        let qg_board = d4_board.with_move(d5_move).unwrap();
         
         // 2. c4 (Queen's Gambit)
         let c4_move = Move::new(
@@ -138,8 +137,7 @@ impl OpeningBook {
         self.add_line(&qg_board, c4_move, 100);  // 2. c4
         
         // After 2. c4, add main responses
-        let mut qg_offered = qg_board.clone();
-        qg_offered.make_move(c4_move).unwrap();
+        let qg_offered = qg_board.with_move(c4_move).unwrap();
         
         // 2...e6 (Queen's Gambit Declined)
         self.add_line(&qg_offered, Move::new(
@@ -161,8 +159,7 @@ impl OpeningBook {
         self.add_line(&d4_board, nf6_move, 90);  // 1...Nf6
         
         // After 1. d4 Nf6, add responses
-        let mut indian_board = d4_board.clone();
-        indian_board.make_move(nf6_move).unwrap();
+        let indian_board = d4_board.with_move(nf6_move).unwrap();
         
         // 2. c4 (King's Indian setup)
         self.add_line(&indian_board, Move::new(
@@ -184,18 +181,26 @@ impl OpeningBook {
         ), 50);  // 1. c4
     }
 
-    pub fn get_book_move(&self, board: &Board) -> Option<Move> {
+    pub fn get_book_move(&self, board: &Board, rng: &mut impl Rng) -> Option<Move> {
         let position_key = self.get_position_key(board);
-        self.positions.get(&position_key).and_then(|moves| {
-            // Choose a move based on weights
+        self.positions.get(&position_key).and_then(|entries| {
+            // A key collision or a bad external book could hand us moves that
+            // don't fit the actual position, so only choose among legal ones
+            let moves: Vec<&BookMove> = entries.iter()
+                .filter(|book_move| board.is_legal(book_move.mv))
+                .collect();
             if moves.is_empty() {
                 return None;
             }
             
+            // Choose a move based on weights
             let total_weight: u32 = moves.iter().map(|m| m.weight).sum();
-            let mut chosen_weight = rand::random::<u32>() % total_weight;
+            if total_weight == 0 {
+                return Some(moves[0].mv);
+            }
+            let mut chosen_weight = rng.gen_range(0..total_weight);
             
-            for book_move in moves {
+            for book_move in &moves {
                 if chosen_weight < book_move.weight {
                     return Some(book_move.mv);
                 }
@@ -210,7 +215,7 @@ impl OpeningBook {
         let position_key = self.get_position_key(board);
         self.positions
             .entry(position_key)
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(BookMove { mv, weight });
     }
 
@@ -219,25 +224,20 @@ impl OpeningBook {
         let mut key = String::new();
         
         // Add pieces to key
-        for rank in 1..=8 {
-            for file in 1..=8 {
-                let pos = Position { rank, file };
-                if let Some(piece) = board.get_piece(pos) {
-                    let color_char = match piece.color {
-                        Color::White => 'w',
-                        Color::Black => 'b',
-                    };
-                    let piece_char = match piece.piece_type {
-                        chess_core::piece::PieceType::Pawn => 'p',
-                        chess_core::piece::PieceType::Knight => 'n',
-                        chess_core::piece::PieceType::Bishop => 'b',
-                        chess_core::piece::PieceType::Rook => 'r',
-                        chess_core::piece::PieceType::Queen => 'q',
-                        chess_core::piece::PieceType::King => 'k',
-                    };
-                    key.push_str(&format!("{}{}:{}{},", pos.rank, pos.file, color_char, piece_char));
-                }
-            }
+        for (pos, piece) in board.occupied() {
+            let color_char = match piece.color {
+                Color::White => 'w',
+                Color::Black => 'b',
+            };
+            let piece_char = match piece.piece_type {
+                chess_core::piece::PieceType::Pawn => 'p',
+                chess_core::piece::PieceType::Knight => 'n',
+                chess_core::piece::PieceType::Bishop => 'b',
+                chess_core::piece::PieceType::Rook => 'r',
+                chess_core::piece::PieceType::Queen => 'q',
+                chess_core::piece::PieceType::King => 'k',
+            };
+            key.push_str(&format!("{}{}:{}{},", pos.rank, pos.file, color_char, piece_char));
         }
         
         // Add current turn
@@ -245,4 +245,25 @@ impl OpeningBook {
         
         key
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn corrupted_entries_are_skipped() {
+        let board = Board::new();
+        let square = |name: &str| Position::from_algebraic(name).unwrap();
+        let mut book = OpeningBook { positions: HashMap::new() };
+        // A pawn can't go three squares, however heavily the book weighs it
+        book.add_line(&board, Move::new(square("e2"), square("e5")), 1000);
+        assert_eq!(book.get_book_move(&board, &mut StdRng::seed_from_u64(0)), None);
+
+        let d4 = Move::new(square("d2"), square("d4"));
+        book.add_line(&board, d4, 1);
+        for seed in 0..20 {
+            assert_eq!(book.get_book_move(&board, &mut StdRng::seed_from_u64(seed)), Some(d4));
+        }
+    }
+}
@@ -1,9 +1,9 @@
 // Standard imports for time management, chess logic, and parallel processing
 use std::time::{Instant, Duration};
-use chess_core::{Board, Move, Position, piece::PieceType, moves::MoveType};
-use crate::evaluation::evaluate_position;
+use chess_core::{Board, Color, Move, Position, piece::PieceType, moves::MoveType};
+use crate::evaluation::{eval_params, evaluate, is_known_draw};
 use std::collections::HashMap;
-use std::sync::{Mutex, atomic::{AtomicBool, Ordering}};
+use std::sync::{Mutex, atomic::{AtomicBool, AtomicI32, AtomicU8, AtomicU64, Ordering}};
 use once_cell::sync::Lazy;
 use rayon::prelude::*;
 
@@ -12,6 +12,7 @@ const MIN_TIME_PER_MOVE: Duration = Duration::from_millis(100);  // Don't move t
 const MAX_TIME_PER_MOVE: Duration = Duration::from_secs(15);     // Don't think forever
 const TIME_BUFFER: Duration = Duration::from_millis(50);         // Safety margin for time management
 const MOVES_TO_GO: u32 = 40;                                     // Assume this many moves left in the game
+const TACTICAL_TIME_PERCENT: u32 = 150;                          // Share of the usual time Tactical gives sharp positions
 
 // Search parameters
 const MAX_DEPTH: u8 = 15;                    // Maximum search depth
@@ -20,20 +21,34 @@ const ASPIRATION_WINDOW: i32 = 50;           // Initial aspiration window size
 const DELTA_MARGIN: i32 = 200;               // Increased from 150 for more tactical awareness
 const NULL_MOVE_R: u8 = 3;                   // Null move reduction
 const LMR_DEPTH_THRESHOLD: u8 = 3;           // Late Move Reduction depth threshold
-const LMR_MOVE_THRESHOLD: usize = 4;         // Number of moves before LMR kicks in
+const LMR_MOVE_THRESHOLD: usize = 4;         // Number of moves searched at full depth before LMR kicks in
+const LMR_MAX_MOVES: usize = 64;             // Move numbers past this share the last table column
 const FUTILITY_MARGIN: [i32; 4] = [0, 300, 500, 800];  // Increased margins for better tactical play
 const MAX_QUIESCENCE_DEPTH: u8 = 8;          // Deeper quiescence search for tactical positions
-const REDUCTION_LIMIT: u8 = 3;               // Don't reduce moves until this depth
-const FULL_DEPTH_MOVES: usize = 4;           // Search this many moves with full window
 const MAX_TT_SIZE: usize = 1_000_000;        // Size of transposition table
 const WINDOW_SIZE_INIT: i32 = 100;           // Initial window size
 
 // Move ordering scores
 const PV_MOVE_SCORE: i32 = 20000;            // Principal variation move
 const CAPTURE_SCORE_BASE: i32 = 10000;       // Base score for captures
+const LOSING_CAPTURE_SCORE: i32 = -10000;    // Captures that lose material, minus what they lose
 const KILLER_MOVE_SCORE: i32 = 9000;         // Killer move score
 const COUNTER_MOVE_SCORE: i32 = 8000;        // Counter move score
 const HISTORY_SCORE_MAX: i32 = 8000;         // Maximum history heuristic score
+const STALEMATE_TRICK_SCORE: i32 = 12000;    // For a move that leaves us no moves of our own
+const QUEEN_PROMOTION_SCORE: i32 = 9500;     // Promoting to a queen
+const UNDERPROMOTION_SCORE: i32 = 7000;      // Knight promotions, and rook/bishop ones that give check
+const STALEMATE_TRICK_STEP: i32 = 3000;      // Less for each move we'd still have
+
+// Being this far behind means a stalemate would be a good result
+const DESPERATE_EVAL: i32 = -500;
+
+// Skill levels run from 0 to MAX_SKILL_LEVEL, which is full strength. Each
+// level below it adds this much noise (either way) to the leaf evaluations
+// and this many percent of the root moves left out of the search.
+pub const MAX_SKILL_LEVEL: u8 = 20;
+const SKILL_NOISE_PER_LEVEL: i32 = 10;
+const SKILL_DROP_PERCENT_PER_LEVEL: u64 = 3;
 
 // Types of entries in our transposition table
 #[derive(Clone, Copy)]
@@ -56,14 +71,95 @@ struct TTEntry {
 static TRANSPOSITION_TABLE: Lazy<Mutex<HashMap<String, TTEntry>>> = 
     Lazy::new(|| Mutex::new(HashMap::with_capacity(MAX_TT_SIZE)));
 
-// History tables
-static mut HISTORY_TABLE: Lazy<Mutex<Vec<Vec<i32>>>> = Lazy::new(|| Mutex::new(vec![vec![0; 64]; 64]));
-static mut KILLER_MOVES: Lazy<Mutex<Vec<[Option<Move>; 2]>>> = Lazy::new(|| Mutex::new(vec![[None, None]; MAX_DEPTH as usize]));
-static mut COUNTER_MOVES: Lazy<Mutex<HashMap<MoveKey, Move>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-
 // Principal Variation (PV) - the best line of play we've found
 const MAX_PV_LENGTH: usize = 64;  // Maximum length of the principal variation
-static PV_TABLE: Lazy<Mutex<Vec<Move>>> = Lazy::new(|| Mutex::new(Vec::with_capacity(MAX_PV_LENGTH)));
+
+// Late move reductions indexed by [depth][move number], grows with both
+static LMR_TABLE: Lazy<Vec<[u8; LMR_MAX_MOVES]>> = Lazy::new(|| {
+    (0..=MAX_DEPTH as usize)
+        .map(|depth| {
+            let mut row = [0; LMR_MAX_MOVES];
+            for (move_number, reduction) in row.iter_mut().enumerate().skip(1) {
+                if depth > 0 {
+                    let r = 0.75 + (depth as f32).ln() * (move_number as f32).ln() / 2.25;
+                    *reduction = r.floor() as u8;
+                }
+            }
+            row
+        })
+        .collect()
+});
+
+// What one search threads through its recursion: the history table, the
+// killer and counter moves, and a set of move lists per ply that nodes generate into
+// instead of allocating. Only one node per ply is on the stack at a time, so
+// each can borrow its ply's lists and hand them back, capacity and all, for
+// the next one.
+struct SearchContext {
+    history: Vec<Vec<i32>>,
+    killers: Vec<[Option<Move>; 2]>,  // Per ply, the last two quiet moves that caused a cutoff
+    counter_moves: HashMap<MoveKey, Move>,  // The quiet move that last refuted each move
+    ply_buffers: Vec<MoveBuffers>,
+}
+
+#[derive(Default)]
+struct MoveBuffers {
+    moves: Vec<Move>,
+    scored: Vec<(Move, i32)>,  // Scratch for ordering
+}
+
+impl SearchContext {
+    fn new() -> Self {
+        SearchContext {
+            history: vec![vec![0; 64]; 64],
+            killers: Vec::new(),
+            counter_moves: HashMap::new(),
+            ply_buffers: Vec::new(),
+        }
+    }
+
+    fn killers(&self, ply: u8) -> [Option<Move>; 2] {
+        self.killers.get(ply as usize).copied().unwrap_or_default()
+    }
+
+    // Remembers a quiet move that refuted the position at this ply, so its
+    // siblings try it early and don't reduce it
+    fn store_killer(&mut self, ply: u8, mv: Move) {
+        let ply = ply as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None, None]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+    }
+
+    fn counter_move(&self, prev_move: Option<Move>) -> Option<Move> {
+        self.counter_moves.get(&MoveKey::from(prev_move?)).copied()
+    }
+
+    fn history_score(&self, mv: Move) -> i32 {
+        self.history[square_index(mv.from)][square_index(mv.to)]
+    }
+
+    // The ply's lists, emptied. Give them back with return_buffers.
+    fn take_buffers(&mut self, ply: u8) -> MoveBuffers {
+        let ply = ply as usize;
+        if self.ply_buffers.len() <= ply {
+            self.ply_buffers.resize_with(ply + 1, MoveBuffers::default);
+        }
+        let mut buffers = std::mem::take(&mut self.ply_buffers[ply]);
+        buffers.moves.clear();
+        buffers.scored.clear();
+        buffers
+    }
+
+    fn return_buffers(&mut self, ply: u8, buffers: MoveBuffers) {
+        self.ply_buffers[ply as usize] = buffers;
+    }
+}
 
 // Move key for hash map
 #[derive(Hash, Eq, PartialEq, Clone, Copy)]
@@ -85,28 +181,142 @@ impl From<Move> for MoveKey {
     }
 }
 
+// Outcome of a search: the move to play plus what the engine expects to happen
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: Move,
+    pub score: i32,      // Centipawns from the side to move's point of view
+    pub depth: u8,       // Deepest fully completed iteration (0 for an obvious move)
+    pub pv: Vec<Move>,   // Principal variation, starting with best_move
+    pub from_tt: bool,   // Taken straight from the transposition table without searching
+    pub time: Duration,  // How long the search took
+    pub tt_stats: TtStats,
+    pub near_best: Vec<Move>,  // Other moves scoring within the variety margin of best_move
+    pub nodes: u64,      // Positions visited, for nodes per second
+}
+
+// How the transposition table fared during a search
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TtStats {
+    pub probes: u64,  // Lookups made by the search
+    pub hits: u64,    // Lookups that found an entry for the position
+    pub stores: u64,  // Entries written
+}
+
+impl TtStats {
+    // Fraction of probes that found an entry, 0.0 if nothing was probed
+    pub fn hit_rate(&self) -> f64 {
+        if self.probes == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.probes as f64
+        }
+    }
+}
+
 // Flag to stop searching when we run out of time
 static SEARCH_TERMINATED: AtomicBool = AtomicBool::new(false);
 
+// Nodes visited by the running search, and how many it may visit
+static NODES_SEARCHED: AtomicU64 = AtomicU64::new(0);
+static NODE_LIMIT: AtomicU64 = AtomicU64::new(u64::MAX);
+
+// How far below the best score (in centipawns) another root move may score
+// and still be reported in near_best. 0 skips looking for them.
+static VARIETY_MARGIN: AtomicI32 = AtomicI32::new(0);
+
+// Skill level of the running search, and the seed its noise and dropped
+// root moves are drawn from
+static SKILL_LEVEL: AtomicU8 = AtomicU8::new(MAX_SKILL_LEVEL);
+static SKILL_SEED: AtomicU64 = AtomicU64::new(0);
+
+// Transposition table traffic of the running search
+static TT_PROBES: AtomicU64 = AtomicU64::new(0);
+static TT_HITS: AtomicU64 = AtomicU64::new(0);
+static TT_STORES: AtomicU64 = AtomicU64::new(0);
+
+// Deepest iteration the running search has finished so far, for progress displays
+static PROGRESS: Mutex<Option<SearchResult>> = Mutex::new(None);
+
+// Side the running search is choosing a move for; contempt is charged to its draws
+static ROOT_IS_WHITE: AtomicBool = AtomicBool::new(true);
+
+// How search_best_move decides how long to think about a move
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeControl {
+    // What's left on the clock spread over the moves still to play
+    // (MOVES_TO_GO when unknown), kept within the per-move minimum and maximum
+    MovesToGo { remaining: Duration, moves_left: Option<u32> },
+    // The same time every move, whatever the position
+    FixedPerMove(Duration),
+    // Like MovesToGo, but with extra time when there's a check, capture or
+    // promotion on the board
+    Tactical { remaining: Duration, moves_left: Option<u32> },
+    // Like MovesToGo, but only the minimum when the move is forced or obvious
+    Economical { remaining: Duration, moves_left: Option<u32> },
+}
+
+impl TimeControl {
+    // Time to spend on the move in this position
+    pub fn allocate(&self, board: &Board) -> Duration {
+        match *self {
+            TimeControl::MovesToGo { remaining, moves_left } => share_of_clock(remaining, moves_left),
+            TimeControl::FixedPerMove(time) => time,
+            TimeControl::Tactical { remaining, moves_left } => {
+                let base = share_of_clock(remaining, moves_left);
+                if board.is_quiet() {
+                    base
+                } else {
+                    (base * TACTICAL_TIME_PERCENT / 100).min(MAX_TIME_PER_MOVE)
+                }
+            }
+            TimeControl::Economical { remaining, moves_left } => {
+                let moves = board.legal_moves();
+                if moves.len() <= 1 || find_obvious_move(board, &moves).is_some() {
+                    MIN_TIME_PER_MOVE
+                } else {
+                    share_of_clock(remaining, moves_left)
+                }
+            }
+        }
+    }
+}
+
+fn share_of_clock(remaining: Duration, moves_left: Option<u32>) -> Duration {
+    let moves_to_go = moves_left.unwrap_or(MOVES_TO_GO).max(1);
+    remaining.div_f32(moves_to_go as f32).min(MAX_TIME_PER_MOVE).max(MIN_TIME_PER_MOVE)
+}
+
 // Manages how long we can spend thinking about a move
 struct TimeManager {
     start_time: Instant,      // When we started thinking
     allocated_time: Duration, // How long we can think
+    max_nodes: Option<u64>,   // Node budget, independent of machine speed
 }
 
 impl TimeManager {
-    // Creates a new time manager based on total time left and estimated moves to go
-    fn new(total_time: Duration, moves_left: Option<u32>) -> Self {
-        let moves_to_go = moves_left.unwrap_or(MOVES_TO_GO);
-        let base_time = total_time.div_f32(moves_to_go as f32);
-        let allocated_time = base_time.min(MAX_TIME_PER_MOVE).max(MIN_TIME_PER_MOVE);
-        
+    fn new(allocated_time: Duration) -> Self {
         Self {
             start_time: Instant::now(),
             allocated_time,
+            max_nodes: None,
         }
     }
 
+    // No time limit at all: the search runs until stop_search is called
+    fn unlimited() -> Self {
+        Self {
+            start_time: Instant::now(),
+            allocated_time: Duration::MAX,
+            max_nodes: None,
+        }
+    }
+
+    fn with_node_limit(mut self, max_nodes: Option<u64>) -> Self {
+        self.max_nodes = max_nodes;
+        self
+    }
+
     // Checks if we still have time to continue searching
     fn should_continue(&self) -> bool {
         let elapsed = self.start_time.elapsed();
@@ -121,6 +331,7 @@ impl TimeManager {
 
 // Core search algorithm parameters
 const MATE_SCORE: i32 = 20000;                    // Value representing checkmate
+const MATE_THRESHOLD: i32 = MATE_SCORE - 1000;    // Scores beyond this are mates
 const ALPHA_INIT: i32 = -19000;                   // Initial alpha for search window
 const BETA_INIT: i32 = 19000;                     // Initial beta for search window
 const QUIESCENCE_DEPTH: u8 = 6;                   // Increased from 4 to search deeper in tactical positions
@@ -139,54 +350,232 @@ fn create_default_move() -> Move {
     }
 }
 
-// Main function that finds the best move in a given position
-pub fn search_best_move(board: &Board, total_time: Duration, moves_left: Option<u32>) -> Option<Move> {
-    println!("\nStarting new search with time limit: {:?}", total_time);
+// Main function that finds the best move in a given position, taking as long
+// as the time control allots. With a node limit the search stops once that
+// many nodes are visited, and with a depth limit once that depth is done,
+// even if time is left. With fresh_table earlier searches can't affect the
+// result, see search.
+pub fn search_best_move(
+    board: &Board,
+    time_control: TimeControl,
+    max_nodes: Option<u64>,
+    max_depth: Option<u8>,
+    fresh_table: bool,
+) -> Option<SearchResult> {
+    let allocated = time_control.allocate(board);
+    eprintln!("\nStarting new search with time limit: {:?} ({:?})", allocated, time_control);
+    let time_manager = TimeManager::new(allocated).with_node_limit(max_nodes);
+    let max_depth = max_depth.map_or(MAX_DEPTH, |depth| depth.clamp(1, MAX_DEPTH));
+    search(board, time_manager, max_depth, fresh_table)
+}
+
+// Searches to a fixed depth, unless the time limit runs out first
+pub fn search_to_depth(board: &Board, depth: u8, time_limit: Duration) -> Option<SearchResult> {
+    eprintln!("\nStarting new search to depth {}", depth);
+    let allocated = share_of_clock(time_limit, Some(1));
+    search(board, TimeManager::new(allocated), depth.clamp(1, MAX_DEPTH), false)
+}
+
+// Searches to exactly the given depth with no time limit and a fresh
+// transposition table, so the same position always gives the same result
+pub fn search_deterministic(board: &Board, depth: u8) -> Option<SearchResult> {
+    log::debug!("Starting new deterministic search to depth {}", depth);
+    search(board, TimeManager::unlimited(), depth.clamp(1, MAX_DEPTH), true)
+}
+
+// Searches deeper and deeper until stop_search is called (or MAX_DEPTH is done)
+pub fn search_infinite(board: &Board) -> Option<SearchResult> {
+    log::debug!("Starting new infinite search");
+    search(board, TimeManager::unlimited(), MAX_DEPTH, false)
+}
+
+// Best move and score after the last iteration the running (or most recent)
+// search completed. None until its first iteration is done.
+pub fn search_progress() -> Option<SearchResult> {
+    PROGRESS.lock().unwrap().clone()
+}
+
+// Asks the running search to stop as soon as possible
+pub fn stop_search() {
+    SEARCH_TERMINATED.store(true, Ordering::SeqCst);
+}
+
+// A stopped or node-limited search leaves its limits set, which would cut
+// short the next test's direct quiescence or PVS calls
+#[cfg(test)]
+pub(crate) fn clear_search_limits() {
+    SEARCH_TERMINATED.store(false, Ordering::SeqCst);
+    NODES_SEARCHED.store(0, Ordering::SeqCst);
+    NODE_LIMIT.store(u64::MAX, Ordering::SeqCst);
+}
+
+// Makes searches also report the root moves within `margin` centipawns of
+// the best one, for callers that want to vary their play. Global like the
+// node limit; 0 turns it off.
+pub fn set_variety_margin(margin: i32) {
+    VARIETY_MARGIN.store(margin.max(0), Ordering::SeqCst);
+}
+
+// Weakens the following searches to a skill level between 0 and
+// MAX_SKILL_LEVEL. Below full strength some root moves are left out of the
+// search and the leaf evaluations get noise, both drawn from `seed`: the same
+// seed gives the same choices in the same position.
+pub fn set_skill_level(level: u8, seed: u64) {
+    SKILL_LEVEL.store(level.min(MAX_SKILL_LEVEL), Ordering::SeqCst);
+    SKILL_SEED.store(seed, Ordering::SeqCst);
+}
+
+// Levels below full strength, 0 at full strength
+fn skill_handicap() -> u64 {
+    (MAX_SKILL_LEVEL - SKILL_LEVEL.load(Ordering::Relaxed)) as u64
+}
+
+// Scrambles the skill seed together with a key into a well-spread number
+fn skill_hash(key: u64) -> u64 {
+    let mut x = key ^ SKILL_SEED.load(Ordering::Relaxed);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+// Static evaluation plus the skill level's noise. The noise is fixed per
+// position, so transpositions and re-searches agree on it.
+fn leaf_evaluate(board: &Board) -> i32 {
+    let handicap = skill_handicap();
+    if handicap == 0 {
+        return evaluate(board);
+    }
+    let amplitude = handicap * SKILL_NOISE_PER_LEVEL as u64;
+    let noise = (skill_hash(board.zobrist_hash()) % (2 * amplitude + 1)) as i32 - amplitude as i32;
+    evaluate(board) + noise
+}
+
+// The root moves a weakened search gets to look at. Which ones are dropped
+// depends only on the move and the seed, so every iteration drops the same
+// ones. At least one legal move is always kept.
+fn skill_root_moves(board: &Board, moves: Vec<Move>) -> Vec<Move> {
+    let drop_percent = skill_handicap() * SKILL_DROP_PERCENT_PER_LEVEL;
+    if drop_percent == 0 {
+        return moves;
+    }
+    let kept: Vec<Move> = moves.iter().copied()
+        .filter(|mv| {
+            let promotion = mv.promotion.map_or(0, |piece| piece as u8 + 1);
+            let key = u64::from_le_bytes([mv.from.rank, mv.from.file, mv.to.rank, mv.to.file, promotion, 0, 0, 0]);
+            skill_hash(key ^ board.zobrist_hash()) % 100 >= drop_percent
+        })
+        .collect();
+    if kept.iter().any(|&mv| board.is_legal(mv)) {
+        kept
+    } else {
+        moves
+    }
+}
+
+// Counts a visited node, stopping the search once the node limit is reached
+fn count_node() {
+    let nodes = NODES_SEARCHED.fetch_add(1, Ordering::Relaxed) + 1;
+    if nodes >= NODE_LIMIT.load(Ordering::Relaxed) {
+        SEARCH_TERMINATED.store(true, Ordering::SeqCst);
+    }
+}
+
+fn tt_stats() -> TtStats {
+    TtStats {
+        probes: TT_PROBES.load(Ordering::Relaxed),
+        hits: TT_HITS.load(Ordering::Relaxed),
+        stores: TT_STORES.load(Ordering::Relaxed),
+    }
+}
+
+// Iterative deepening driver shared by the public search entry points. With
+// fresh_table the search starts from an empty transposition table of its own
+// instead of the shared one, so earlier searches can't affect the result.
+fn search(board: &Board, mut time_manager: TimeManager, max_depth: u8, fresh_table: bool) -> Option<SearchResult> {
+    // Take the table before clearing the stop flag, so a search that's being
+    // stopped on another thread gets to finish and release it first
+    let mut shared_tt = TRANSPOSITION_TABLE.lock().unwrap();
+    // Time spent waiting for the table doesn't count against this move
     let start_time = Instant::now();
-    
+    time_manager.start_time = start_time;
+    let mut own_tt = HashMap::new();
+    // A weakened search's noisy scores would mislead later full-strength ones
+    let fresh_table = fresh_table || skill_handicap() > 0;
+    let tt: &mut HashMap<String, TTEntry> = if fresh_table { &mut own_tt } else { &mut shared_tt };
     SEARCH_TERMINATED.store(false, Ordering::SeqCst);
-    let time_manager = TimeManager::new(total_time, moves_left);
+    NODES_SEARCHED.store(0, Ordering::SeqCst);
+    NODE_LIMIT.store(time_manager.max_nodes.unwrap_or(u64::MAX), Ordering::SeqCst);
+    for counter in [&TT_PROBES, &TT_HITS, &TT_STORES] {
+        counter.store(0, Ordering::SeqCst);
+    }
+    ROOT_IS_WHITE.store(board.current_turn() == Color::White, Ordering::SeqCst);
+    *PROGRESS.lock().unwrap() = None;
     
     // Clear transposition table if it's getting too large
-    let mut tt = TRANSPOSITION_TABLE.lock().unwrap();
     let tt_size = tt.len();
     if tt_size > MAX_TT_SIZE {
-        println!("Clearing transposition table (size: {})", tt_size);
+        eprintln!("Clearing transposition table (size: {})", tt_size);
         tt.clear();
     }
     
-    // Try to find an obvious move first
-    let mut moves = Vec::new();
-    for pos in (1..=8).flat_map(|rank| (1..=8).map(move |file| Position { rank, file })) {
-        if let Some(piece) = board.get_piece(pos) {
-            if piece.color == board.current_turn() {
-                moves.extend(board.get_valid_moves(pos));
+    // A previous search may already have settled this position deeply enough
+    if let Some(entry) = tt.get(&get_position_key(board)) {
+        if let (EntryType::Exact, Some(mv)) = (entry.entry_type, entry.best_move) {
+            if entry.depth >= max_depth && board.clone().make_move(mv).is_ok() {
+                log::debug!("Transposition table hit at depth {}: {:?}", entry.depth, mv);
+                return Some(SearchResult {
+                    best_move: mv,
+                    score: entry.score,
+                    depth: entry.depth,
+                    pv: extract_pv(board, mv, tt),
+                    from_tt: true,
+                    time: start_time.elapsed(),
+                    tt_stats: TtStats::default(),
+                    near_best: Vec::new(),
+                    nodes: 0,
+                });
             }
         }
     }
-    println!("Generated {} possible moves", moves.len());
+
+    // Try to find an obvious move first
+    let moves = skill_root_moves(board, board.pseudo_legal_moves());
+    eprintln!("Generated {} possible moves", moves.len());
     
     if let Some(obvious) = find_obvious_move(board, &moves) {
-        println!("Found obvious move: {:?}", obvious);
-        return Some(obvious);
+        eprintln!("Found obvious move: {:?}", obvious);
+        // Scored after the capture, or the material it wins wouldn't show
+        let score = board.with_move(obvious).map_or_else(|_| evaluate(board), |after| -evaluate(&after));
+        return Some(SearchResult {
+            best_move: obvious,
+            score,
+            depth: 0,
+            pv: vec![obvious],
+            from_tt: false,
+            time: start_time.elapsed(),
+            tt_stats: TtStats::default(),
+            near_best: Vec::new(),
+            nodes: 0,
+        });
     }
     
     let mut best_move = None;
     let mut best_score = ALPHA_INIT;
+    let mut completed_depth = 0;
     let mut pv_table = Vec::new();
-    let mut history = vec![vec![0; 64]; 64];
+    let mut ctx = SearchContext::new();
     
     // Aspiration windows for better move ordering
     let mut window_size = WINDOW_SIZE_INIT;
     
-    for depth in 1..=MAX_DEPTH {
+    for depth in 1..=max_depth {
         let elapsed = start_time.elapsed();
         if !time_manager.should_continue() {
-            println!("Stopping search at depth {} due to time limit ({:?} elapsed)", depth, elapsed);
+            eprintln!("Stopping search at depth {} due to time limit ({:?} elapsed)", depth, elapsed);
             break;
         }
         
-        println!("\nSearching at depth {}", depth);
+        eprintln!("\nSearching at depth {}", depth);
         let depth_start = Instant::now();
         
         // Calculate alpha and beta with overflow protection
@@ -196,10 +585,11 @@ pub fn search_best_move(board: &Board, total_time: Duration, moves_left: Option<
         let mut score = principal_variation_search(
             board,
             depth,
+            0,
             alpha,
             beta,
-            &mut tt,
-            &mut history,
+            tt,
+            &mut ctx,
             &mut pv_table,
             true,
             None,
@@ -207,33 +597,52 @@ pub fn search_best_move(board: &Board, total_time: Duration, moves_left: Option<
         
         // If score is outside our window, research with full window
         if score <= alpha || score >= beta {
-            println!("Score {} outside window [{}, {}], researching with full window", score, alpha, beta);
+            eprintln!("Score {} outside window [{}, {}], researching with full window", score, alpha, beta);
             score = principal_variation_search(
                 board,
                 depth,
+                0,
                 -MATE_SCORE,
                 MATE_SCORE,
-                &mut tt,
-                &mut history,
+                tt,
+                &mut ctx,
                 &mut pv_table,
                 true,
                 None,
             );
         }
         
+        // An interrupted iteration's score can't be trusted
+        if SEARCH_TERMINATED.load(Ordering::SeqCst) {
+            log::debug!("Search stopped during depth {}", depth);
+            break;
+        }
+        
         let depth_time = depth_start.elapsed();
-        println!("Depth {} completed in {:?}, score: {}", depth, depth_time, score);
+        eprintln!("Depth {} completed in {:?}, score: {}", depth, depth_time, score);
         
         // Update best move if we found one
         if !pv_table.is_empty() {
             best_move = Some(pv_table[0]);
             best_score = score;
-            println!("New best move: {:?}, score: {}", best_move, best_score);
+            completed_depth = depth;
+            eprintln!("New best move: {:?}, score: {}", best_move, best_score);
+            *PROGRESS.lock().unwrap() = Some(SearchResult {
+                best_move: pv_table[0],
+                score,
+                depth,
+                pv: pv_table.clone(),
+                from_tt: false,
+                time: start_time.elapsed(),
+                tt_stats: tt_stats(),
+                near_best: Vec::new(),
+                nodes: NODES_SEARCHED.load(Ordering::Relaxed),
+            });
         }
         
         // Early exit if we found a forced mate
-        if score.abs() > MATE_SCORE - 100 {
-            println!("Found forced mate, stopping search");
+        if score.abs() > MATE_THRESHOLD {
+            eprintln!("Found forced mate, stopping search");
             break;
         }
         
@@ -241,15 +650,123 @@ pub fn search_best_move(board: &Board, total_time: Duration, moves_left: Option<
         window_size = window_size.saturating_mul(5).saturating_div(4);
     }
     
+    // Stopped before the first iteration finished - any legal move beats none
+    if best_move.is_none() {
+        best_move = moves.iter().copied().find(|&mv| board.clone().make_move(mv).is_ok());
+        best_score = evaluate(board);
+    }
+    
+    // Mates aren't varied: any other move would throw the win away or lose sooner
+    let near_best = match best_move {
+        Some(best) if completed_depth > 0 && best_score.abs() < MATE_THRESHOLD => {
+            near_best_moves(board, best, best_score, completed_depth, tt, &mut ctx)
+        }
+        _ => Vec::new(),
+    };
+    
     let total_time = start_time.elapsed();
-    println!("\nSearch completed in {:?}", total_time);
+    let stats = tt_stats();
+    eprintln!("\nSearch completed in {:?}", total_time);
+    eprintln!(
+        "Transposition table: {} probes, {} hits ({:.1}%), {} stores",
+        stats.probes, stats.hits, stats.hit_rate() * 100.0, stats.stores
+    );
     if let Some(mv) = best_move {
-        println!("Best move found: {:?} with score {}", mv, best_score);
+        eprintln!("Best move found: {:?} with score {}", mv, best_score);
     } else {
-        println!("No valid move found!");
+        eprintln!("No valid move found!");
+    }
+    
+    best_move.map(|mv| SearchResult {
+        best_move: mv,
+        score: best_score,
+        depth: completed_depth,
+        pv: extract_pv(board, mv, tt),
+        from_tt: false,
+        time: total_time,
+        tt_stats: stats,
+        near_best,
+        nodes: NODES_SEARCHED.load(Ordering::Relaxed),
+    })
+}
+
+// Root moves other than `best` that score within VARIETY_MARGIN of it. The
+// search only proves the best move's score, so each of the others gets a
+// null-window search at the same depth to see if it clears the margin.
+fn near_best_moves(
+    board: &Board,
+    best: Move,
+    best_score: i32,
+    depth: u8,
+    tt: &mut HashMap<String, TTEntry>,
+    ctx: &mut SearchContext,
+) -> Vec<Move> {
+    let margin = VARIETY_MARGIN.load(Ordering::SeqCst);
+    if margin == 0 {
+        return Vec::new();
+    }
+    
+    let threshold = best_score - margin;
+    let mut near_best = Vec::new();
+    for mv in skill_root_moves(board, board.legal_moves()) {
+        if mv == best {
+            continue;
+        }
+        let Ok(after) = board.with_move(mv) else {
+            continue;
+        };
+        let mut pv_table = Vec::new();
+        let score = -principal_variation_search(
+            &after,
+            depth - 1,
+            1,
+            -(threshold + 1),
+            -threshold,
+            tt,
+            ctx,
+            &mut pv_table,
+            false,
+            Some(mv),
+        );
+        // A stopped search's scores can't be trusted; keep what was proven
+        if SEARCH_TERMINATED.load(Ordering::SeqCst) {
+            break;
+        }
+        if score > threshold {
+            near_best.push(mv);
+        }
     }
+    near_best
+}
+
+// Follows best moves stored in the transposition table to rebuild the expected line
+fn extract_pv(board: &Board, first_move: Move, tt: &HashMap<String, TTEntry>) -> Vec<Move> {
+    let mut pv = vec![first_move];
+    let mut current = board.clone();
+    let mut seen = vec![get_position_key(&current)];
     
-    best_move
+    if current.make_move(first_move).is_err() {
+        return pv;
+    }
+    
+    while pv.len() < MAX_PV_LENGTH {
+        let key = get_position_key(&current);
+        // Stop on repetitions, otherwise a cycle in the table would loop forever
+        if seen.contains(&key) {
+            break;
+        }
+        let next = match tt.get(&key).and_then(|entry| entry.best_move) {
+            Some(mv) => mv,
+            None => break,
+        };
+        if current.make_move(next).is_err() {
+            break;
+        }
+        seen.push(key);
+        pv.push(next);
+    }
+    
+    pv
 }
 
 // Looks for simple winning captures that we can make immediately
@@ -262,7 +779,7 @@ fn find_obvious_move(board: &Board, moves: &[Move]) -> Option<Move> {
                 let mut new_board = board.clone();
                 if new_board.make_move(mv).is_ok() {
                     // Make sure it's not a trap where we lose the piece
-                    if !is_piece_hanging(&new_board, mv.to) {
+                    if !new_board.is_hanging(mv.to) {
                         return Some(mv);
                     }
                 }
@@ -276,38 +793,52 @@ fn find_obvious_move(board: &Board, moves: &[Move]) -> Option<Move> {
 fn principal_variation_search(
     board: &Board,
     depth: u8,
+    ply: u8,
     alpha: i32,
     beta: i32,
     tt: &mut HashMap<String, TTEntry>,
-    history: &mut Vec<Vec<i32>>,
+    ctx: &mut SearchContext,
     pv_table: &mut Vec<Move>,
     is_pv_node: bool,
     prev_move: Option<Move>,
 ) -> i32 {
     // Early exits
     if SEARCH_TERMINATED.load(Ordering::SeqCst) {
-        return evaluate_position(board);
+        return evaluate(board);
     }
+    count_node();
 
-    if depth == 0 || board.is_checkmate() || board.is_stalemate() {
-        let score = quiescence_search(board, alpha, beta, QUIESCENCE_DEPTH);
-        if depth == 0 {
-            println!("Reached depth 0, quiescence score: {}", score);
-        }
-        return score;
+    // Dead drawn positions need no further search
+    if is_known_draw(board) {
+        return draw_score(board);
+    }
+
+    // Coming back to a position, earlier in the game or along this line, is
+    // worth no more than the draw the repetition can be turned into. Without
+    // this a winning side happily shuffles back and forth.
+    if ply > 0 && board.repetition_count() > 1 {
+        return draw_score(board);
+    }
+
+    // Mate and stalemate aren't tested for here: finding no legal move in the
+    // loop below says the same thing without generating every move twice
+    if depth == 0 {
+        return quiescence_search(board, alpha, beta, QUIESCENCE_DEPTH, ply, ctx);
     }
 
     // Try to use cached result if we have one
     let pos_key = get_position_key(board);
     let original_alpha = alpha;
     let mut best_move = None;
-    let mut best_score = ALPHA_INIT;
+    let mut best_score = -MATE_SCORE;
     let mut current_alpha = alpha;
 
     // Check transposition table
+    TT_PROBES.fetch_add(1, Ordering::Relaxed);
     if let Some(entry) = tt.get(&pos_key) {
+        TT_HITS.fetch_add(1, Ordering::Relaxed);
         if entry.depth >= depth && !is_pv_node {
-            let score = adjust_mate_score(entry.score, depth);
+            let score = score_from_tt(entry.score, ply);
             match entry.entry_type {
                 EntryType::Exact => return score,
                 EntryType::LowerBound => current_alpha = current_alpha.max(score),
@@ -325,14 +856,20 @@ fn principal_variation_search(
     }
 
     // Generate and try moves
-    let mut moves = generate_ordered_moves(board, best_move, depth, prev_move);
+    let killers = ctx.killers(ply);
+    let mut buffers = ctx.take_buffers(ply);
+    // Material alone says whether we're desperate: a full evaluation at
+    // every node would cost more than the ordering gains
+    let desperate = material_balance(board) < DESPERATE_EVAL;
+    generate_ordered_moves(board, best_move, killers, ctx.counter_move(prev_move), ctx, desperate, &mut buffers);
+    if ply == 0 {
+        buffers.moves = skill_root_moves(board, std::mem::take(&mut buffers.moves));
+    }
     let mut searched_moves = 0;
     let mut has_legal_moves = false;
 
-    println!("Searching {} moves at depth {}", moves.len(), depth);
-
     // Try each move
-    for mv in moves {
+    for &mv in &buffers.moves {
         let mut new_board = board.clone();
         if new_board.make_move(mv).is_ok() {
             has_legal_moves = true;
@@ -343,44 +880,64 @@ fn principal_variation_search(
                 -principal_variation_search(
                     &new_board,
                     depth - 1,
+                    ply + 1,
                     -beta,
                     -current_alpha,
                     tt,
-                    history,
+                    ctx,
                     pv_table,
                     is_pv_node,
                     Some(mv),
                 )
             } else {
-                // Try late move reductions for other moves
-                let reduction = if depth >= REDUCTION_LIMIT && searched_moves > FULL_DEPTH_MOVES {
-                    ((searched_moves as f32).ln().floor() as u8).min(depth - 1)
-                } else {
-                    0
-                };
-
-                // First try a shallow search
+                // Captures, promotions, checks and killers are never reduced
+                let is_tactical = mv.is_capture(board) ||
+                    mv.promotion.is_some() ||
+                    new_board.in_check() ||
+                    killers.contains(&Some(mv));
+                let reduction = if is_tactical { 0 } else { lmr_reduction(depth, searched_moves) };
+
+                // First try a (possibly reduced) null-window search
                 let mut score = -principal_variation_search(
                     &new_board,
                     depth - 1 - reduction,
+                    ply + 1,
                     -(current_alpha + 1),
                     -current_alpha,
                     tt,
-                    history,
+                    ctx,
                     pv_table,
                     false,
                     Some(mv),
                 );
 
-                // If the shallow search looks promising, do a full search
+                // A reduced move that raises alpha must be verified at full depth,
+                // otherwise a fail-high from the shallow search would be trusted as-is
+                if reduction > 0 && score > current_alpha {
+                    score = -principal_variation_search(
+                        &new_board,
+                        depth - 1,
+                        ply + 1,
+                        -(current_alpha + 1),
+                        -current_alpha,
+                        tt,
+                        ctx,
+                        pv_table,
+                        false,
+                        Some(mv),
+                    );
+                }
+
+                // Inside the window - search again with the full window for an exact score
                 if score > current_alpha && score < beta {
                     score = -principal_variation_search(
                         &new_board,
                         depth - 1,
+                        ply + 1,
                         -beta,
                         -current_alpha,
                         tt,
-                        history,
+                        ctx,
                         pv_table,
                         is_pv_node,
                         Some(mv),
@@ -395,8 +952,10 @@ fn principal_variation_search(
                 best_move = Some(mv);
                 if score > current_alpha {
                     current_alpha = score;
-                    if is_pv_node {
-                        println!("New best move at depth {}: {:?}, score: {}", depth, mv, score);
+                    // Only the root records its move: the table is shared, and a
+                    // deeper PV node would leave the other side's reply in it
+                    if ply == 0 {
+                        eprintln!("New best move at depth {}: {:?}, score: {}", depth, mv, score);
                         pv_table.clear();
                         pv_table.push(mv);
                     }
@@ -405,17 +964,22 @@ fn principal_variation_search(
 
             // Beta cutoff - position is too good, opponent won't allow it
             if current_alpha >= beta {
-                if !is_capture(board, mv) {
-                    update_history(history, mv, depth);
+                if !mv.is_capture(board) {
+                    update_history(&mut ctx.history, mv, depth);
+                    ctx.store_killer(ply, mv);
+                    if let Some(prev) = prev_move {
+                        ctx.counter_moves.insert(MoveKey::from(prev), mv);
+                    }
                 }
                 break;
             }
         }
     }
+    ctx.return_buffers(ply, buffers);
 
     // Handle special cases
     if !has_legal_moves {
-        return if is_endgame_or_in_check(board) { -MATE_SCORE + depth as i32 } else { 0 };
+        return if board.in_check() { -MATE_SCORE + ply as i32 } else { draw_score(board) };
     }
 
     // Save position to transposition table
@@ -427,9 +991,10 @@ fn principal_variation_search(
         EntryType::Exact
     };
 
+    TT_STORES.fetch_add(1, Ordering::Relaxed);
     tt.insert(pos_key, TTEntry {
         depth,
-        score: best_score,
+        score: score_to_tt(best_score, ply),
         entry_type,
         best_move,
     });
@@ -437,18 +1002,43 @@ fn principal_variation_search(
     best_score
 }
 
+// A draw from the side to move's point of view: worth -contempt to the side
+// the search is playing for, and +contempt to its opponent
+fn draw_score(board: &Board) -> i32 {
+    let contempt = eval_params().contempt;
+    let root_to_move = (board.current_turn() == Color::White) == ROOT_IS_WHITE.load(Ordering::Relaxed);
+    if root_to_move { -contempt } else { contempt }
+}
+
+// How many plies to reduce a quiet move by, given its position in the move ordering
+fn lmr_reduction(depth: u8, move_number: usize) -> u8 {
+    if depth < LMR_DEPTH_THRESHOLD || move_number <= LMR_MOVE_THRESHOLD {
+        return 0;
+    }
+    let row = &LMR_TABLE[(depth as usize).min(MAX_DEPTH as usize)];
+    row[move_number.min(LMR_MAX_MOVES - 1)].min(depth - 1)
+}
+
+// Ordering bonus for a move that leaves us with few moves of our own, which
+// is how a lost side sets up a stalemate. `scratch` is overwritten with the
+// position after the move, which saves a fresh clone per candidate.
+fn stalemate_trick_score(board: &Board, scratch: &mut Board, mv: Move) -> i32 {
+    scratch.clone_from(board);
+    if scratch.make_move(mv).is_err() {
+        return 0;
+    }
+    scratch.set_turn(board.current_turn());
+    let our_moves = scratch.legal_moves().len() as i32;
+    (STALEMATE_TRICK_SCORE - our_moves * STALEMATE_TRICK_STEP).max(0)
+}
+
 // Creates a unique string key for a board position
 fn get_position_key(board: &Board) -> String {
     let mut key = String::with_capacity(100);
     // Add each piece's position and type to the key
-    for rank in 1..=8 {
-        for file in 1..=8 {
-            let pos = chess_core::Position { rank, file };
-            if let Some(piece) = board.get_piece(pos) {
-                key.push_str(&format!("{}{}:{:?}{:?},", 
-                    pos.rank, pos.file, piece.piece_type, piece.color));
-            }
-        }
+    for (pos, piece) in board.occupied() {
+        key.push_str(&format!("{}{}:{:?}{:?},", 
+            pos.rank, pos.file, piece.piece_type, piece.color));
     }
     // Add whose turn it is
     key.push_str(&format!("turn:{:?}", board.current_turn()));
@@ -456,17 +1046,25 @@ fn get_position_key(board: &Board) -> String {
 }
 
 // Search captures to make sure we don't miss any tactical opportunities
-fn quiescence_search(board: &Board, mut alpha: i32, beta: i32, depth: u8) -> i32 {
+fn quiescence_search(board: &Board, mut alpha: i32, beta: i32, depth: u8, ply: u8, ctx: &mut SearchContext) -> i32 {
     // Check if we need to stop searching
     if SEARCH_TERMINATED.load(Ordering::SeqCst) {
-        return evaluate_position(board);
+        return evaluate(board);
     }
+    count_node();
 
     // Get a quick evaluation of the current position
-    let stand_pat = evaluate_position(board);
+    let stand_pat = leaf_evaluate(board);
     
-    // Stop searching if we're too deep or the game is over
-    if depth == 0 || board.is_checkmate() || board.is_stalemate() {
+    // Can't stand pat while in check - every evasion has to be looked at
+    if board.in_check() {
+        if depth == 0 {
+            return stand_pat;
+        }
+        return quiescence_evasions(board, alpha, beta, depth, ply, stand_pat, ctx);
+    }
+
+    if depth == 0 {
         return stand_pat;
     }
 
@@ -475,77 +1073,126 @@ fn quiescence_search(board: &Board, mut alpha: i32, beta: i32, depth: u8) -> i32
         return beta;
     }
 
+    // Look at all possible captures
+    let mut buffers = ctx.take_buffers(ply);
+    board.generate_captures_into(&mut buffers.moves);
+    if buffers.moves.is_empty() {
+        ctx.return_buffers(ply, buffers);
+        // Only a position without captures can be stalemate, which is a draw
+        // however far ahead the static evaluation says we are
+        return if board.legal_move_exists() { stand_pat } else { draw_score(board) };
+    }
+
     // Don't search further if even the best capture can't improve our position
     if stand_pat < alpha - DELTA_MARGIN {
+        ctx.return_buffers(ply, buffers);
         return alpha;
     }
 
     // Current position is better than what we've found so far
     alpha = alpha.max(stand_pat);
 
-    // Look at all possible captures
-    let mut captures = generate_captures(board);
-    if captures.is_empty() {
-        return stand_pat;
+    // Only look at captures that don't lose too much material, sorted by how
+    // good they look
+    for &m in &buffers.moves {
+        let see_score = static_exchange_evaluation(board, m);
+        if see_score >= -50 {  // Only slightly losing captures might be worth checking
+            let mvv_lva = get_mvv_lva_score(board, m);
+            buffers.scored.push((m, -(see_score * 1000 + mvv_lva)));
+        }
     }
-    
-    // Sort captures by how good they look
-    captures.sort_by_cached_key(|m| {
-        let see_score = static_exchange_evaluation(board, *m);
-        let mvv_lva = get_mvv_lva_score(board, *m);
-        -(see_score * 1000 + mvv_lva)
-    });
-    
-    // Only look at captures that don't lose too much material
-    captures.retain(|m| {
-        let see_score = static_exchange_evaluation(board, *m);
-        see_score >= -50 // Only slightly losing captures might be worth checking
-    });
+    buffers.scored.sort_by_key(|&(_, key)| key);
 
     // Try each capture
-    for capture in captures {
+    let mut any_legal = false;
+    for &(capture, _) in &buffers.scored {
         // Stop if we're out of time
         if SEARCH_TERMINATED.load(Ordering::SeqCst) {
-            return alpha;
+            break;
         }
 
         // Make the capture and evaluate the resulting position
         let mut new_board = board.clone();
         if new_board.make_move(capture).is_ok() {
-            let score = -quiescence_search(&new_board, -beta, -alpha, depth - 1);
+            any_legal = true;
+            let score = -quiescence_search(&new_board, -beta, -alpha, depth - 1, ply + 1, ctx);
             alpha = alpha.max(score);
             if alpha >= beta {
                 break;
             }
         }
     }
+    ctx.return_buffers(ply, buffers);
 
+    // The captures on offer may all have been pinned pieces
+    if !any_legal && !board.legal_move_exists() {
+        return draw_score(board);
+    }
     alpha
 }
 
-// Generates a list of moves sorted by how good they're likely to be
-fn generate_ordered_moves(
+// Quiescence search for a side in check: all legal replies, no stand-pat lower bound
+fn quiescence_evasions(
     board: &Board,
-    tt_move: Option<Move>,
+    mut alpha: i32,
+    beta: i32,
     depth: u8,
-    prev_move: Option<Move>,
-) -> Vec<Move> {
-    let mut moves = Vec::new();
-    for pos in (1..=8).flat_map(|rank| (1..=8).map(move |file| Position { rank, file })) {
-        if let Some(piece) = board.get_piece(pos) {
-            if piece.color == board.current_turn() {
-                moves.extend(board.get_valid_moves(pos));
+    ply: u8,
+    static_eval: i32,
+    ctx: &mut SearchContext,
+) -> i32 {
+    let mut best_score = -MATE_SCORE + ply as i32;
+    
+    let mut buffers = ctx.take_buffers(ply);
+    generate_ordered_moves(board, None, [None, None], None, ctx, static_eval < DESPERATE_EVAL, &mut buffers);
+    for &mv in &buffers.moves {
+        if SEARCH_TERMINATED.load(Ordering::SeqCst) {
+            ctx.return_buffers(ply, buffers);
+            return alpha;
+        }
+        
+        let mut new_board = board.clone();
+        if new_board.make_move(mv).is_ok() {
+            let score = -quiescence_search(&new_board, -beta, -alpha, depth - 1, ply + 1, ctx);
+            best_score = best_score.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
             }
         }
     }
+    ctx.return_buffers(ply, buffers);
     
-    if moves.is_empty() {
-        return moves;
-    }
+    // No legal evasion means checkmate, which best_score already reflects
+    best_score
+}
+
+// Fills buffers.moves with the moves sorted by how good they're likely to be.
+// Badly lost positions (`desperate`) also look for stalemate tricks: getting
+// rid of our own moves so that the opponent can't avoid stalemating us.
+fn generate_ordered_moves(
+    board: &Board,
+    tt_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    counter_move: Option<Move>,
+    ctx: &SearchContext,
+    desperate: bool,
+    buffers: &mut MoveBuffers,
+) {
+    // Pseudo-legal only: moves that leave the king in check are rejected
+    // by make_move when they're tried
+    board.generate_moves_into(&mut buffers.moves);
     
+    if buffers.moves.is_empty() {
+        return;
+    }
+
+    // One scratch board for trying out stalemate tricks, refilled per move
+    let mut scratch = desperate.then(|| board.clone());
+
     // Score moves
-    let mut scored_moves: Vec<(Move, i32)> = moves.into_iter()
-        .map(|mv| {
+    buffers.scored.extend(buffers.moves.iter()
+        .map(|&mv| {
             let mut score = 0;
             
             // TT move gets highest priority
@@ -555,55 +1202,63 @@ fn generate_ordered_moves(
                 }
             }
             
-            // Captures
+            // Captures, with the ones that lose material after the recaptures
+            // put behind the quiet moves
             if let Some(victim) = board.get_piece(mv.to) {
                 let attacker = board.get_piece(mv.from).unwrap();
-                score += CAPTURE_SCORE_BASE + mvv_lva_score(victim.piece_type, attacker.piece_type);
-                
-                // SEE (Static Exchange Evaluation) for captures
                 let see_score = static_exchange_evaluation(board, mv);
-                if see_score > 0 {
+                if see_score < 0 {
+                    score += LOSING_CAPTURE_SCORE + see_score;
+                } else {
+                    score += CAPTURE_SCORE_BASE + mvv_lva_score(victim.piece_type, attacker.piece_type);
                     score += see_score * 100;
                 }
             }
             
+            score += promotion_score(board, mv);
+            
             // Killer moves
-            unsafe {
-                let killer_moves = KILLER_MOVES.get_mut().unwrap().get(depth as usize);
-                if let Some(killers) = killer_moves {
-                    if killers[0] == Some(mv) {
-                        score += KILLER_MOVE_SCORE;
-                    } else if killers[1] == Some(mv) {
-                        score += KILLER_MOVE_SCORE - 100;
-                    }
-                }
+            if killers[0] == Some(mv) {
+                score += KILLER_MOVE_SCORE;
+            } else if killers[1] == Some(mv) {
+                score += KILLER_MOVE_SCORE - 100;
             }
             
             // Counter moves
-            if let Some(prev) = prev_move {
-                unsafe {
-                    let counter_moves = COUNTER_MOVES.get_mut().unwrap();
-                    if counter_moves.get(&MoveKey::from(prev)) == Some(&mv) {
-                        score += COUNTER_MOVE_SCORE;
-                    }
-                }
+            if counter_move == Some(mv) {
+                score += COUNTER_MOVE_SCORE;
             }
             
             // History heuristic
-            unsafe {
-                let history = HISTORY_TABLE.get_mut().unwrap();
-                let from_idx = ((mv.from.rank - 1) * 8 + (mv.from.file - 1)) as usize;
-                let to_idx = ((mv.to.rank - 1) * 8 + (mv.to.file - 1)) as usize;
-                score += history[from_idx][to_idx].min(HISTORY_SCORE_MAX);
+            score += ctx.history_score(mv).min(HISTORY_SCORE_MAX);
+            
+            if let Some(scratch) = scratch.as_mut() {
+                score += stalemate_trick_score(board, scratch, mv);
             }
             
             (mv, score)
-        })
-        .collect();
+        }));
     
     // Sort moves by score
-    scored_moves.sort_by_key(|(_, score)| -score);
-    scored_moves.into_iter().map(|(mv, _)| mv).collect()
+    buffers.scored.sort_by_key(|(_, score)| -score);
+    buffers.moves.clear();
+    buffers.moves.extend(buffers.scored.iter().map(|&(mv, _)| mv));
+}
+
+// Queening is almost always best, but underpromotions must still come before
+// the quiet moves: a knight fork or a check can be the only winning move, and
+// a rook or bishop dodges stalemate. A plain rook or bishop promotion is never
+// better than a queen, so those get nothing.
+fn promotion_score(board: &Board, mv: Move) -> i32 {
+    match mv.promotion {
+        None => 0,
+        Some(PieceType::Queen) => QUEEN_PROMOTION_SCORE,
+        Some(PieceType::Knight) => UNDERPROMOTION_SCORE,
+        Some(_) if board.with_move(mv).is_ok_and(|after| after.in_check()) => {
+            UNDERPROMOTION_SCORE
+        }
+        Some(_) => 0,
+    }
 }
 
 fn mvv_lva_score(victim: PieceType, attacker: PieceType) -> i32 {
@@ -629,64 +1284,6 @@ fn mvv_lva_score(victim: PieceType, attacker: PieceType) -> i32 {
     victim_value * 100 - attacker_value * 10
 }
 
-// Updates history tables after a successful move
-fn update_history_tables(mv: Move, depth: u8, prev_move: Option<Move>) {
-    let bonus = depth as i32 * depth as i32;
-    
-    unsafe {
-        // Update history table
-        let mut history = HISTORY_TABLE.get_mut().unwrap();
-        let from_idx = ((mv.from.rank - 1) * 8 + (mv.from.file - 1)) as usize;
-        let to_idx = ((mv.to.rank - 1) * 8 + (mv.to.file - 1)) as usize;
-        history[from_idx][to_idx] += bonus;
-        
-        // Decay history values if they get too large
-        if history[from_idx][to_idx] > HISTORY_SCORE_MAX * 2 {
-            for row in history.iter_mut() {
-                for cell in row.iter_mut() {
-                    *cell /= 2;
-                }
-            }
-        }
-        
-        // Update killer moves
-        let mut killer_moves = KILLER_MOVES.get_mut().unwrap();
-        if let Some(killers) = killer_moves.get_mut(depth as usize) {
-            if killers[0] != Some(mv) {
-                killers[1] = killers[0];
-                killers[0] = Some(mv);
-            }
-        }
-        
-        // Update counter moves using move keys
-        if let Some(prev) = prev_move {
-            let mut counter_moves = COUNTER_MOVES.get_mut().unwrap();
-            counter_moves.insert(MoveKey::from(prev), mv);
-        }
-    }
-}
-
-// Finds all possible captures in the current position
-fn generate_captures(board: &Board) -> Vec<Move> {
-    let mut captures = Vec::new();
-    for rank in 1..=8 {
-        for file in 1..=8 {
-            let pos = chess_core::Position { rank, file };
-            if let Some(piece) = board.get_piece(pos) {
-                if piece.color == board.current_turn() {
-                    let moves = board.get_valid_moves(pos);
-                    for mv in moves {
-                        if board.get_piece(mv.to).is_some() {
-                            captures.push(mv);
-                        }
-                    }
-                }
-            }
-        }
-    }
-    captures
-}
-
 // Scores captures based on Most Valuable Victim - Least Valuable Attacker principle
 fn get_mvv_lva_score(board: &Board, mv: Move) -> i32 {
     let victim = board.get_piece(mv.to);
@@ -718,61 +1315,13 @@ fn get_piece_value(piece_type: PieceType) -> i32 {
     }
 }
 
-// Checks if we're in endgame or if the king is under attack
-fn is_endgame_or_in_check(board: &Board) -> bool {
-    let mut queens = 0;
-    let mut pieces = 0;
-    let current_color = board.current_turn();
-    let mut king_attacked = false;
-
-    // Count material and look for king attacks
-    for rank in 1..=8 {
-        for file in 1..=8 {
-            let pos = chess_core::Position { rank, file };
-            if let Some(piece) = board.get_piece(pos) {
-                match piece.piece_type {
-                    PieceType::Queen => queens += 1,
-                    PieceType::Rook | PieceType::Bishop | PieceType::Knight => pieces += 1,
-                    PieceType::King if piece.color == current_color => {
-                        // Look for any enemy pieces that can attack our king
-                        for r in 1..=8 {
-                            for f in 1..=8 {
-                                let attack_pos = chess_core::Position { rank: r, file: f };
-                                if let Some(attacker) = board.get_piece(attack_pos) {
-                                    if attacker.color != current_color {
-                                        let moves = board.get_valid_moves(attack_pos);
-                                        if moves.iter().any(|m| m.to == pos) {
-                                            king_attacked = true;
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                            if king_attacked {
-                                break;
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            }
-        }
-    }
-
-    // We're in endgame if there are few pieces left
-    let is_endgame = queens == 0 || (queens == 2 && pieces <= 2);
-    is_endgame || king_attacked
-} 
-
 // Updates the history table when a move causes a beta cutoff
-fn update_history(history: &mut Vec<Vec<i32>>, mv: Move, bonus: u8) {
-    let from_idx = ((mv.from.rank - 1) * 8 + (mv.from.file - 1)) as usize;
-    let to_idx = ((mv.to.rank - 1) * 8 + (mv.to.file - 1)) as usize;
-    
-    history[from_idx][to_idx] += bonus as i32;
+fn update_history(history: &mut [Vec<i32>], mv: Move, bonus: u8) {
+    let cell = &mut history[square_index(mv.from)][square_index(mv.to)];
+    *cell += bonus as i32;
     
     // Scale down all history scores if they get too large
-    if history[from_idx][to_idx] > HISTORY_SCORE_MAX {
+    if *cell > HISTORY_SCORE_MAX {
         for row in history.iter_mut() {
             for cell in row.iter_mut() {
                 *cell /= 2;
@@ -781,70 +1330,53 @@ fn update_history(history: &mut Vec<Vec<i32>>, mv: Move, bonus: u8) {
     }
 }
 
-// Gets the history score for a move
-fn get_history_score(history: &Vec<Vec<i32>>, mv: Move) -> i32 {
-    let from_idx = ((mv.from.rank - 1) * 8 + (mv.from.file - 1)) as usize;
-    let to_idx = ((mv.to.rank - 1) * 8 + (mv.to.file - 1)) as usize;
-    history[from_idx][to_idx]
-}
-
-// Checks if a move is a capture
-fn is_capture(board: &Board, mv: Move) -> bool {
-    board.get_piece(mv.to).is_some()
+// Index of a square in the 64-entry history rows
+fn square_index(pos: Position) -> usize {
+    ((pos.rank - 1) * 8 + (pos.file - 1)) as usize
 }
 
-// Checks if a move gives check to the opponent
-fn gives_check(board: &Board) -> bool {
-    let current_color = board.current_turn();
-    
-    // Find the opponent's king
-    let mut king_pos = None;
-    'outer: for rank in 1..=8 {
-        for file in 1..=8 {
-            let pos = chess_core::Position { rank, file };
-            if let Some(piece) = board.get_piece(pos) {
-                if piece.piece_type == PieceType::King && piece.color != current_color {
-                    king_pos = Some(pos);
-                    break 'outer;
-                }
-            }
-        }
-    }
-
-    // See if any of our pieces can attack the king
-    if let Some(king_pos) = king_pos {
-        for rank in 1..=8 {
-            for file in 1..=8 {
-                let pos = chess_core::Position { rank, file };
-                if let Some(piece) = board.get_piece(pos) {
-                    if piece.color == current_color {
-                        let moves = board.get_valid_moves(pos);
-                        if moves.iter().any(|m| m.to == king_pos) {
-                            return true;
-                        }
-                    }
-                }
-            }
-        }
+// What a capture wins once every recapture on the square has been played
+// out, each side taking with its cheapest piece and free to stop whenever
+// going on would lose. Pieces are lifted off a scratch board as they capture,
+// so sliders lined up behind them join in. Non-captures score 0.
+fn static_exchange_evaluation(board: &Board, mv: Move) -> i32 {
+    let (Some(&victim), Some(&attacker)) = (board.get_piece(mv.to), board.get_piece(mv.from)) else {
+        return 0;
+    };
+    let victim_value = get_piece_static_value(victim.piece_type);
+    if board.attackers(mv.to, victim.color).is_empty() {
+        return victim_value;
     }
     
-    false
-} 
-
-// Evaluates a capture sequence to see if it's good for us
-fn static_exchange_evaluation(board: &Board, mv: Move) -> i32 {
-    let victim = board.get_piece(mv.to);
-    let attacker = board.get_piece(mv.from);
+    let mut board = board.clone();
+    board.remove_piece(mv.from);
+    board.set_piece(mv.to, Some(attacker));
     
-    if let (Some(victim), Some(attacker)) = (victim, attacker) {
-        let victim_value = get_piece_static_value(victim.piece_type);
-        let attacker_value = get_piece_static_value(attacker.piece_type);
-        
-        // Simple evaluation - just look at material difference
-        victim_value - attacker_value
-    } else {
-        0
+    // gains[n]: material won by the side making the nth capture if it's the last
+    let mut gains = vec![victim_value];
+    let mut on_square = get_piece_static_value(attacker.piece_type);
+    let mut side = victim.color;
+    loop {
+        let cheapest = board.attackers(mv.to, side).into_iter()
+            .filter_map(|pos| board.get_piece(pos).map(|&piece| (pos, piece)))
+            .min_by_key(|(_, piece)| get_piece_static_value(piece.piece_type));
+        let Some((from, piece)) = cheapest else {
+            break;
+        };
+        gains.push(on_square - gains[gains.len() - 1]);
+        on_square = get_piece_static_value(piece.piece_type);
+        board.remove_piece(from);
+        board.set_piece(mv.to, Some(piece));
+        side = if side == Color::White { Color::Black } else { Color::White };
+    }
+    
+    // Back up the exchange: each side only recaptures if it pays
+    while gains.len() > 1 {
+        let last = gains.pop().unwrap();
+        let previous = gains.last_mut().unwrap();
+        *previous = -(-*previous).max(last);
     }
+    gains[0]
 }
 
 // More precise piece values for static evaluation
@@ -859,126 +1391,228 @@ fn get_piece_static_value(piece_type: PieceType) -> i32 {
     }
 } 
 
-// Checks if a position requires careful tactical play
-fn is_tactical_position(board: &Board) -> bool {
-    // Position is tactical if:
-    // 1. There are hanging pieces
-    // 2. There are multiple possible captures
-    // 3. Queens are on the board
-    let mut capture_count = 0;
-    let mut has_queen = false;
-
-    for rank in 1..=8 {
-        for file in 1..=8 {
-            let pos = chess_core::Position { rank, file };
-            if let Some(piece) = board.get_piece(pos) {
-                if piece.piece_type == PieceType::Queen {
-                    has_queen = true;
-                }
-                // Check if piece is hanging
-                if is_piece_hanging(board, pos) {
-                    return true;
-                }
-                // Count possible captures
-                let moves = board.get_valid_moves(pos);
-                for mv in moves {
-                    if board.get_piece(mv.to).is_some() {
-                        capture_count += 1;
-                        if capture_count > 2 {
-                            return true;
-                        }
-                    }
-                }
-            }
-        }
+// Material of the side to move minus the opponent's, kings left out
+fn material_balance(board: &Board) -> i32 {
+    board.occupied()
+        .filter(|(_, piece)| piece.piece_type != PieceType::King)
+        .map(|(_, piece)| {
+            let value = get_piece_static_value(piece.piece_type);
+            if piece.color == board.current_turn() { value } else { -value }
+        })
+        .sum()
+}
+
+// Mate scores are searched relative to the root (mate at ply n scores
+// MATE_SCORE - n), but stored in the table relative to the node itself so
+// the same entry stays valid when reached at a different distance.
+fn score_to_tt(score: i32, ply: u8) -> i32 {
+    if score > MATE_THRESHOLD {
+        score + ply as i32
+    } else if score < -MATE_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
     }
-    
-    has_queen && capture_count > 0
 }
 
-// Checks if a piece can be captured without losing material
-fn is_piece_hanging(board: &Board, pos: chess_core::Position) -> bool {
-    if let Some(piece) = board.get_piece(pos) {
-        let piece_value = get_piece_value(piece.piece_type);
-        
-        // Find the lowest value attacker
-        let mut min_attacker_value = i32::MAX;
-        for rank in 1..=8 {
-            for file in 1..=8 {
-                let attack_pos = chess_core::Position { rank, file };
-                if let Some(attacker) = board.get_piece(attack_pos) {
-                    if attacker.color != piece.color {
-                        let moves = board.get_valid_moves(attack_pos);
-                        if moves.iter().any(|m| m.to == pos) {
-                            min_attacker_value = min_attacker_value.min(get_piece_value(attacker.piece_type));
-                        }
-                    }
-                }
-            }
+// Converts a stored score back to root-relative for a node at this ply
+fn score_from_tt(score: i32, ply: u8) -> i32 {
+    if score > MATE_THRESHOLD {
+        score - ply as i32
+    } else if score < -MATE_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess_core::positions;
+
+    // Tactical suite for the reductions: mates in two that have to be found
+    // at depth 4. Depth 3 is one short for the rook mate, whose quiet first
+    // move is late in the ordering and gets reduced.
+    const MATES: [(&str, &str); 3] = [
+        (positions::BACK_RANK_MATE_IN_TWO_FEN, "e2e8"),
+        (positions::ROOK_MATE_IN_TWO_FEN, "c6c7"),
+        (positions::MIDDLEGAME_MATE_IN_TWO_FEN, "d2h6"),
+    ];
+
+    #[test]
+    fn reductions_keep_mates_in_two() {
+        let _engine = crate::ai::lock_engine_for_test();
+        for (fen, best) in MATES {
+            let board = Board::from_fen(fen).unwrap();
+            let result = search_deterministic(&board, 4).unwrap();
+            assert_eq!(result.best_move.to_uci(), best, "{}", fen);
+            assert!(result.score > MATE_THRESHOLD, "{}: no mate found, score {}", fen, result.score);
         }
-        
-        // Piece is hanging if it can be captured by a lower value piece
-        if min_attacker_value < piece_value {
-            return true;
+    }
+
+    // Best move and score at depth 3, recorded from the search as it stands.
+    // Search changes that are meant to be pure speedups (move generation,
+    // terminal detection) must leave these alone; update them deliberately
+    // when the search or evaluation is changed on purpose.
+    const FIXED_DEPTH_RESULTS: [(&str, &str, i32); 4] = [
+        (positions::START_FEN, "e2e3", -135),
+        (positions::KIWIPETE_FEN, "d5e6", 75),
+        (positions::ROOK_PAWN_ENDGAME_FEN, "b4f4", 10),
+        (positions::EN_PASSANT_FEN, "d1f3", -85),
+    ];
+
+    #[test]
+    fn fixed_depth_search_is_unchanged() {
+        let _engine = crate::ai::lock_engine_for_test();
+        for (fen, best, score) in FIXED_DEPTH_RESULTS {
+            let board = Board::from_fen(fen).unwrap();
+            let result = search_deterministic(&board, 3).unwrap();
+            assert_eq!((result.best_move.to_uci().as_str(), result.score), (best, score), "{}", fen);
         }
     }
-    false
-}
 
-// Calculates total material value on the board
-fn get_material_count(board: &Board) -> i32 {
-    let mut total = 0;
-    for rank in 1..=8 {
-        for file in 1..=8 {
-            let pos = chess_core::Position { rank, file };
-            if let Some(piece) = board.get_piece(pos) {
-                total += get_piece_static_value(piece.piece_type);
-            }
+    #[test]
+    fn deterministic_search_ignores_earlier_searches() {
+        let _engine = crate::ai::lock_engine_for_test();
+        let board = positions::kiwipete();
+        let first = search_deterministic(&board, 3).unwrap();
+        // Would leave its history and counter moves behind if they outlived it
+        search_to_depth(&positions::start(), 3, Duration::from_secs(60));
+        let second = search_deterministic(&board, 3).unwrap();
+        assert_eq!(
+            (first.best_move, first.score, first.nodes),
+            (second.best_move, second.score, second.nodes),
+        );
+    }
+
+    #[test]
+    fn underpromotes_to_a_knight_with_check() {
+        let _engine = crate::ai::lock_engine_for_test();
+        let board = Board::from_fen(positions::KNIGHT_PROMOTION_FEN).unwrap();
+        let result = search_deterministic(&board, 4).unwrap();
+        assert_eq!(result.best_move.to_san(&board), "e8=N+");
+        assert!(result.score > 0, "score {}", result.score);
+    }
+
+    #[test]
+    fn quiescence_scores_stalemate_as_a_draw() {
+        let _engine = crate::ai::lock_engine_for_test();
+        // A bare stalemate, and one where the only capture on offer is by a
+        // pinned knight
+        for fen in ["k7/2Q5/1K6/8/8/8/8/8 b - - 0 1", "k7/1nP5/1KBP4/8/8/8/8/8 b - - 0 1"] {
+            let board = Board::from_fen(fen).unwrap();
+            assert!(board.is_stalemate(), "{}", fen);
+            let score = quiescence_search(&board, -MATE_SCORE, MATE_SCORE, QUIESCENCE_DEPTH, 0, &mut SearchContext::new());
+            assert_eq!(score, draw_score(&board), "{}", fen);
         }
     }
-    total
-}
 
-// Adjusts mate scores based on distance to mate
-fn adjust_mate_score(score: i32, depth: u8) -> i32 {
-    if score > MATE_SCORE - 1000 {
-        // We found a mate - prefer shorter mates
-        score - depth as i32
-    } else if score < -MATE_SCORE + 1000 {
-        // We're getting mated - prefer longer mates
-        score + depth as i32
-    } else {
-        score
+    #[test]
+    fn killers_keep_the_two_latest_per_ply() {
+        let board = Board::new();
+        let moves: Vec<Move> = ["g1f3", "b1c3", "e2e4"].iter()
+            .map(|uci| Move::from_uci(uci, &board).unwrap())
+            .collect();
+        let mut ctx = SearchContext::new();
+        for &mv in &moves {
+            ctx.store_killer(2, mv);
+        }
+        ctx.store_killer(2, moves[2]);
+        assert_eq!(ctx.killers(2), [Some(moves[2]), Some(moves[1])]);
+        assert_eq!(ctx.killers(1), [None, None]);
     }
-} 
 
-// Updates the killer move table after a good quiet move
-fn update_killer_moves(killer_moves: &mut Option<[Move; 2]>, mv: Move) {
-    let moves = killer_moves.get_or_insert([create_default_move(); 2]);
-    
-    // Keep track of the two most recent killer moves
-    if moves[0].from != mv.from || moves[0].to != mv.to {
-        moves[1] = moves[0];
-        moves[0] = mv;
+    #[test]
+    fn losing_capture_is_ordered_after_the_quiet_moves() {
+        // Qxe5 wins a pawn and loses the queen to dxe5
+        let board = Board::from_fen("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1").unwrap();
+        let mut buffers = MoveBuffers::default();
+        generate_ordered_moves(&board, None, [None, None], None, &SearchContext::new(), false, &mut buffers);
+        // It's the only capture, and every quiet queen and king move goes first
+        let losing = Move::from_uci("e1e5", &board).unwrap();
+        assert!(buffers.moves.len() > 10);
+        assert_eq!(buffers.moves.last(), Some(&losing));
     }
-} 
 
-// Checks if a capture is clearly winning material
-fn is_clearly_winning_capture(board: &Board, mv: Move) -> bool {
-    if let Some(victim) = board.get_piece(mv.to) {
-        if let Some(attacker) = board.get_piece(mv.from) {
-            let victim_value = get_piece_value(victim.piece_type);
-            let attacker_value = get_piece_value(attacker.piece_type);
-            
-            // Only return true if we're winning significant material
-            if victim_value > attacker_value + 2 {
-                let mut new_board = board.clone();
-                if new_board.make_move(mv).is_ok() {
-                    // Make sure the piece isn't immediately recaptured
-                    return !is_piece_hanging(&new_board, mv.to);
-                }
-            }
+    #[test]
+    fn mate_distance_survives_table_hits() {
+        let _engine = crate::ai::lock_engine_for_test();
+        let board = positions::rook_mate_in_two();
+        let first = search_to_depth(&board, 4, Duration::from_secs(60)).unwrap();
+        assert_eq!(first.score, MATE_SCORE - 3);
+
+        // Straight from the table the second time, still mate in two
+        let again = search_to_depth(&board, 4, Duration::from_secs(60)).unwrap();
+        assert!(again.from_tt);
+        assert_eq!((again.best_move, again.score), (first.best_move, first.score));
+
+        // Two plies on, what's stored under the old root is a mate in one
+        let mut board = board;
+        for &mv in &first.pv[..2] {
+            board.make_move(mv).unwrap();
         }
+        let closer = search_to_depth(&board, 4, Duration::from_secs(60)).unwrap();
+        assert_eq!(closer.score, MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn finds_the_stalemate_saving_rook_check() {
+        let _engine = crate::ai::lock_engine_for_test();
+        // Black's king has no moves, so the rook checks until it's taken and
+        // the capture stalemates. Depth 4 is too shallow to score the draw,
+        // but it's enough to pick the check.
+        let board = positions::stalemate_trap();
+        let result = search_deterministic(&board, 4).unwrap();
+        assert_eq!(result.best_move.to_uci(), "h1h6");
+        assert!(board.with_move(result.best_move).unwrap().in_check());
+    }
+
+    #[test]
+    fn time_controls_allocate_as_documented() {
+        let quiet = positions::start();
+        let sharp = positions::en_passant();  // exd6 is on
+        let forced = Board::from_fen("k7/8/8/8/8/8/8/1R5K b - - 0 1").unwrap();  // Only Ka7
+        assert!(!sharp.is_quiet());
+        assert_eq!(forced.legal_moves().len(), 1);
+        let minute = Duration::from_secs(60);
+
+        let moves_to_go = |remaining, moves_left| TimeControl::MovesToGo { remaining, moves_left }.allocate(&quiet);
+        assert_eq!(moves_to_go(minute, Some(20)), Duration::from_secs(3));
+        assert_eq!(moves_to_go(minute, None), minute / MOVES_TO_GO);
+        assert_eq!(moves_to_go(Duration::from_secs(1), Some(40)), MIN_TIME_PER_MOVE);
+        assert_eq!(moves_to_go(Duration::from_secs(3600), Some(10)), MAX_TIME_PER_MOVE);
+
+        assert_eq!(TimeControl::FixedPerMove(Duration::from_secs(2)).allocate(&sharp), Duration::from_secs(2));
+
+        let tactical = TimeControl::Tactical { remaining: minute, moves_left: Some(20) };
+        assert_eq!(tactical.allocate(&quiet), Duration::from_secs(3));
+        assert_eq!(tactical.allocate(&sharp), Duration::from_millis(4500));
+
+        let economical = TimeControl::Economical { remaining: minute, moves_left: Some(20) };
+        assert_eq!(economical.allocate(&quiet), Duration::from_secs(3));
+        assert_eq!(economical.allocate(&forced), MIN_TIME_PER_MOVE);
     }
-    false
-} 
\ No newline at end of file
+
+    #[test]
+    fn node_limit_ends_the_search_quickly() {
+        let _engine = crate::ai::lock_engine_for_test();
+        let board = positions::kiwipete();
+        let minute = TimeControl::FixedPerMove(Duration::from_secs(60));
+        let result = search_best_move(&board, minute, Some(10_000), None, true).unwrap();
+        assert!(board.is_legal(result.best_move));
+        assert!(result.nodes >= 10_000 && result.nodes < 11_000, "{} nodes", result.nodes);
+        assert!(result.time < Duration::from_secs(30), "the limit, not the clock, ended it");
+    }
+
+    #[test]
+    fn deeper_iterations_reuse_the_table() {
+        let _engine = crate::ai::lock_engine_for_test();
+        let minute = TimeControl::FixedPerMove(Duration::from_secs(60));
+        let result = search_best_move(&positions::kiwipete(), minute, None, Some(5), true).unwrap();
+        let stats = result.tt_stats;
+        assert_eq!(result.depth, 5);
+        assert!(stats.stores > 0);
+        assert!(stats.hit_rate() > 0.1, "hit rate {:.3}", stats.hit_rate());
+    }
+}
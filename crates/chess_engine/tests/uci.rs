@@ -0,0 +1,35 @@
+// Drives the uci binary over stdin/stdout, the way a chess GUI would
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use chess_core::Board;
+
+#[test]
+fn stop_ends_an_infinite_search_with_a_bestmove() {
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_uci"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut input = engine.stdin.take().unwrap();
+
+    writeln!(input, "position startpos").unwrap();
+    writeln!(input, "go infinite").unwrap();
+    input.flush().unwrap();
+    thread::sleep(Duration::from_millis(500));
+    writeln!(input, "stop").unwrap();
+    writeln!(input, "quit").unwrap();
+    drop(input);
+
+    let output = BufReader::new(engine.stdout.take().unwrap());
+    let lines: Vec<String> = output.lines().map(Result::unwrap).collect();
+    assert!(engine.wait().unwrap().success());
+
+    let bestmoves: Vec<&String> = lines.iter().filter(|line| line.starts_with("bestmove ")).collect();
+    assert_eq!(bestmoves.len(), 1, "{:?}", lines);
+    let uci = bestmoves[0].split_whitespace().nth(1).unwrap();
+    let board = Board::new();
+    assert!(board.legal_moves().iter().any(|mv| mv.to_uci() == uci), "{} isn't legal", uci);
+}
@@ -1,16 +1,27 @@
 use bevy::{
     prelude::*,
-    window::{PresentMode, WindowResolution},
+    window::{PresentMode, ReceivedCharacter, WindowResolution},
     tasks::{AsyncComputeTaskPool, Task},
     sprite::Anchor,
 };
 use chess_core::{
-    Board, Position, Move,
-    piece::{PieceType as ChessPieceType, Color as ChessColor},
+    Board, Position, Move, MoveCache, GameResult, DrawReason, SquareColor,
+    piece::{PieceType as ChessPieceType, Color as ChessColor, Piece as ChessPiece},
 };
-use chess_engine::ChessAI;
+use chess_engine::{ChessAI, SearchResult, AiMove, MoveSource, Personality};
+use futures_lite::future;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-const SQUARE_SIZE: f32 = 80.0;
+const DEFAULT_SQUARE_SIZE: f32 = 80.0;
+const MIN_SQUARE_SIZE: f32 = 20.0;
+const BOARD_BORDER: f32 = 10.0;       // Frame around the squares on each side
+const TOP_BAR_HEIGHT: f32 = 50.0;
+const BOTTOM_BAR_HEIGHT: f32 = 40.0;
+const SAVE_FILE_PATH: &str = "chess_save.json";
+const TOAST_DURATION_SECS: f32 = 3.0;
+const FIFTY_MOVE_WARNING: u32 = 10;  // Moves left before the move counter shows the countdown
+const MOVE_CACHE_POSITIONS: usize = 256;  // Positions kept when the move cache is switched on
 
 pub struct ChessUiPlugin;
 
@@ -21,23 +32,54 @@ enum Turn {
     AI,
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
-enum GameEndState {
-    Checkmate(ChessColor),  // Color is the winner
-    Stalemate,
-    InsufficientMaterial,
-    Ongoing,
+// While the editor is active the game is paused and board clicks place pieces
+#[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+enum EditorMode {
+    #[default]
+    Inactive,
+    Active,
+}
+
+#[derive(Resource)]
+struct EditorState {
+    board: Board,               // Position being built, separate from the paused game
+    brush: Option<ChessPiece>,  // Piece placed on click, None erases
+}
+
+impl Default for EditorState {
+    fn default() -> Self {
+        Self {
+            board: Board::new(),
+            brush: Some(ChessPiece::new(ChessPieceType::Pawn, ChessColor::White)),
+        }
+    }
 }
 
 #[derive(Resource)]
 pub struct GameState {
     pub board: Board,
     pub selected_square: Option<Position>,
-    pub valid_moves: Vec<Move>,
+    pub valid_moves: Vec<Move>,         // Moves of the piece on selected_square
+    valid_moves_hash: Option<u64>,      // Position valid_moves was generated in
     pub ai: ChessAI,
     pub ai_thinking: bool,
-    pub game_end_state: GameEndState,
+    ai_task: Option<Task<(ChessAI, Option<AiMove>)>>,  // Background search for the AI's move
+    pub game_result: Option<GameResult>,  // Set once the game is over
     pub pending_promotion: Option<PendingPromotion>,
+    pub move_cache: Option<MoveCache>,  // Opt-in cache of legal moves per position
+    pub expected_line: Vec<String>,     // Engine's predicted continuation in SAN
+    pub ai_move_source: Option<MoveSource>,  // How the AI picked its last move
+    pub start_fen: Option<String>,      // Set when the game started from an edited position
+    pub takeback_request: Option<TakebackRequest>,
+    pub ai_color: ChessColor,           // Side the engine plays
+    pub view_ply: Option<usize>,        // Earlier position being reviewed; None shows the live game
+}
+
+// A request to take back the last `plies` half-moves. The AI opponent always
+// grants it; it's carried out once no piece is mid-animation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TakebackRequest {
+    pub plies: usize,
 }
 
 impl Default for GameState {
@@ -46,12 +88,140 @@ impl Default for GameState {
             board: Board::new(),
             ai: ChessAI::new(4),
             ai_thinking: false,
+            ai_task: None,
             selected_square: None,
             valid_moves: Vec::new(),
-            game_end_state: GameEndState::Ongoing,
+            valid_moves_hash: None,
+            game_result: None,
             pending_promotion: None,
+            move_cache: None,
+            expected_line: Vec::new(),
+            ai_move_source: None,
+            start_fen: None,
+            takeback_request: None,
+            ai_color: ChessColor::Black,
+            view_ply: None,
+        }
+    }
+}
+
+impl GameState {
+    fn player_color(&self) -> ChessColor {
+        match self.ai_color {
+            ChessColor::White => ChessColor::Black,
+            ChessColor::Black => ChessColor::White,
+        }
+    }
+
+    // Whose turn the current position is, given which side the AI plays
+    fn turn(&self) -> Turn {
+        if self.board.current_turn() == self.ai_color {
+            Turn::AI
+        } else {
+            Turn::Player
+        }
+    }
+
+    // Abandons the AI's search for a move, if it's thinking
+    fn cancel_ai_move(&mut self) {
+        if self.ai_task.take().is_some() {
+            ChessAI::stop_search();
+        }
+        self.ai_thinking = false;
+    }
+
+    // Puts a new game on the board, counting from `start_fen` (None for the
+    // usual starting position), and drops everything left over from the old
+    // one: the selection, the AI's search, the result, review, promotion and
+    // takeback requests. Callers that set those for the new game do so after.
+    fn reset_for_new_position(&mut self, board: Board, start_fen: Option<String>) {
+        self.board = board;
+        self.selected_square = None;
+        self.valid_moves.clear();
+        self.valid_moves_hash = None;
+        self.cancel_ai_move();
+        self.view_ply = None;
+        self.game_result = None;
+        self.pending_promotion = None;
+        self.expected_line.clear();
+        self.ai_move_source = None;
+        self.start_fen = start_fen;
+        self.takeback_request = None;
+        if let Some(cache) = self.move_cache.as_mut() {
+            cache.clear();
+        }
+    }
+
+    // Position the current game started from
+    fn start_board(&self) -> Board {
+        self.start_fen.as_deref()
+            .and_then(|fen| Board::from_fen(fen).ok())
+            .unwrap_or_else(Board::new)
+    }
+
+    // Rewinds the game by replaying all but the last `plies` moves from the start
+    fn take_back(&mut self, plies: usize) -> Result<(), &'static str> {
+        let history = self.board.move_history();
+        let keep = history.len().saturating_sub(plies);
+        let mut board = self.start_board();
+        for &mv in &history[..keep] {
+            board.make_move(mv)?;
+        }
+
+        self.board = board;
+        self.selected_square = None;
+        self.valid_moves.clear();
+        self.game_result = None;
+        self.pending_promotion = None;
+        self.expected_line.clear();
+        self.ai_move_source = None;
+        self.view_ply = None;
+        Ok(())
+    }
+
+    // The position after the first `ply` moves of the game
+    fn board_at(&self, ply: usize) -> Board {
+        let mut board = self.start_board();
+        for &mv in self.board.move_history().iter().take(ply) {
+            if board.make_move(mv).is_err() {
+                break;
+            }
+        }
+        board
+    }
+
+    // Valid moves for the selected piece, only regenerated when the selection
+    // or the position changes
+    fn selected_moves(&mut self, pos: Position) -> &[Move] {
+        let hash = self.board.zobrist_hash();
+        if self.selected_square != Some(pos) || self.valid_moves_hash != Some(hash) {
+            self.valid_moves = self.valid_moves_from(pos);
+            self.selected_square = Some(pos);
+            self.valid_moves_hash = Some(hash);
+        }
+        &self.valid_moves
+    }
+
+    // Legal moves for the piece at `pos`, served from the move cache when enabled
+    fn valid_moves_from(&mut self, pos: Position) -> Vec<Move> {
+        let moves = match self.move_cache.as_mut() {
+            Some(cache) => cache.legal_moves(&self.board),
+            None => self.board.legal_moves(),
+        };
+        moves.into_iter().filter(|m| m.from == pos).collect()
+    }
+
+    // Turns the move cache on or off. The AI's evaluation follows suit from
+    // its next search on.
+    fn toggle_move_cache(&mut self) {
+        if self.move_cache.take().is_none() {
+            self.move_cache = Some(MoveCache::new(MOVE_CACHE_POSITIONS));
         }
     }
+
+    fn move_cache_label(enabled: bool) -> &'static str {
+        if enabled { "Move Cache: On" } else { "Move Cache: Off" }
+    }
 }
 
 #[derive(Resource, Clone)]
@@ -69,6 +239,29 @@ pub struct ChessAssets {
     black_knight: Handle<Image>,
     black_pawn: Handle<Image>,
     valid_move: Handle<Image>,
+    capture_ring: Handle<Image>,
+    win_sound: Handle<AudioSource>,
+    draw_sound: Handle<AudioSource>,
+    loss_sound: Handle<AudioSource>,
+}
+
+impl ChessAssets {
+    fn piece_texture(&self, piece_type: ChessPieceType, is_white: bool) -> Handle<Image> {
+        match (piece_type, is_white) {
+            (ChessPieceType::King, true) => self.white_king.clone(),
+            (ChessPieceType::Queen, true) => self.white_queen.clone(),
+            (ChessPieceType::Rook, true) => self.white_rook.clone(),
+            (ChessPieceType::Bishop, true) => self.white_bishop.clone(),
+            (ChessPieceType::Knight, true) => self.white_knight.clone(),
+            (ChessPieceType::Pawn, true) => self.white_pawn.clone(),
+            (ChessPieceType::King, false) => self.black_king.clone(),
+            (ChessPieceType::Queen, false) => self.black_queen.clone(),
+            (ChessPieceType::Rook, false) => self.black_rook.clone(),
+            (ChessPieceType::Bishop, false) => self.black_bishop.clone(),
+            (ChessPieceType::Knight, false) => self.black_knight.clone(),
+            (ChessPieceType::Pawn, false) => self.black_pawn.clone(),
+        }
+    }
 }
 
 // Components
@@ -96,12 +289,20 @@ struct AiThinkingText;
 #[derive(Component)]
 struct ValidMoveIndicator;
 
+// Speed of every piece animation, in world units per second
+const PIECE_SPEED: f32 = 500.0;
+
 #[derive(Component)]
 struct MovingPiece {
     target_position: Vec3,
     speed: f32,
 }
 
+// A taken piece on its way off the board towards the captured-pieces tray.
+// It's no longer a Piece, and is despawned once it gets there.
+#[derive(Component)]
+struct CapturedPieceFlight;
+
 #[derive(Component)]
 struct GameStatusText;
 
@@ -114,889 +315,4398 @@ struct LastMoveText;
 #[derive(Component)]
 struct EvaluationText;
 
-// Add new component for game end overlay
 #[derive(Component)]
-struct GameEndOverlay;
+struct PvText;
 
 #[derive(Component)]
-struct PromotionDialog;
+struct ThinkInfoText;
 
 #[derive(Component)]
-struct PromotionButton {
-    piece_type: ChessPieceType,
+struct MoveCountText;
+
+#[derive(Component)]
+struct CapturedPiecesText;
+
+// Row of captured piece icons; `by_white` rows hold Black pieces taken by White
+#[derive(Component)]
+struct CapturedTrayRow {
+    by_white: bool,
 }
 
+#[derive(Component)]
+struct MaterialDiffText;
+
+const TRAY_ICON_SIZE: f32 = 18.0;
+
+// Number of predicted moves shown in the PV line
+const PV_DISPLAY_LENGTH: usize = 5;
+
+// Depth range selectable in the analysis panel
+const MIN_ANALYSIS_DEPTH: u8 = 1;
+const MAX_ANALYSIS_DEPTH: u8 = 12;
+const DEFAULT_ANALYSIS_DEPTH: u8 = 6;
+
+// Infinite-analysis mode: searches the current position to increasing depths
+// in the background and reports the results without ever making a move
 #[derive(Resource)]
-struct PendingPromotion {
-    from: Position,
-    to: Position,
+struct AnalysisState {
+    open: bool,
+    max_depth: u8,
+    position: Option<u64>,                         // Zobrist hash of the analyzed position
+    completed_depth: u8,
+    latest: Option<SearchResult>,
+    task: Option<Task<Option<SearchResult>>>,
+    stopping: bool,  // The running search was asked to stop; its result doesn't count
 }
 
-// Add this struct to hold move information
-#[derive(Clone, Copy)]
-struct MoveInfo {
-    from: Position,
-    to: Position,
-    is_promotion: bool,
+impl Default for AnalysisState {
+    fn default() -> Self {
+        Self {
+            open: false,
+            max_depth: DEFAULT_ANALYSIS_DEPTH,
+            position: None,
+            completed_depth: 0,
+            latest: None,
+            task: None,
+            stopping: false,
+        }
+    }
 }
 
-// Add this enum to represent the result of a move attempt
-enum MoveAttempt {
-    Invalid,
-    Promotion(Position, Position),
-    ValidMove(Move),
-}
+// The engine's settings, stop flag and transposition table are shared by
+// every search, so the AI, analysis and coach searches take turns: none of
+// them starts while another is running. That's also what makes it safe for
+// analysis to stop "the" search.
+impl AnalysisState {
+    // Asks the running search, if any, to stop. Its result will be dropped.
+    fn stop(&mut self) {
+        if self.task.is_some() && !self.stopping {
+            ChessAI::stop_search();
+            self.stopping = true;
+        }
+    }
 
-// Add this enum to represent the action to take after validation
-enum PlayerAction {
-    ShowPromotionDialog {
-        from: Position,
-        to: Position,
-    },
-    MakeMove {
-        chess_move: Move,
-        selected_entity: Entity,
-        captured_entity: Option<Entity>,
-    },
-    SelectPiece {
-        entity: Entity,
-        deselect_entity: Option<Entity>,
-    },
-    Deselect {
-        entity: Entity,
-    },
+    // Abandons the current results and stops the running search, if any.
+    // The stopped task is kept until it finishes so nothing else starts
+    // searching before it has let go of the engine.
+    fn reset(&mut self) {
+        self.stop();
+        self.position = None;
+        self.completed_depth = 0;
+        self.latest = None;
+    }
 }
 
-fn validate_player_move(board: &Board, piece: &Piece, target: Position) -> MoveAttempt {
-    let valid_moves = board.get_valid_moves(piece.position);
-    if let Some(valid_move) = valid_moves.iter().find(|m| m.to == target) {
-        let is_promotion = piece.piece_type == ChessPieceType::Pawn && 
-            ((piece.is_white && valid_move.to.rank == 8) ||
-             (!piece.is_white && valid_move.to.rank == 1));
+// Coaching: each of the player's moves is compared with the engine's choice
+// by how much evaluation it gave away, in centipawns for the player
+const COACH_DEPTH: u8 = 4;
+const COACH_SCORE_CAP: i32 = 1500;  // Mate scores are capped so a slower mate isn't a blunder
+const COACH_BEST_LOSS: i32 = 10;
+const COACH_GOOD_LOSS: i32 = 60;
+const COACH_INACCURACY_LOSS: i32 = 120;
+const COACH_MISTAKE_LOSS: i32 = 300;
 
-        if is_promotion {
-            MoveAttempt::Promotion(valid_move.from, valid_move.to)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MoveQuality {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveQuality {
+    fn from_loss(loss: i32) -> Self {
+        if loss <= COACH_BEST_LOSS {
+            MoveQuality::Best
+        } else if loss <= COACH_GOOD_LOSS {
+            MoveQuality::Good
+        } else if loss <= COACH_INACCURACY_LOSS {
+            MoveQuality::Inaccuracy
+        } else if loss <= COACH_MISTAKE_LOSS {
+            MoveQuality::Mistake
         } else {
-            MoveAttempt::ValidMove(*valid_move)
+            MoveQuality::Blunder
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            MoveQuality::Best => "Best",
+            MoveQuality::Good => "Good",
+            MoveQuality::Inaccuracy => "Inaccuracy",
+            MoveQuality::Mistake => "Mistake",
+            MoveQuality::Blunder => "Blunder",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            MoveQuality::Best => Color::rgb(0.4, 0.9, 0.4),
+            MoveQuality::Good => Color::rgb(0.7, 0.9, 0.6),
+            MoveQuality::Inaccuracy => Color::rgb(0.95, 0.85, 0.3),
+            MoveQuality::Mistake => Color::rgb(0.95, 0.6, 0.2),
+            MoveQuality::Blunder => Color::rgb(0.9, 0.25, 0.25),
         }
-    } else {
-        MoveAttempt::Invalid
     }
 }
 
-impl Plugin for ChessUiPlugin {
-    fn build(&self, app: &mut App) {
-        app.add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "Chess Engine".into(),
-                resolution: WindowResolution::new(800.0, 800.0),
-                present_mode: PresentMode::AutoVsync,
-                resizable: true,
-                ..default()
-            }),
-            ..default()
-        }))
-        .add_state::<Turn>()
-        .init_resource::<GameState>()
-        .add_systems(PreStartup, setup)
-        .add_systems(Update, (
-            handle_resize,
-            handle_input,
-            update_selected_pieces,
-            update_ai,
-            update_ui_text,
-            show_valid_moves,
-            update_piece_movement,
-            update_game_status,
-            handle_new_game_button,
-            update_last_move,
-            update_evaluation_text,
-            check_game_end,
-            update_game_end_overlay,
-            handle_promotion_selection,
-        ));
+// The coach's judgement of one of the player's moves
+#[derive(Debug, Clone)]
+struct CoachVerdict {
+    ply: usize,          // Index of the judged move in the game's history
+    played: Move,
+    played_san: String,
+    best: Move,
+    best_san: String,
+    quality: MoveQuality,
+}
+
+// Judges the player's latest move once it's their turn again, so the search
+// never competes with the AI choosing its reply
+#[derive(Resource, Default)]
+struct Coach {
+    enabled: bool,
+    judged: Option<(usize, Move)>,       // Ply and move last handed to the task
+    task: Option<Task<Option<CoachVerdict>>>,
+    verdict: Option<CoachVerdict>,
+}
+
+impl Coach {
+    fn label(&self) -> &'static str {
+        if self.enabled { "Coach: On" } else { "Coach: Off" }
     }
 }
 
-// System functions
-fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-) {
-    // Load assets
-    let chess_assets = ChessAssets {
-        white_king: asset_server.load("white_king.png"),
-        white_queen: asset_server.load("white_queen.png"),
-        white_rook: asset_server.load("white_rook.png"),
-        white_bishop: asset_server.load("white_bishop.png"),
-        white_knight: asset_server.load("white_knight.png"),
-        white_pawn: asset_server.load("white_pawn.png"),
-        black_king: asset_server.load("black_king.png"),
-        black_queen: asset_server.load("black_queen.png"),
-        black_rook: asset_server.load("black_rook.png"),
-        black_bishop: asset_server.load("black_bishop.png"),
-        black_knight: asset_server.load("black_knight.png"),
-        black_pawn: asset_server.load("black_pawn.png"),
-        valid_move: asset_server.load("valid_move.png"),
-    };
+#[derive(Component)]
+struct AnalysisButton;
 
-    commands.insert_resource(chess_assets.clone());
+#[derive(Component)]
+struct CoachButton;
 
-    // Camera
-    commands.spawn(Camera2dBundle::default());
+#[derive(Component)]
+struct CoachButtonText;
 
-    // Board
-    let board_size = 8.0;
-    let board_offset = Vec3::new(-board_size * SQUARE_SIZE / 2.0, -board_size * SQUARE_SIZE / 2.0, 0.0);
+#[derive(Component)]
+struct CoachText;
 
-    commands
-        .spawn((
-            SpriteBundle {
-                sprite: Sprite {
-                    color: Color::rgb(0.1, 0.1, 0.1),
-                    custom_size: Some(Vec2::new(board_size * SQUARE_SIZE + 20.0, board_size * SQUARE_SIZE + 20.0)),
-                    ..default()
-                },
-                transform: Transform::from_xyz(0.0, 0.0, 0.0),
-                ..default()
-            },
-            ChessBoard,
-        ));
+#[derive(Component)]
+struct CoachArrow;
 
-    // Squares
-    for rank in 0..8 {
-        for file in 0..8 {
-            let is_white = (rank + file) % 2 == 0;
-            let position = Vec3::new(
-                board_offset.x + file as f32 * SQUARE_SIZE + SQUARE_SIZE / 2.0,
-                board_offset.y + rank as f32 * SQUARE_SIZE + SQUARE_SIZE / 2.0,
-                1.0,
-            );
+#[derive(Component)]
+struct AnalysisPanel;
 
-            commands.spawn((
-                SpriteBundle {
-                    sprite: Sprite {
-                        color: if is_white {
-                            Color::rgb(0.9, 0.9, 0.9)
-                        } else {
-                            Color::rgb(0.3, 0.3, 0.3)
-                        },
-                        custom_size: Some(Vec2::new(SQUARE_SIZE, SQUARE_SIZE)),
-                        ..default()
-                    },
-                    transform: Transform::from_translation(position),
-                    ..default()
-                },
-                Square {
-                    position: Position {
-                        file: (file + 1) as u8,
-                        rank: (8 - rank) as u8,
-                    },
-                },
-            ));
-        }
-    }
+#[derive(Component)]
+struct AnalysisText;
 
-    // Initial pieces
-    let mut commands = commands;
-    spawn_initial_pieces(&mut commands, board_offset, &chess_assets);
-    
-    // UI
-    spawn_ui(&mut commands);
+#[derive(Component)]
+struct AnalysisDepthText;
+
+#[derive(Component)]
+struct AnalysisDepthButton {
+    delta: i8,
 }
 
-fn spawn_initial_pieces(
-    commands: &mut Commands,
-    board_offset: Vec3,
-    assets: &ChessAssets,
-) {
-    // Spawn white pieces
-    spawn_piece(commands, ChessPieceType::Rook, true, 1, 1, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::Knight, true, 2, 1, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::Bishop, true, 3, 1, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::Queen, true, 4, 1, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::King, true, 5, 1, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::Bishop, true, 6, 1, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::Knight, true, 7, 1, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::Rook, true, 8, 1, board_offset, assets);
-    for file in 1..=8 {
-        spawn_piece(commands, ChessPieceType::Pawn, true, file, 2, board_offset, assets);
-    }
+// Add new component for game end overlay
+#[derive(Component)]
+struct GameEndOverlay;
 
-    // Spawn black pieces
-    spawn_piece(commands, ChessPieceType::Rook, false, 1, 8, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::Knight, false, 2, 8, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::Bishop, false, 3, 8, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::Queen, false, 4, 8, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::King, false, 5, 8, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::Bishop, false, 6, 8, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::Knight, false, 7, 8, board_offset, assets);
-    spawn_piece(commands, ChessPieceType::Rook, false, 8, 8, board_offset, assets);
-    for file in 1..=8 {
-        spawn_piece(commands, ChessPieceType::Pawn, false, file, 7, board_offset, assets);
-    }
-}
+// Gold halo around the board while a game the player won is shown as over
+#[derive(Component)]
+struct GameEndGlow;
 
-fn spawn_piece(
-    commands: &mut Commands,
-    piece_type: ChessPieceType,
-    is_white: bool,
-    file: u8,
-    rank: u8,
-    board_offset: Vec3,
-    assets: &ChessAssets,
-) {
-    let texture = match (piece_type, is_white) {
-        (ChessPieceType::King, true) => assets.white_king.clone(),
-        (ChessPieceType::Queen, true) => assets.white_queen.clone(),
-        (ChessPieceType::Rook, true) => assets.white_rook.clone(),
-        (ChessPieceType::Bishop, true) => assets.white_bishop.clone(),
-        (ChessPieceType::Knight, true) => assets.white_knight.clone(),
-        (ChessPieceType::Pawn, true) => assets.white_pawn.clone(),
-        (ChessPieceType::King, false) => assets.black_king.clone(),
-        (ChessPieceType::Queen, false) => assets.black_queen.clone(),
-        (ChessPieceType::Rook, false) => assets.black_rook.clone(),
-        (ChessPieceType::Bishop, false) => assets.black_bishop.clone(),
-        (ChessPieceType::Knight, false) => assets.black_knight.clone(),
-        (ChessPieceType::Pawn, false) => assets.black_pawn.clone(),
-    };
+const GAME_END_GLOW_WIDTH: f32 = 12.0;  // How far the glow reaches past the board's frame
+
+#[derive(Component)]
+struct SaveGameButton;
+
+#[derive(Component)]
+struct TakebackButton;
+
+#[derive(Component)]
+struct ThreatsButton;
+
+#[derive(Component)]
+struct ThemeButton;
+
+#[derive(Component)]
+struct AiSideButton;
+
+#[derive(Component)]
+struct AiSideText;
+
+#[derive(Component)]
+struct PersonalityButton;
+
+#[derive(Component)]
+struct PersonalityText;
+
+#[derive(Component)]
+struct EloText;
+
+#[derive(Component)]
+struct CopyFenButton;
+
+#[derive(Component)]
+struct ClaimDrawButton;
+
+#[derive(Component)]
+struct BlindfoldButton;
+
+#[derive(Component)]
+struct ResignButton;
+
+#[derive(Component)]
+struct ConfirmToggleButton;
+
+#[derive(Component)]
+struct ConfirmToggleText;
+
+#[derive(Component)]
+struct ConfirmOverlay;
+
+#[derive(Component)]
+struct MoveCacheButton;
+
+#[derive(Component)]
+struct MoveCacheText;
+
+#[derive(Component)]
+struct ConfirmAnswerButton {
+    yes: bool,
+}
+
+// Actions that throw the current game away and are asked about first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfirmAction {
+    NewGame,
+    Resign,
+}
+
+impl ConfirmAction {
+    fn question(self) -> &'static str {
+        match self {
+            ConfirmAction::NewGame => "Start a new game? Current game will be lost.",
+            ConfirmAction::Resign => "Resign this game?",
+        }
+    }
+}
+
+// The confirmation waiting for an answer, if any. Turning it off makes
+// New Game and Resign act straight away.
+#[derive(Resource)]
+struct ConfirmDialog {
+    enabled: bool,
+    pending: Option<ConfirmAction>,
+}
+
+impl Default for ConfirmDialog {
+    fn default() -> Self {
+        Self { enabled: true, pending: None }
+    }
+}
+
+impl ConfirmDialog {
+    fn label(&self) -> &'static str {
+        if self.enabled { "Confirm: On" } else { "Confirm: Off" }
+    }
+}
+
+#[derive(Component)]
+struct ReviewBackButton;
+
+#[derive(Component)]
+struct ReviewForwardButton;
+
+#[derive(Component)]
+struct ReviewLiveButton;
+
+#[derive(Component)]
+struct ReviewText;
+
+#[derive(Component)]
+struct SquareInfoButton;
+
+#[derive(Component)]
+struct SquareTooltip;
+
+#[derive(Component)]
+struct PuzzleButton;
+
+#[derive(Component)]
+struct FlipBoardButton;
+
+#[derive(Component)]
+struct TrainingButton;
+
+#[derive(Component)]
+struct TrainingText;
+
+#[derive(Component)]
+struct MoveEntryButton;
+
+#[derive(Component)]
+struct MoveEntryText;
+
+#[derive(Component)]
+struct BlindfoldText;
+
+// Colors used to draw the board
+#[derive(Resource, Clone, Copy)]
+struct BoardTheme {
+    name: &'static str,
+    light: Color,
+    dark: Color,
+    highlight: Color,   // Tint for valid move markers
+    background: Color,  // Frame around the squares
+}
+
+const BOARD_THEMES: [BoardTheme; 4] = [
+    BoardTheme {
+        name: "Classic",
+        light: Color::rgb(0.9, 0.9, 0.9),
+        dark: Color::rgb(0.3, 0.3, 0.3),
+        highlight: Color::WHITE,
+        background: Color::rgb(0.1, 0.1, 0.1),
+    },
+    BoardTheme {
+        name: "Brown",
+        light: Color::rgb(0.94, 0.85, 0.71),
+        dark: Color::rgb(0.71, 0.53, 0.39),
+        highlight: Color::rgb(0.8, 0.9, 0.4),
+        background: Color::rgb(0.3, 0.2, 0.12),
+    },
+    BoardTheme {
+        name: "Blue",
+        light: Color::rgb(0.87, 0.89, 0.9),
+        dark: Color::rgb(0.55, 0.64, 0.75),
+        highlight: Color::rgb(0.4, 0.8, 1.0),
+        background: Color::rgb(0.13, 0.18, 0.26),
+    },
+    BoardTheme {
+        name: "Green",
+        light: Color::rgb(0.93, 0.93, 0.82),
+        dark: Color::rgb(0.46, 0.59, 0.34),
+        highlight: Color::rgb(1.0, 1.0, 0.5),
+        background: Color::rgb(0.15, 0.22, 0.12),
+    },
+];
+
+impl Default for BoardTheme {
+    fn default() -> Self {
+        BOARD_THEMES[0]
+    }
+}
+
+impl BoardTheme {
+    fn square_color(&self, pos: Position) -> Color {
+        match pos.square_color() {
+            SquareColor::Light => self.light,
+            SquareColor::Dark => self.dark,
+        }
+    }
+
+    // Preset following this one, wrapping around
+    fn next(&self) -> Self {
+        let index = BOARD_THEMES.iter().position(|t| t.name == self.name).unwrap_or(0);
+        BOARD_THEMES[(index + 1) % BOARD_THEMES.len()]
+    }
+}
+
+// Tint over a hanging piece while the threats overlay is on
+#[derive(Component)]
+struct ThreatHighlight;
+
+// Size and placement of the board. The squares scale so the board fills the
+// window between the top and bottom bars while staying square.
+#[derive(Resource, Clone, Copy, PartialEq)]
+struct BoardLayout {
+    square_size: f32,
+    offset: Vec3,   // World position of the board's center
+    flipped: bool,  // Black's side at the bottom
+}
+
+impl Default for BoardLayout {
+    fn default() -> Self {
+        Self {
+            square_size: DEFAULT_SQUARE_SIZE,
+            offset: Vec3::new(0.0, (BOTTOM_BAR_HEIGHT - TOP_BAR_HEIGHT) / 2.0, 0.0),
+            flipped: false,
+        }
+    }
+}
+
+impl BoardLayout {
+    fn fit(window: &Window, flipped: bool) -> Self {
+        let height = window.height() - TOP_BAR_HEIGHT - BOTTOM_BAR_HEIGHT;
+        let side = window.width().min(height) - 2.0 * BOARD_BORDER;
+        Self {
+            square_size: (side / 8.0).max(MIN_SQUARE_SIZE),
+            flipped,
+            ..default()
+        }
+    }
+
+    // Files and ranks counted from the bottom-left square on screen
+    fn screen_coords(&self, pos: Position) -> (u8, u8) {
+        if self.flipped {
+            (9 - pos.file, 9 - pos.rank)
+        } else {
+            (pos.file, pos.rank)
+        }
+    }
+
+    fn board_size(&self) -> f32 {
+        8.0 * self.square_size
+    }
+
+    // Window coordinates (y down) of the board's top-left corner
+    fn window_origin(&self, window: &Window) -> Vec2 {
+        let window_size = Vec2::new(window.width(), window.height());
+        (window_size - Vec2::splat(self.board_size())) / 2.0 + Vec2::new(self.offset.x, -self.offset.y)
+    }
+}
+
+#[derive(Resource, Default)]
+struct ThreatOverlay {
+    enabled: bool,
+}
+
+// Hover tooltip counting the pieces that attack each square
+#[derive(Resource, Default)]
+struct SquareInfo {
+    enabled: bool,
+}
+
+// A training position and the line that solves it. Moves are in SAN or UCI
+// and alternate between the player and the opponent's replies.
+struct Puzzle {
+    fen: &'static str,
+    solution: &'static str,
+}
+
+const PUZZLES: [Puzzle; 5] = [
+    Puzzle { fen: "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", solution: "Rd8#" },
+    Puzzle { fen: "6k1/5ppp/8/8/8/8/1r3PPP/6K1 b - - 0 1", solution: "Rb1#" },
+    Puzzle {
+        fen: "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+        solution: "Qxf7#",
+    },
+    Puzzle { fen: "q3k3/8/8/1N6/8/8/8/4K3 w - - 0 1", solution: "Nc7+ Kd7 Nxa8" },
+    Puzzle { fen: "2r3k1/5ppp/8/8/8/8/4RPPP/4Q1K1 w - - 0 1", solution: "Re8+ Rxe8 Qxe8#" },
+];
+
+// Puzzle mode: the player has to find the solution's moves, and the
+// opponent's replies are played from it instead of by the AI
+#[derive(Resource, Default)]
+struct PuzzleState {
+    next: usize,                  // Index into PUZZLES of the one to load next
+    active: Option<ActivePuzzle>,
+}
+
+struct ActivePuzzle {
+    solution: Vec<Move>,
+    solved: usize,                // Plies of the solution played so far
+}
+
+// Coordinate training: a square is named and the player clicks it. The game
+// underneath is left alone, and can't be played, while a round is running.
+#[derive(Resource, Default)]
+struct CoordinateTraining {
+    target: Option<Position>,     // Square to find; None when training is off
+    asked_at: f32,                // Elapsed seconds when the target was named
+    correct: u32,
+    attempts: u32,
+    correct_secs: f32,            // Time taken over the correct answers
+}
+
+impl CoordinateTraining {
+    fn start(&mut self, now: f32) {
+        *self = Self::default();
+        self.next_target(now);
+    }
+
+    fn next_target(&mut self, now: f32) {
+        let mut rng = rand::thread_rng();
+        let previous = self.target;
+        // Never ask for the same square twice in a row
+        let square = loop {
+            let square = Position { rank: rng.gen_range(1..=8), file: rng.gen_range(1..=8) };
+            if Some(square) != previous {
+                break square;
+            }
+        };
+        self.target = Some(square);
+        self.asked_at = now;
+    }
+
+    fn score(&self) -> String {
+        if self.correct == 0 {
+            format!("{}/{}", self.correct, self.attempts)
+        } else {
+            format!("{}/{}, {:.1}s each", self.correct, self.attempts, self.correct_secs / self.correct as f32)
+        }
+    }
+}
+
+// Keyboard move entry: click the box, type a move in SAN or UCI and press Enter
+#[derive(Resource, Default)]
+struct MoveEntry {
+    text: String,
+    focused: bool,
+}
+
+const MOVE_ENTRY_MAX_LENGTH: usize = 8;
+
+// Blindfold practice: which pieces stay on screen. Moves are still played by
+// clicking squares, so the game carries on as normal underneath.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq)]
+enum Blindfold {
+    #[default]
+    Off,
+    Opponent,    // Hide the AI's pieces
+    All,         // Hide every piece
+    AllNoHints,  // Hide every piece and the valid move markers
+}
+
+impl Blindfold {
+    fn next(self) -> Self {
+        match self {
+            Blindfold::Off => Blindfold::Opponent,
+            Blindfold::Opponent => Blindfold::All,
+            Blindfold::All => Blindfold::AllNoHints,
+            Blindfold::AllNoHints => Blindfold::Off,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Blindfold::Off => "Blindfold: Off",
+            Blindfold::Opponent => "Blindfold: AI",
+            Blindfold::All => "Blindfold: All",
+            Blindfold::AllNoHints => "Blindfold: All, no hints",
+        }
+    }
+
+    fn hides(self, piece_is_white: bool, ai_color: ChessColor) -> bool {
+        match self {
+            Blindfold::Off => false,
+            Blindfold::Opponent => piece_is_white == (ai_color == ChessColor::White),
+            Blindfold::All | Blindfold::AllNoHints => true,
+        }
+    }
+}
+
+#[derive(Component)]
+struct EditorButton;
+
+#[derive(Component)]
+struct EditorPanel;
+
+// Palette entry; None is the eraser
+#[derive(Component)]
+struct EditorPaletteButton {
+    piece: Option<ChessPiece>,
+}
+
+#[derive(Component)]
+struct EditorTurnButton;
+
+#[derive(Component)]
+struct EditorTurnText;
+
+#[derive(Component)]
+struct EditorClearButton;
+
+#[derive(Component)]
+struct EditorPlayButton;
+
+#[derive(Component)]
+struct EditorCancelButton;
+
+#[derive(Component)]
+struct LoadGameButton;
+
+// Short-lived message shown at the bottom of the screen
+#[derive(Component)]
+struct Toast {
+    timer: Timer,
+}
+
+// What goes into a save file. The board is rebuilt by replaying the moves,
+// which restores turn, castling rights and en passant state as well.
+#[derive(Serialize, Deserialize)]
+struct SavedGame {
+    start_fen: Option<String>,  // None for the standard starting position
+    moves: Vec<Move>,
+    result: Option<GameResult>,
+}
+
+#[derive(Component)]
+struct PromotionDialog;
+
+#[derive(Component)]
+struct PromotionButton {
+    piece_type: ChessPieceType,
+}
+
+#[derive(Resource)]
+struct PendingPromotion {
+    from: Position,
+    to: Position,
+}
+
+// Add this struct to hold move information
+#[derive(Clone, Copy)]
+struct MoveInfo {
+    from: Position,
+    to: Position,
+    is_promotion: bool,
+}
+
+// Add this enum to represent the result of a move attempt
+enum MoveAttempt {
+    Invalid,
+    Promotion(Position, Position),
+    ValidMove(Move),
+}
+
+// Add this enum to represent the action to take after validation
+enum PlayerAction {
+    ShowPromotionDialog {
+        from: Position,
+        to: Position,
+    },
+    MakeMove {
+        chess_move: Move,
+        selected_entity: Entity,
+    },
+    SelectPiece {
+        entity: Entity,
+        deselect_entity: Option<Entity>,
+    },
+    Deselect {
+        entity: Entity,
+    },
+}
+
+fn validate_player_move(board: &Board, piece: &Piece, target: Position) -> MoveAttempt {
+    let valid_moves = board.get_valid_moves(piece.position);
+    if let Some(valid_move) = valid_moves.iter().find(|m| m.to == target) {
+        let is_promotion = piece.piece_type == ChessPieceType::Pawn && 
+            ((piece.is_white && valid_move.to.rank == 8) ||
+             (!piece.is_white && valid_move.to.rank == 1));
+
+        if is_promotion {
+            MoveAttempt::Promotion(valid_move.from, valid_move.to)
+        } else {
+            MoveAttempt::ValidMove(*valid_move)
+        }
+    } else {
+        MoveAttempt::Invalid
+    }
+}
+
+impl Plugin for ChessUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                title: "Chess Engine".into(),
+                resolution: WindowResolution::new(800.0, 800.0),
+                present_mode: PresentMode::AutoVsync,
+                resizable: true,
+                ..default()
+            }),
+            ..default()
+        }))
+        .add_state::<Turn>()
+        .add_state::<EditorMode>()
+        .init_resource::<GameState>()
+        .init_resource::<EditorState>()
+        .init_resource::<ThreatOverlay>()
+        .init_resource::<Blindfold>()
+        .init_resource::<SquareInfo>()
+        .init_resource::<PuzzleState>()
+        .init_resource::<CoordinateTraining>()
+        .init_resource::<MoveEntry>()
+        .init_resource::<ConfirmDialog>()
+        .init_resource::<BoardTheme>()
+        .init_resource::<BoardLayout>()
+        .init_resource::<AnalysisState>()
+        .init_resource::<Coach>()
+        .add_systems(PreStartup, setup)
+        .add_systems(Update, (
+            handle_resize,
+            handle_input,
+            update_selected_pieces,
+            update_ai,
+            update_ui_text,
+            show_valid_moves,
+            update_piece_movement,
+            update_game_status.after(check_game_end),
+            handle_new_game_button,
+            update_last_move,
+            update_pv_text,
+            update_think_info_text,
+            update_evaluation_text,
+            // After every system that can change the board, so a game that
+            // just ended shows as over in the same frame
+            check_game_end
+                .after(handle_input)
+                .after(update_ai)
+                .after(handle_promotion_selection)
+                .after(handle_move_entry)
+                .after(update_puzzle)
+                .after(handle_puzzle_button)
+                .after(handle_load_game_button)
+                .after(handle_editor_play)
+                .after(resolve_takeback),
+            update_game_end_overlay.after(check_game_end),
+            handle_promotion_selection,
+            handle_analysis_buttons,
+            // The coach gets the engine first whenever analysis is between depths
+            run_analysis.after(run_coach),
+            update_analysis_panel,
+        ))
+        .add_systems(Update, (
+            handle_save_game_button,
+            handle_load_game_button,
+            update_toasts,
+            update_move_count_text,
+            update_elo_text,
+            update_captured_pieces_text,
+            update_captured_tray,
+            handle_square_info_button,
+            update_square_tooltip,
+            handle_puzzle_button,
+            update_puzzle,
+            handle_move_entry,
+            handle_resign_button,
+            handle_confirm_dialog,
+            handle_confirm_toggle,
+            handle_flip_button,
+            handle_training_button,
+            update_training,
+        ))
+        .add_systems(Update, (
+            handle_editor_toggle,
+            handle_editor_palette,
+            handle_editor_play,
+            handle_editor_input,
+            update_editor_panel,
+            handle_takeback_button,
+            resolve_takeback,
+            handle_threats_button,
+            update_threat_highlights,
+            handle_theme_button,
+            apply_board_theme,
+            handle_ai_side_button,
+            handle_personality_button,
+            handle_copy_fen,
+            handle_claim_draw,
+            update_claim_draw_button,
+            handle_blindfold_button,
+            apply_blindfold,
+            handle_review,
+            update_review_text,
+        ))
+        .add_systems(Update, (
+            handle_coach_button,
+            run_coach,
+            update_coach_display.after(run_coach),
+            handle_move_cache_button,
+            close_abandoned_promotion_dialog,
+        ));
+    }
+}
+
+// System functions
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    theme: Res<BoardTheme>,
+    layout: Res<BoardLayout>,
+) {
+    // Load assets
+    let chess_assets = ChessAssets {
+        white_king: asset_server.load("white_king.png"),
+        white_queen: asset_server.load("white_queen.png"),
+        white_rook: asset_server.load("white_rook.png"),
+        white_bishop: asset_server.load("white_bishop.png"),
+        white_knight: asset_server.load("white_knight.png"),
+        white_pawn: asset_server.load("white_pawn.png"),
+        black_king: asset_server.load("black_king.png"),
+        black_queen: asset_server.load("black_queen.png"),
+        black_rook: asset_server.load("black_rook.png"),
+        black_bishop: asset_server.load("black_bishop.png"),
+        black_knight: asset_server.load("black_knight.png"),
+        black_pawn: asset_server.load("black_pawn.png"),
+        valid_move: asset_server.load("valid_move.png"),
+        capture_ring: asset_server.load("capture_ring.png"),
+        win_sound: asset_server.load("game_win.wav"),
+        draw_sound: asset_server.load("game_draw.wav"),
+        loss_sound: asset_server.load("game_loss.wav"),
+    };
+
+    commands.insert_resource(chess_assets.clone());
+
+    // Camera
+    commands.spawn(Camera2dBundle::default());
+
+    // Board
+    let frame_size = layout.board_size() + 2.0 * BOARD_BORDER;
+
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: theme.background,
+                    custom_size: Some(Vec2::new(frame_size, frame_size)),
+                    ..default()
+                },
+                transform: Transform::from_translation(layout.offset),
+                ..default()
+            },
+            ChessBoard,
+        ));
+
+    // Squares
+    for rank in 0..8 {
+        for file in 0..8 {
+            let square = Position {
+                file: (file + 1) as u8,
+                rank: (8 - rank) as u8,
+            };
+            let position = board_position_to_world(square, 1.0, &layout);
+
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: theme.square_color(square),
+                        custom_size: Some(Vec2::splat(layout.square_size)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(position),
+                    ..default()
+                },
+                Square {
+                    position: square,
+                },
+            ));
+        }
+    }
+
+    // Initial pieces
+    let mut commands = commands;
+    spawn_initial_pieces(&mut commands, &layout, &chess_assets);
+    
+    // UI
+    spawn_ui(&mut commands);
+    spawn_analysis_panel(&mut commands);
+    spawn_captured_tray(&mut commands);
+    spawn_editor_panel(&mut commands);
+    spawn_square_tooltip(&mut commands);
+}
+
+fn spawn_initial_pieces(
+    commands: &mut Commands,
+    layout: &BoardLayout,
+    assets: &ChessAssets,
+) {
+    // Spawn white pieces
+    spawn_piece(commands, ChessPieceType::Rook, true, 1, 1, layout, assets);
+    spawn_piece(commands, ChessPieceType::Knight, true, 2, 1, layout, assets);
+    spawn_piece(commands, ChessPieceType::Bishop, true, 3, 1, layout, assets);
+    spawn_piece(commands, ChessPieceType::Queen, true, 4, 1, layout, assets);
+    spawn_piece(commands, ChessPieceType::King, true, 5, 1, layout, assets);
+    spawn_piece(commands, ChessPieceType::Bishop, true, 6, 1, layout, assets);
+    spawn_piece(commands, ChessPieceType::Knight, true, 7, 1, layout, assets);
+    spawn_piece(commands, ChessPieceType::Rook, true, 8, 1, layout, assets);
+    for file in 1..=8 {
+        spawn_piece(commands, ChessPieceType::Pawn, true, file, 2, layout, assets);
+    }
+
+    // Spawn black pieces
+    spawn_piece(commands, ChessPieceType::Rook, false, 1, 8, layout, assets);
+    spawn_piece(commands, ChessPieceType::Knight, false, 2, 8, layout, assets);
+    spawn_piece(commands, ChessPieceType::Bishop, false, 3, 8, layout, assets);
+    spawn_piece(commands, ChessPieceType::Queen, false, 4, 8, layout, assets);
+    spawn_piece(commands, ChessPieceType::King, false, 5, 8, layout, assets);
+    spawn_piece(commands, ChessPieceType::Bishop, false, 6, 8, layout, assets);
+    spawn_piece(commands, ChessPieceType::Knight, false, 7, 8, layout, assets);
+    spawn_piece(commands, ChessPieceType::Rook, false, 8, 8, layout, assets);
+    for file in 1..=8 {
+        spawn_piece(commands, ChessPieceType::Pawn, false, file, 7, layout, assets);
+    }
+}
+
+fn spawn_piece(
+    commands: &mut Commands,
+    piece_type: ChessPieceType,
+    is_white: bool,
+    file: u8,
+    rank: u8,
+    layout: &BoardLayout,
+    assets: &ChessAssets,
+) {
+    let texture = assets.piece_texture(piece_type, is_white);
 
     let position = Position { rank, file };
-    let world_pos = board_position_to_world(position, 2.0);
+    let world_pos = board_position_to_world(position, 2.0, layout);
+
+    commands.spawn((
+        SpriteBundle {
+            texture,
+            transform: Transform::from_translation(world_pos)
+                .with_scale(Vec3::splat(1.0)),
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(layout.square_size * 0.8)),
+                anchor: Anchor::Center,
+                ..default()
+            },
+            ..default()
+        },
+        Piece {
+            piece_type,
+            is_white,
+            position,
+        },
+    ));
+}
+
+// Fits the board to the window and lays out the squares and resting pieces
+fn handle_resize(
+    windows: Query<&Window>,
+    mut layout: ResMut<BoardLayout>,
+    mut board_query: Query<(&mut Transform, &mut Sprite), With<ChessBoard>>,
+    mut square_query: Query<(&mut Transform, &mut Sprite, &Square), (With<Square>, Without<ChessBoard>)>,
+    mut piece_query: Query<(&mut Transform, &mut Sprite, &Piece), PieceAtRest>,
+    mut glow_query: Query<(&mut Transform, &mut Sprite), GlowOnly>,
+) {
+    if let Ok(window) = windows.get_single() {
+        let fitted = BoardLayout::fit(window, layout.flipped);
+        if *layout != fitted {
+            *layout = fitted;
+        }
+    }
+    let frame_size = layout.board_size() + 2.0 * BOARD_BORDER;
+    
+    // Update board
+    if let Ok((mut transform, mut sprite)) = board_query.get_single_mut() {
+        sprite.custom_size = Some(Vec2::new(frame_size, frame_size));
+        transform.translation.x = layout.offset.x;
+        transform.translation.y = layout.offset.y;
+    }
+    for (mut transform, mut sprite) in glow_query.iter_mut() {
+        sprite.custom_size = Some(Vec2::splat(frame_size + 2.0 * GAME_END_GLOW_WIDTH));
+        transform.translation.x = layout.offset.x;
+        transform.translation.y = layout.offset.y;
+    }
+
+    // Update squares
+    for (mut transform, mut sprite, square) in square_query.iter_mut() {
+        sprite.custom_size = Some(Vec2::splat(layout.square_size));
+        transform.translation = board_position_to_world(square.position, 1.0, &layout);
+    }
+
+    // Update pieces; sliding ones are left to finish their move
+    for (mut transform, mut sprite, piece) in piece_query.iter_mut() {
+        sprite.custom_size = Some(Vec2::splat(layout.square_size * 0.9));
+        transform.translation = board_position_to_world(piece.position, 2.0, &layout);
+    }
+}
+
+fn handle_input(
+    mut commands: Commands,
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut game_state: ResMut<GameState>,
+    mut pieces: Query<(Entity, &mut Piece, &mut Transform)>,
+    selected_pieces: Query<Entity, With<SelectedPiece>>,
+    chess_assets: Res<ChessAssets>,
+    buttons: Res<Input<MouseButton>>,
+    turn: Res<State<Turn>>,
+    mut turn_state: ResMut<NextState<Turn>>,
+    editor_mode: Res<State<EditorMode>>,
+    puzzles: Res<PuzzleState>,
+    layout: Res<BoardLayout>,
+    confirm: Res<ConfirmDialog>,
+    training: Res<CoordinateTraining>,
+) {
+    // Only process during player's turn, and not while editing a position,
+    // looking back at an earlier one, answering a confirmation or training
+    if *turn.get() != Turn::Player || *editor_mode.get() == EditorMode::Active
+        || game_state.view_ply.is_some() || confirm.pending.is_some()
+        || training.target.is_some() {
+        return;
+    }
+
+    let window = windows.single();
+    let player_white = game_state.player_color() == ChessColor::White;
+    
+    if let Some(cursor_pos) = window.cursor_position() {
+        if let Some(position) = get_board_position(Some(cursor_pos), window, &layout) {
+            if buttons.just_pressed(MouseButton::Left) {
+                // First, determine what action to take
+                let action = if let Some(selected_entity) = selected_pieces.iter().next() {
+                    if let Some((_, piece, _)) = pieces.iter().find(|(e, _, _)| *e == selected_entity) {
+                        let valid_moves = game_state.selected_moves(piece.position).to_vec();
+                        if let Some(valid_move) = valid_moves.iter().find(|m| m.to == position) {
+                            let is_promotion = piece.piece_type == ChessPieceType::Pawn && 
+                                ((piece.is_white && valid_move.to.rank == 8) ||
+                                 (!piece.is_white && valid_move.to.rank == 1));
+
+                            if is_promotion {
+                                Some(PlayerAction::ShowPromotionDialog {
+                                    from: valid_move.from,
+                                    to: valid_move.to,
+                                })
+                            } else {
+                                Some(PlayerAction::MakeMove {
+                                    chess_move: *valid_move,
+                                    selected_entity,
+                                })
+                            }
+                        } else if let Some((entity, _, _)) = pieces.iter().find(|(_, p, _)| {
+                            p.position == position && p.is_white == player_white
+                        }) {
+                            Some(PlayerAction::SelectPiece {
+                                entity,
+                                deselect_entity: Some(selected_entity),
+                            })
+                        } else {
+                            Some(PlayerAction::Deselect {
+                                entity: selected_entity,
+                            })
+                        }
+                    } else {
+                        Some(PlayerAction::Deselect {
+                            entity: selected_entity,
+                        })
+                    }
+                } else if let Some((entity, _, _)) = pieces.iter().find(|(_, p, _)| {
+                    p.position == position && p.is_white == player_white
+                }) {
+                    Some(PlayerAction::SelectPiece {
+                        entity,
+                        deselect_entity: None,
+                    })
+                } else {
+                    None
+                };
+
+                // Then execute the action
+                if let Some(action) = action {
+                    match action {
+                        PlayerAction::ShowPromotionDialog { from, to } => {
+                            game_state.pending_promotion = Some(PendingPromotion { from, to });
+                            spawn_promotion_dialog(&mut commands, &chess_assets, player_white, &layout);
+                        }
+                        PlayerAction::MakeMove { chess_move, selected_entity } => {
+                            if play_player_move(
+                                &mut commands,
+                                &mut game_state,
+                                &mut pieces,
+                                chess_move,
+                                &layout,
+                                &chess_assets,
+                                &puzzles,
+                                &mut turn_state,
+                            ) {
+                                commands.entity(selected_entity).remove::<SelectedPiece>();
+                            }
+                        }
+                        PlayerAction::SelectPiece { entity, deselect_entity } => {
+                            if let Some(old_entity) = deselect_entity {
+                                commands.entity(old_entity).remove::<SelectedPiece>();
+                            }
+                            commands.entity(entity).insert(SelectedPiece);
+                        }
+                        PlayerAction::Deselect { entity } => {
+                            commands.entity(entity).remove::<SelectedPiece>();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn update_selected_pieces(
+    mut pieces: Query<(&mut Sprite, Option<&SelectedPiece>), With<Piece>>,
+) {
+    for (mut sprite, selected) in pieces.iter_mut() {
+        if selected.is_some() {
+            sprite.color = sprite.color.with_a(0.7);
+        } else {
+            sprite.color = sprite.color.with_a(1.0);
+        }
+    }
+}
+
+fn update_ai(
+    mut game_state: ResMut<GameState>,
+    mut commands: Commands,
+    mut pieces: Query<(Entity, &mut Piece, &mut Transform)>,
+    mut turn_state: ResMut<NextState<Turn>>,
+    turn: Res<State<Turn>>,
+    chess_assets: Res<ChessAssets>,
+    layout: Res<BoardLayout>,
+    mut analysis: ResMut<AnalysisState>,
+    coach: Res<Coach>,
+) {
+    // Only process during AI's turn
+    if *turn.get() != Turn::AI {
+        return;
+    }
+
+    // Think in the background so the window stays responsive. The AI goes
+    // along with the task and comes back with its move.
+    let Some(task) = game_state.ai_task.as_mut() else {
+        // Wait for the coach to finish and the analysis to stop before
+        // taking over the engine
+        analysis.stop();
+        if analysis.task.is_some() || coach.task.is_some() {
+            return;
+        }
+
+        let board = game_state.board.clone();
+        let mut ai = game_state.ai.clone();
+        ai.set_move_cache_size(if game_state.move_cache.is_some() { MOVE_CACHE_POSITIONS } else { 0 });
+        game_state.ai_thinking = true;
+        game_state.ai_task = Some(AsyncComputeTaskPool::get().spawn(async move {
+            let ai_move = ai.get_move(&board);
+            (ai, ai_move)
+        }));
+        return;
+    };
+    let Some((ai, ai_move)) = future::block_on(future::poll_once(task)) else {
+        return;
+    };
+    game_state.ai_task = None;
+    game_state.ai = ai;
+
+    if let Some(AiMove { chess_move: ai_move, source }) = ai_move {
+        // Try to make the move
+        if let Ok(captured) = game_state.board.make_move_capturing(ai_move) {
+            game_state.ai_move_source = Some(source);
+
+            // The PV starts with the move just played; show what the engine expects next
+            game_state.expected_line = match game_state.ai.last_search() {
+                Some(result) if result.pv.len() > 1 => {
+                    pv_to_san(&game_state.board, &result.pv[1..], PV_DISPLAY_LENGTH)
+                }
+                _ => Vec::new(),
+            };
+            
+            // Send the captured piece, if any, off to the tray
+            if let Some((square, _)) = captured {
+                if let Some((entity, _, _)) = pieces.iter().find(|(_, p, _)| p.position == square) {
+                    fly_to_tray(&mut commands, entity, &layout);
+                }
+            }
+            
+            // Handle promotion
+            if let Some(promotion_type) = ai_move.promotion {
+                // Remove the old pawn
+                for (entity, piece, _) in pieces.iter() {
+                    if piece.position == ai_move.from {
+                        commands.entity(entity).despawn();
+                        break;
+                    }
+                }
+
+                // Spawn the promoted piece
+                let ai_white = game_state.ai_color == ChessColor::White;
+                let world_pos = board_position_to_world(ai_move.to, 2.0, &layout);
+                commands.spawn((
+                    SpriteBundle {
+                        texture: chess_assets.piece_texture(promotion_type, ai_white),
+                        transform: Transform::from_translation(world_pos)
+                            .with_scale(Vec3::splat(1.0)),
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::splat(layout.square_size - 10.0)),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    Piece {
+                        piece_type: promotion_type,
+                        is_white: ai_white,
+                        position: ai_move.to,
+                    },
+                ));
+            } else {
+                // Handle normal move
+                for (entity, mut piece, transform) in pieces.iter_mut() {
+                    if piece.position == ai_move.from {
+                        piece.position = ai_move.to;
+                        let target_pos = board_position_to_world(ai_move.to, transform.translation.z, &layout);
+                        commands.entity(entity).insert(MovingPiece {
+                            target_position: target_pos,
+                            speed: PIECE_SPEED,
+                        });
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    
+    game_state.ai_thinking = false;
+    turn_state.set(Turn::Player);
+}
+
+fn spawn_ui(commands: &mut Commands) {
+    // Main UI container
+    commands.spawn(NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::SpaceBetween,
+            ..default()
+        },
+        ..default()
+    }).with_children(|parent| {
+        // Top bar
+        parent.spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Px(50.0),
+                padding: UiRect::all(Val::Px(10.0)),
+                justify_content: JustifyContent::SpaceBetween,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgb(0.2, 0.2, 0.2).into(),
+            ..default()
+        }).with_children(|parent| {
+            // Left section with game status and evaluation
+            parent.spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    margin: UiRect::right(Val::Px(20.0)),
+                    ..default()
+                },
+                ..default()
+            }).with_children(|parent| {
+                // Game status text
+                parent.spawn((
+                    TextBundle::from_section(
+                        "White's Turn",
+                        TextStyle {
+                            font_size: 24.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    GameStatusText,
+                ));
+
+                // Evaluation text
+                parent.spawn((
+                    TextBundle::from_section(
+                        "Eval: 0.0",
+                        TextStyle {
+                            font_size: 24.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    )
+                    .with_style(Style {
+                        margin: UiRect::left(Val::Px(20.0)),
+                        ..default()
+                    }),
+                    EvaluationText,
+                ));
+
+                // AI thinking text
+                parent.spawn((
+                    TextBundle::from_section(
+                        "AI is thinking...",
+                        TextStyle {
+                            font_size: 24.0,
+                            color: Color::YELLOW,
+                            ..default()
+                        },
+                    )
+                    .with_style(Style {
+                        margin: UiRect::left(Val::Px(20.0)),
+                        ..default()
+                    }),
+                    AiThinkingText,
+                ));
+            });
+
+            // Right section with buttons
+            parent.spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            }).with_children(|parent| {
+                // Analysis toggle
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(10.0)),
+                            margin: UiRect::right(Val::Px(10.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                        ..default()
+                    },
+                    AnalysisButton,
+                )).with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Analysis",
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+
+                // Coaching toggle: rates each of the player's moves
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(10.0)),
+                            margin: UiRect::right(Val::Px(10.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                        ..default()
+                    },
+                    CoachButton,
+                )).with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            Coach::default().label(),
+                            TextStyle {
+                                font_size: 20.0,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        ),
+                        CoachButtonText,
+                    ));
+                });
+
+                // Threats overlay toggle
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(10.0)),
+                            margin: UiRect::right(Val::Px(10.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                        ..default()
+                    },
+                    ThreatsButton,
+                )).with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Threats",
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+
+                // Takeback request
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(10.0)),
+                            margin: UiRect::right(Val::Px(10.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                        ..default()
+                    },
+                    TakebackButton,
+                )).with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Takeback",
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+
+                // Position editor toggle
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(10.0)),
+                            margin: UiRect::right(Val::Px(10.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                        ..default()
+                    },
+                    EditorButton,
+                )).with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Edit",
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+
+                // Loads the next training puzzle
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(10.0)),
+                            margin: UiRect::right(Val::Px(10.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                        ..default()
+                    },
+                    PuzzleButton,
+                )).with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Puzzle",
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+
+                // Save / Load buttons
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(10.0)),
+                            margin: UiRect::right(Val::Px(10.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                        ..default()
+                    },
+                    SaveGameButton,
+                )).with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Save",
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(10.0)),
+                            margin: UiRect::right(Val::Px(10.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                        ..default()
+                    },
+                    LoadGameButton,
+                )).with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Load",
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+
+                // New Game button
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(10.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                        ..default()
+                    },
+                    MenuButton,
+                )).with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "New Game",
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+            });
+        });
+
+        // Bottom bar
+        parent.spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Px(40.0),
+                padding: UiRect::all(Val::Px(10.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgb(0.2, 0.2, 0.2).into(),
+            ..default()
+        }).with_children(|parent| {
+            // Last move text
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                LastMoveText,
+            ));
+
+            // Typed move entry
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(20.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                MoveEntryButton,
+            )).with_children(|parent| {
+                parent.spawn((
+                    TextBundle::from_section(
+                        "Type move",
+                        TextStyle {
+                            font_size: 18.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    MoveEntryText,
+                ));
+            });
+
+            // Engine's expected continuation
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::rgb(0.7, 0.7, 0.7),
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::left(Val::Px(20.0)),
+                    ..default()
+                }),
+                PvText,
+            ));
+
+            // How long the AI thought about its last move, and how deep it got
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::rgb(0.7, 0.7, 0.7),
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::left(Val::Px(20.0)),
+                    ..default()
+                }),
+                ThinkInfoText,
+            ));
+
+            // Game stats
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::left(Val::Px(20.0)),
+                    ..default()
+                }),
+                MoveCountText,
+            ));
+
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::left(Val::Px(20.0)),
+                    ..default()
+                }),
+                CapturedPiecesText,
+            ));
+
+            // Board theme selector
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(20.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                ThemeButton,
+            )).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Theme",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            });
+
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                BlindfoldButton,
+            )).with_children(|parent| {
+                parent.spawn((
+                    TextBundle::from_section(
+                        Blindfold::Off.label(),
+                        TextStyle {
+                            font_size: 18.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    BlindfoldText,
+                ));
+            });
+
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                SquareInfoButton,
+            )).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Square Info",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            });
+
+            // Which side the AI plays
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                AiSideButton,
+            )).with_children(|parent| {
+                parent.spawn((
+                    TextBundle::from_section(
+                        "AI: Black",
+                        TextStyle {
+                            font_size: 18.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    AiSideText,
+                ));
+            });
+
+            // The AI's playing style
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                PersonalityButton,
+            )).with_children(|parent| {
+                parent.spawn((
+                    TextBundle::from_section(
+                        format!("Style: {}", Personality::default().name()),
+                        TextStyle {
+                            font_size: 18.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    PersonalityText,
+                ));
+            });
+
+            // Rough strength of the AI as configured
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::left(Val::Px(10.0)),
+                    ..default()
+                }),
+                EloText,
+            ));
+
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                CopyFenButton,
+            )).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Copy FEN",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            });
+
+            // Only lit up while a draw can be claimed
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.2, 0.2, 0.2).into(),
+                    ..default()
+                },
+                ClaimDrawButton,
+            )).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Claim Draw",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            });
+
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                ResignButton,
+            )).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Resign",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            });
+
+            // Whether New Game and Resign ask first
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                ConfirmToggleButton,
+            )).with_children(|parent| {
+                parent.spawn((
+                    TextBundle::from_section(
+                        ConfirmDialog::default().label(),
+                        TextStyle {
+                            font_size: 18.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    ConfirmToggleText,
+                ));
+            });
+
+            // Opt-in cache of legal move lists, see GameState::toggle_move_cache
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                MoveCacheButton,
+            )).with_children(|parent| {
+                parent.spawn((
+                    TextBundle::from_section(
+                        GameState::move_cache_label(false),
+                        TextStyle {
+                            font_size: 18.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    MoveCacheText,
+                ));
+            });
+
+            // Step through the game's moves; Live returns to the current position
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(20.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                ReviewBackButton,
+            )).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "<",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            });
+
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                ReviewForwardButton,
+            )).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    ">",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            });
+
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                ReviewLiveButton,
+            )).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Live",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            });
+
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::YELLOW,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::left(Val::Px(10.0)),
+                    ..default()
+                }),
+                ReviewText,
+            ));
+
+            // Turns the board around; Coords starts or stops coordinate training
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(20.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                FlipBoardButton,
+            )).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Flip",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            });
+
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::horizontal(Val::Px(8.0)),
+                        margin: UiRect::left(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                    ..default()
+                },
+                TrainingButton,
+            )).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Coords",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            });
+
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::YELLOW,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::left(Val::Px(10.0)),
+                    ..default()
+                }),
+                TrainingText,
+            ));
+
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::left(Val::Px(10.0)),
+                    ..default()
+                }),
+                CoachText,
+            ));
+        });
+    });
+}
+
+fn spawn_analysis_panel(commands: &mut Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.0),
+                top: Val::Px(60.0),
+                width: Val::Px(260.0),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.15, 0.15, 0.15, 0.9).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        AnalysisPanel,
+    )).with_children(|parent| {
+        // Depth limit control
+        parent.spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                margin: UiRect::bottom(Val::Px(8.0)),
+                ..default()
+            },
+            ..default()
+        }).with_children(|parent| {
+            for (label, delta) in [("-", -1), ("+", 1)] {
+                if delta > 0 {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 18.0,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::horizontal(Val::Px(8.0)),
+                            ..default()
+                        }),
+                        AnalysisDepthText,
+                    ));
+                }
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(24.0),
+                            height: Val::Px(24.0),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                        ..default()
+                    },
+                    AnalysisDepthButton { delta },
+                )).with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        label,
+                        TextStyle {
+                            font_size: 18.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+            }
+        });
+
+        // Results
+        parent.spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            AnalysisText,
+        ));
+    });
+}
+
+fn handle_analysis_buttons(
+    toggle_query: Query<&Interaction, (Changed<Interaction>, With<AnalysisButton>)>,
+    depth_query: Query<(&Interaction, &AnalysisDepthButton), Changed<Interaction>>,
+    mut analysis: ResMut<AnalysisState>,
+) {
+    for interaction in toggle_query.iter() {
+        if *interaction == Interaction::Pressed {
+            analysis.open = !analysis.open;
+            if !analysis.open {
+                analysis.reset();
+            }
+        }
+    }
+
+    for (interaction, button) in depth_query.iter() {
+        if *interaction == Interaction::Pressed {
+            let depth = analysis.max_depth as i8 + button.delta;
+            analysis.max_depth = (depth as u8).clamp(MIN_ANALYSIS_DEPTH, MAX_ANALYSIS_DEPTH);
+        }
+    }
+}
+
+// Drives the background analysis: one search per depth, restarted whenever the position changes
+fn run_analysis(
+    game_state: Res<GameState>,
+    mut analysis: ResMut<AnalysisState>,
+    coach: Res<Coach>,
+    turn: Res<State<Turn>>,
+) {
+    // Collect the running search first, even with the panel closed: a
+    // stopped one still holds the engine until it's done
+    if let Some(task) = analysis.task.as_mut() {
+        if let Some(result) = future::block_on(future::poll_once(task)) {
+            analysis.task = None;
+            // A stopped search's result doesn't count; its depth is redone later
+            if !std::mem::take(&mut analysis.stopping) {
+                analysis.completed_depth += 1;
+                if result.is_some() {
+                    analysis.latest = result;
+                }
+            }
+        }
+    }
+
+    if !analysis.open {
+        return;
+    }
+
+    let position = game_state.board.zobrist_hash();
+    if analysis.position != Some(position) {
+        analysis.reset();
+        analysis.position = Some(position);
+    }
+    if analysis.task.is_some() {
+        return;
+    }
+
+    // Leave the engine to the AI while it's choosing its move, and to the coach
+    if *turn.get() != Turn::Player
+        || game_state.ai_task.is_some()
+        || coach.task.is_some()
+        || game_state.game_result.is_some()
+        || analysis.completed_depth >= analysis.max_depth
+    {
+        return;
+    }
+
+    let board = game_state.board.clone();
+    let ai = game_state.ai.clone();
+    let depth = analysis.completed_depth + 1;
+    analysis.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+        ai.analyze(&board, depth)
+    }));
+}
+
+fn update_analysis_panel(
+    game_state: Res<GameState>,
+    analysis: Res<AnalysisState>,
+    mut panel_query: Query<&mut Visibility, With<AnalysisPanel>>,
+    mut depth_query: Query<&mut Text, (With<AnalysisDepthText>, Without<AnalysisText>)>,
+    mut text_query: Query<&mut Text, (With<AnalysisText>, Without<AnalysisDepthText>)>,
+) {
+    if let Ok(mut visibility) = panel_query.get_single_mut() {
+        *visibility = if analysis.open { Visibility::Visible } else { Visibility::Hidden };
+    }
+    if !analysis.open {
+        return;
+    }
+
+    if let Ok(mut text) = depth_query.get_single_mut() {
+        text.sections[0].value = format!("Max depth: {}", analysis.max_depth);
+    }
+
+    if let Ok(mut text) = text_query.get_single_mut() {
+        text.sections[0].value = match &analysis.latest {
+            Some(result) => {
+                // Search scores are relative to the side to move
+                let score = if game_state.board.current_turn() == ChessColor::White {
+                    result.score
+                } else {
+                    -result.score
+                };
+                let line = pv_to_san(&game_state.board, &result.pv, PV_DISPLAY_LENGTH);
+                let status = if analysis.task.is_some() { "searching..." } else { "done" };
+                format!(
+                    "Depth {} ({})\nBest: {}\nScore: {:+.2}\n{}",
+                    analysis.completed_depth,
+                    status,
+                    line.first().cloned().unwrap_or_default(),
+                    score as f32 / 100.0,
+                    line.join(" "),
+                )
+            }
+            None => "Analyzing...".to_string(),
+        };
+    }
+}
+
+fn handle_coach_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<CoachButton>)>,
+    mut text_query: Query<&mut Text, With<CoachButtonText>>,
+    mut coach: ResMut<Coach>,
+) {
+    if !interaction_query.iter().any(|i| *i == Interaction::Pressed) {
+        return;
+    }
+    coach.enabled = !coach.enabled;
+    if !coach.enabled {
+        // A running judgement is left to finish, see run_coach
+        coach.judged = None;
+        coach.verdict = None;
+    }
+    if let Ok(mut text) = text_query.get_single_mut() {
+        text.sections[0].value = coach.label().to_string();
+    }
+}
+
+// Judges the player's latest move in the background. Verdicts on moves that
+// have since been taken back are dropped.
+fn run_coach(
+    game_state: Res<GameState>,
+    mut coach: ResMut<Coach>,
+    analysis: Res<AnalysisState>,
+    turn: Res<State<Turn>>,
+) {
+    let history = game_state.board.move_history();
+    let still_played = |verdict: &CoachVerdict| history.get(verdict.ply) == Some(&verdict.played);
+
+    // Collected even when coaching was switched off meanwhile, as the search
+    // holds the engine until it's done
+    if let Some(task) = coach.task.as_mut() {
+        if let Some(verdict) = future::block_on(future::poll_once(task)) {
+            coach.task = None;
+            coach.verdict = verdict.filter(still_played).filter(|_| coach.enabled);
+        }
+        return;
+    }
+    if !coach.enabled {
+        return;
+    }
+    if coach.verdict.as_ref().is_some_and(|verdict| !still_played(verdict)) {
+        coach.verdict = None;
+    }
+
+    // Leave the engine to the AI while it's choosing its reply, and wait
+    // for the analysis between two of its depths
+    if (*turn.get() != Turn::Player && game_state.game_result.is_none())
+        || game_state.ai_task.is_some()
+        || analysis.task.is_some()
+    {
+        return;
+    }
+
+    // The player's latest move is the last one, or the one before the AI's reply
+    let player_started = game_state.start_board().current_turn() == game_state.player_color();
+    let latest = (0..history.len()).rev().take(2).find(|ply| (ply % 2 == 0) == player_started);
+    let Some(ply) = latest else {
+        return;
+    };
+    let played = history[ply];
+    if coach.judged == Some((ply, played)) {
+        return;
+    }
+    coach.judged = Some((ply, played));
+
+    let before = game_state.board_at(ply);
+    let ai = game_state.ai.clone();
+    coach.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+        judge_move(&ai, &before, played, ply)
+    }));
+}
+
+// Rates `played` by the evaluation it gives away against the best move in
+// `before`. Both positions are searched to the same horizon.
+fn judge_move(ai: &ChessAI, before: &Board, played: Move, ply: usize) -> Option<CoachVerdict> {
+    let best = ai.analyze(before, COACH_DEPTH)?;
+    let best_score = best.score.clamp(-COACH_SCORE_CAP, COACH_SCORE_CAP);
+    let played_score = if played == best.best_move {
+        best_score
+    } else {
+        let after = before.with_move(played).ok()?;
+        match after.result() {
+            Some(GameResult::Draw(_)) => 0,
+            // Only the side that just moved can have won
+            Some(_) => COACH_SCORE_CAP,
+            None => -ai.analyze(&after, COACH_DEPTH - 1)?.score.clamp(-COACH_SCORE_CAP, COACH_SCORE_CAP),
+        }
+    };
+
+    Some(CoachVerdict {
+        ply,
+        played,
+        played_san: played.to_san(before),
+        best: best.best_move,
+        best_san: best.best_move.to_san(before),
+        quality: MoveQuality::from_loss(best_score - played_score),
+    })
+}
+
+// Shows the verdict on the player's last move, with an arrow for the move
+// they missed when it was a blunder
+fn update_coach_display(
+    mut commands: Commands,
+    coach: Res<Coach>,
+    layout: Res<BoardLayout>,
+    mut text_query: Query<&mut Text, With<CoachText>>,
+    arrows: Query<Entity, With<CoachArrow>>,
+    mut drawn: Local<Option<Move>>,
+) {
+    if let Ok(mut text) = text_query.get_single_mut() {
+        let (label, color) = match &coach.verdict {
+            Some(verdict) if verdict.quality == MoveQuality::Best => {
+                (format!("{}: {}", verdict.played_san, verdict.quality.label()), verdict.quality.color())
+            }
+            Some(verdict) => (
+                format!("{}: {} (best was {})", verdict.played_san, verdict.quality.label(), verdict.best_san),
+                verdict.quality.color(),
+            ),
+            None if coach.task.is_some() => ("Coach is thinking...".to_string(), Color::rgb(0.7, 0.7, 0.7)),
+            None => (String::new(), Color::WHITE),
+        };
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+            text.sections[0].style.color = color;
+        }
+    }
+
+    let missed = coach.verdict.as_ref()
+        .filter(|verdict| verdict.quality == MoveQuality::Blunder)
+        .map(|verdict| verdict.best);
+    if *drawn == missed && !layout.is_changed() {
+        return;
+    }
+    *drawn = missed;
+    for entity in arrows.iter() {
+        commands.entity(entity).despawn();
+    }
+    if let Some(mv) = missed {
+        spawn_coach_arrow(&mut commands, mv, &layout);
+    }
+}
+
+// A shaft from the move's square to its target, ending in a diamond, drawn
+// over the pieces
+fn spawn_coach_arrow(commands: &mut Commands, mv: Move, layout: &BoardLayout) {
+    let from = board_position_to_world(mv.from, 2.5, layout);
+    let to = board_position_to_world(mv.to, 2.5, layout);
+    let delta = (to - from).truncate();
+    let rotation = Quat::from_rotation_z(delta.y.atan2(delta.x));
+    let width = layout.square_size * 0.15;
+    let color = Color::rgba(0.9, 0.25, 0.25, 0.75);
+
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(Vec2::new((delta.length() - width).max(0.0), width)),
+                ..default()
+            },
+            transform: Transform::from_translation(from + (to - from) * 0.5 - (to - from).normalize_or_zero() * width * 0.5)
+                .with_rotation(rotation),
+            ..default()
+        },
+        CoachArrow,
+    ));
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(Vec2::splat(width * 2.0)),
+                ..default()
+            },
+            transform: Transform::from_translation(to)
+                .with_rotation(rotation * Quat::from_rotation_z(std::f32::consts::FRAC_PI_4)),
+            ..default()
+        },
+        CoachArrow,
+    ));
+}
+
+// Shows the thinking label during the AI's turn, with how deep it has got
+fn update_ui_text(
+    turn: Res<State<Turn>>,
+    game_state: Res<GameState>,
+    mut text_query: Query<(&mut Visibility, &mut Text), With<AiThinkingText>>,
+) {
+    let Ok((mut visibility, mut text)) = text_query.get_single_mut() else {
+        return;
+    };
+    if *turn.get() != Turn::AI {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Visible;
+
+    let progress = if game_state.ai_thinking { ChessAI::search_progress() } else { None };
+    let label = match progress {
+        Some(result) => format!(
+            "AI is thinking... depth {}, best {}",
+            result.depth,
+            result.best_move.to_san(&game_state.board),
+        ),
+        None => "AI is thinking...".to_string(),
+    };
+    if text.sections[0].value != label {
+        text.sections[0].value = label;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn show_valid_moves(
+    mut commands: Commands,
+    mut game_state: ResMut<GameState>,
+    selected_pieces: Query<&Piece, With<SelectedPiece>>,
+    chess_assets: Res<ChessAssets>,
+    indicators: Query<Entity, With<ValidMoveIndicator>>,
+    theme: Res<BoardTheme>,
+    blindfold: Res<Blindfold>,
+    layout: Res<BoardLayout>,
+) {
+    // Remove existing indicators
+    for entity in indicators.iter() {
+        commands.entity(entity).despawn();
+    }
+    if *blindfold == Blindfold::AllNoHints {
+        return;
+    }
+
+    // Show valid moves for selected piece
+    if let Ok(piece) = selected_pieces.get_single() {
+        // Only show moves for the player's own pieces
+        if piece.is_white == (game_state.player_color() == ChessColor::White) {
+            let valid_moves = game_state.selected_moves(piece.position).to_vec();
+            for valid_move in valid_moves {
+                let target_pos = board_position_to_world(valid_move.to, 2.0, &layout);
+                // A ring around pieces that can be taken (en passant included,
+                // though its square is empty), a dot on plain moves
+                let texture = if valid_move.is_capture(&game_state.board) {
+                    chess_assets.capture_ring.clone()
+                } else {
+                    chess_assets.valid_move.clone()
+                };
+                commands.spawn((
+                    SpriteBundle {
+                        texture,
+                        transform: Transform::from_translation(target_pos)
+                            .with_scale(Vec3::splat(1.0)),
+                        sprite: Sprite {
+                            color: theme.highlight,
+                            custom_size: Some(Vec2::splat(layout.square_size)),
+                            anchor: Anchor::Center,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    ValidMoveIndicator,
+                ));
+            }
+        }
+    }
+}
+
+// Square under the cursor, or None when it's off the board. Unlike
+// get_board_position this doesn't snap outside positions to the edge.
+fn hovered_square(cursor: Vec2, window: &Window, layout: &BoardLayout) -> Option<Position> {
+    let board_size = layout.board_size();
+    let relative = cursor - layout.window_origin(window);
+    if relative.x < 0.0 || relative.y < 0.0 || relative.x >= board_size || relative.y >= board_size {
+        return None;
+    }
+    get_board_position(Some(cursor), window, layout)
+}
+
+fn get_board_position(cursor_position: Option<Vec2>, window: &Window, layout: &BoardLayout) -> Option<Position> {
+    cursor_position.map(|cursor| {
+        // Calculate relative position on board
+        let relative_pos = cursor - layout.window_origin(window);
+        
+        // Convert to file and rank (1-based). Negative values saturate to 0
+        // in the cast and are clamped below.
+        let file = (relative_pos.x / layout.square_size).floor() as u8 + 1;
+        // Calculate rank from bottom (rank 1) to top (rank 8)
+        let rank = (8.0 - (relative_pos.y / layout.square_size).floor()) as u8;
+        
+        // Clamp values to valid range
+        let file = file.clamp(1, 8);
+        let rank = rank.clamp(1, 8);
+        
+        // The flip is its own inverse
+        let (file, rank) = layout.screen_coords(Position { file, rank });
+        Position { file, rank }
+    })
+}
+
+fn board_position_to_world(pos: Position, z: f32, layout: &BoardLayout) -> Vec3 {
+    let (file, rank) = layout.screen_coords(pos);
+    Vec3::new(
+        ((file as f32 - 1.0) - 3.5) * layout.square_size + layout.offset.x,
+        ((rank as f32 - 1.0) - 3.5) * layout.square_size + layout.offset.y,
+        z,
+    )
+}
+
+fn update_piece_movement(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &MovingPiece, Has<CapturedPieceFlight>)>,
+) {
+    for (entity, mut transform, moving, captured) in query.iter_mut() {
+        let direction = (moving.target_position - transform.translation).normalize();
+        let distance = (moving.target_position - transform.translation).length();
+        
+        // Snap to final position when close enough, and don't overshoot
+        let movement = direction * moving.speed * time.delta_seconds();
+        if distance < 1.0 || movement.length() > distance {
+            transform.translation = moving.target_position;
+            if captured {
+                // Made it to the tray, which shows the piece from here on
+                commands.entity(entity).despawn();
+            } else {
+                commands.entity(entity).remove::<MovingPiece>();
+            }
+        } else {
+            // Smooth movement
+            transform.translation += movement;
+        }
+    }
+}
+
+// Plays one of the player's moves on the board and the sprites, then hands the
+// turn to the AI - or leaves it with the player when a puzzle supplies the
+// reply. Clicks, the promotion dialog and typed moves all end up here.
+// Returns false if the board rejected the move.
+#[allow(clippy::too_many_arguments)]
+fn play_player_move(
+    commands: &mut Commands,
+    game_state: &mut GameState,
+    pieces: &mut Query<(Entity, &mut Piece, &mut Transform)>,
+    chess_move: Move,
+    layout: &BoardLayout,
+    assets: &ChessAssets,
+    puzzles: &PuzzleState,
+    turn_state: &mut NextState<Turn>,
+) -> bool {
+    let before = game_state.board.clone();
+    let Ok(captured) = game_state.board.make_move_capturing(chess_move) else {
+        return false;
+    };
+
+    if let Some((square, _)) = captured {
+        if let Some((entity, _, _)) = pieces.iter().find(|(_, p, _)| p.position == square) {
+            fly_to_tray(commands, entity, layout);
+        }
+    }
+
+    if let Some(piece_type) = chess_move.promotion {
+        // Swap the pawn for the promoted piece
+        if let Some((entity, _, _)) = pieces.iter().find(|(_, p, _)| p.position == chess_move.from) {
+            commands.entity(entity).despawn();
+        }
+        let is_white = before.current_turn() == ChessColor::White;
+        spawn_piece(commands, piece_type, is_white, chess_move.to.file, chess_move.to.rank, layout, assets);
+    } else {
+        let mut slides = vec![(chess_move.from, chess_move.to)];
+        let is_king = before.get_piece(chess_move.from)
+            .is_some_and(|p| p.piece_type == ChessPieceType::King);
+        if is_king && chess_move.is_castle() {
+            let rank = chess_move.from.rank;
+            let (rook_from, rook_to) = if chess_move.to.file == 7 { (8, 6) } else { (1, 4) };
+            slides.push((Position { rank, file: rook_from }, Position { rank, file: rook_to }));
+        }
+        for (from, to) in slides {
+            if let Some((entity, mut piece, _)) = pieces.iter_mut().find(|(_, p, _)| p.position == from) {
+                move_piece(commands, entity, &mut piece, to, layout);
+            }
+        }
+    }
+
+    // Puzzle replies come from the solution, not the AI
+    if puzzles.active.is_none() {
+        turn_state.set(Turn::AI);
+    }
+    true
+}
+
+// Takes a captured piece off the board and slides it out past the board's
+// top-left corner, towards the tray, where update_piece_movement despawns it.
+// It flies above the pieces still in play.
+fn fly_to_tray(commands: &mut Commands, entity: Entity, layout: &BoardLayout) {
+    let half_board = layout.board_size() / 2.0;
+    let target = layout.offset + Vec3::new(-half_board - layout.square_size / 2.0, half_board, 3.0);
+    commands.entity(entity)
+        .remove::<Piece>()
+        .insert((
+            MovingPiece {
+                target_position: target,
+                speed: PIECE_SPEED,
+            },
+            CapturedPieceFlight,
+        ));
+}
+
+fn move_piece(
+    commands: &mut Commands,
+    piece_entity: Entity,
+    piece: &mut Piece,
+    to: Position,
+    layout: &BoardLayout,
+) {
+    // Update the piece's position immediately
+    piece.position = to;
+    
+    // Calculate the target position in world coordinates
+    let target_pos = board_position_to_world(to, 2.0, layout);
+
+    // Add the MovingPiece component to handle smooth movement
+    commands.entity(piece_entity).insert(MovingPiece {
+        target_position: target_pos,
+        speed: PIECE_SPEED,
+    });
+}
+
+// Runs after check_game_end, so a finished game is reported from the same
+// game_result the overlay shows rather than worked out again here
+fn update_game_status(
+    game_state: Res<GameState>,
+    mut query: Query<&mut Text, With<GameStatusText>>,
+) {
+    if let Ok(mut text) = query.get_single_mut() {
+        let status = match game_state.game_result {
+            Some(result) => game_end_message(result, &game_state.board),
+            None if game_state.board.current_turn() == ChessColor::White => "White's Turn".to_string(),
+            None => "Black's Turn".to_string(),
+        };
+        if text.sections[0].value != status {
+            text.sections[0].value = status;
+        }
+    }
+}
+
+// New Game from the top bar, the game end overlay or Ctrl+N. A game in
+// progress is only thrown away once the player confirms it.
+#[allow(clippy::too_many_arguments)]
+fn handle_new_game_button(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<MenuButton>),
+    >,
+    keys: Res<Input<KeyCode>>,
+    mut game_state: ResMut<GameState>,
+    mut commands: Commands,
+    pieces: Query<(Entity, &Piece)>,
+    mut turn_state: ResMut<NextState<Turn>>,
+    chess_assets: Res<ChessAssets>,
+    mut puzzles: ResMut<PuzzleState>,
+    layout: Res<BoardLayout>,
+    mut confirm: ResMut<ConfirmDialog>,
+) {
+    let mut pressed = false;
+    for (interaction, mut color) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                pressed = true;
+                *color = Color::rgb(0.4, 0.4, 0.4).into();
+            }
+            Interaction::Hovered => {
+                *color = Color::rgb(0.5, 0.5, 0.5).into();
+            }
+            Interaction::None => {
+                *color = Color::rgb(0.4, 0.4, 0.4).into();
+            }
+        }
+    }
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shortcut = ctrl && keys.just_pressed(KeyCode::N);
+    if !(pressed || shortcut) || confirm.pending.is_some() {
+        return;
+    }
+
+    let in_progress = game_state.game_result.is_none() && !game_state.board.move_history().is_empty();
+    if confirm.enabled && in_progress {
+        ask_confirmation(&mut commands, &mut confirm, ConfirmAction::NewGame);
+    } else {
+        reset_game(&mut commands, &mut game_state, &pieces, &layout, &chess_assets, &mut puzzles, &mut turn_state);
+    }
+}
+
+// Throws the current game away and sets up the starting position
+fn reset_game(
+    commands: &mut Commands,
+    game_state: &mut GameState,
+    pieces: &Query<(Entity, &Piece)>,
+    layout: &BoardLayout,
+    chess_assets: &ChessAssets,
+    puzzles: &mut PuzzleState,
+    turn_state: &mut NextState<Turn>,
+) {
+    puzzles.active = None;
+    game_state.reset_for_new_position(Board::new(), None);
+
+    sync_pieces_to_board(commands, pieces, &game_state.board, layout, chess_assets);
+
+    // The AI opens when it plays White
+    turn_state.set(game_state.turn());
+}
+
+// The player gives up and the AI's side wins
+fn resign(game_state: &mut GameState, puzzles: &mut PuzzleState) {
+    puzzles.active = None;
+    game_state.cancel_ai_move();
+    game_state.game_result = Some(match game_state.player_color() {
+        ChessColor::White => GameResult::BlackWins,
+        ChessColor::Black => GameResult::WhiteWins,
+    });
+}
+
+fn handle_resign_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ResignButton>)>,
+    mut game_state: ResMut<GameState>,
+    turn: Res<State<Turn>>,
+    editor_mode: Res<State<EditorMode>>,
+    mut puzzles: ResMut<PuzzleState>,
+    mut confirm: ResMut<ConfirmDialog>,
+    mut commands: Commands,
+) {
+    if !interaction_query.iter().any(|i| *i == Interaction::Pressed) {
+        return;
+    }
+    if *turn.get() != Turn::Player || *editor_mode.get() == EditorMode::Active
+        || game_state.game_result.is_some() || confirm.pending.is_some() {
+        return;
+    }
+
+    if confirm.enabled {
+        ask_confirmation(&mut commands, &mut confirm, ConfirmAction::Resign);
+    } else {
+        resign(&mut game_state, &mut puzzles);
+    }
+}
+
+fn ask_confirmation(commands: &mut Commands, confirm: &mut ConfirmDialog, action: ConfirmAction) {
+    confirm.pending = Some(action);
+    spawn_confirm_dialog(commands, action.question());
+}
+
+// Yes carries out the pending action, No or Escape drops it
+#[allow(clippy::too_many_arguments)]
+fn handle_confirm_dialog(
+    answer_query: Query<(&Interaction, &ConfirmAnswerButton), Changed<Interaction>>,
+    keys: Res<Input<KeyCode>>,
+    overlay_query: Query<Entity, With<ConfirmOverlay>>,
+    mut confirm: ResMut<ConfirmDialog>,
+    mut game_state: ResMut<GameState>,
+    mut commands: Commands,
+    pieces: Query<(Entity, &Piece)>,
+    mut turn_state: ResMut<NextState<Turn>>,
+    chess_assets: Res<ChessAssets>,
+    mut puzzles: ResMut<PuzzleState>,
+    layout: Res<BoardLayout>,
+) {
+    let Some(action) = confirm.pending else {
+        return;
+    };
+    let answer = answer_query.iter()
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, button)| button.yes)
+        .or_else(|| keys.just_pressed(KeyCode::Escape).then_some(false));
+    let Some(yes) = answer else {
+        return;
+    };
+
+    confirm.pending = None;
+    for entity in overlay_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !yes {
+        return;
+    }
+    match action {
+        ConfirmAction::NewGame => {
+            reset_game(&mut commands, &mut game_state, &pieces, &layout, &chess_assets, &mut puzzles, &mut turn_state);
+        }
+        ConfirmAction::Resign => resign(&mut game_state, &mut puzzles),
+    }
+}
+
+fn handle_confirm_toggle(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ConfirmToggleButton>)>,
+    mut text_query: Query<&mut Text, With<ConfirmToggleText>>,
+    mut confirm: ResMut<ConfirmDialog>,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            confirm.enabled = !confirm.enabled;
+            if let Ok(mut text) = text_query.get_single_mut() {
+                text.sections[0].value = confirm.label().to_string();
+            }
+        }
+    }
+}
+
+fn handle_move_cache_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<MoveCacheButton>)>,
+    mut text_query: Query<&mut Text, With<MoveCacheText>>,
+    mut game_state: ResMut<GameState>,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            game_state.toggle_move_cache();
+            if let Ok(mut text) = text_query.get_single_mut() {
+                text.sections[0].value = GameState::move_cache_label(game_state.move_cache.is_some()).to_string();
+            }
+        }
+    }
+}
+
+fn handle_save_game_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<SaveGameButton>)>,
+    game_state: Res<GameState>,
+    mut commands: Commands,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let saved = SavedGame {
+            start_fen: game_state.start_fen.clone(),
+            moves: game_state.board.move_history().to_vec(),
+            result: game_state.game_result,
+        };
+        let message = match save_game(&saved) {
+            Ok(()) => format!("Game saved to {}", SAVE_FILE_PATH),
+            Err(err) => format!("Could not save game: {}", err),
+        };
+        spawn_toast(&mut commands, &message);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_load_game_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<LoadGameButton>)>,
+    mut game_state: ResMut<GameState>,
+    mut commands: Commands,
+    pieces: Query<(Entity, &Piece)>,
+    mut turn_state: ResMut<NextState<Turn>>,
+    chess_assets: Res<ChessAssets>,
+    mut puzzles: ResMut<PuzzleState>,
+    layout: Res<BoardLayout>,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let (board, saved) = match load_game() {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                spawn_toast(&mut commands, &format!("Could not load game: {}", err));
+                continue;
+            }
+        };
+
+        // Swap in the loaded game
+        puzzles.active = None;
+        game_state.reset_for_new_position(board, saved.start_fen);
+        game_state.game_result = saved.result;
+
+        sync_pieces_to_board(&mut commands, &pieces, &game_state.board, &layout, &chess_assets);
+
+        turn_state.set(game_state.turn());
+        spawn_toast(&mut commands, "Game loaded");
+    }
+}
+
+fn save_game(saved: &SavedGame) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(saved).map_err(|e| e.to_string())?;
+    std::fs::write(SAVE_FILE_PATH, json).map_err(|e| e.to_string())
+}
+
+// Reads the save file and replays its moves from the saved starting position
+fn load_game() -> Result<(Board, SavedGame), String> {
+    let json = std::fs::read_to_string(SAVE_FILE_PATH).map_err(|e| e.to_string())?;
+    let saved: SavedGame = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+
+    let mut board = match &saved.start_fen {
+        Some(fen) => Board::from_fen(fen).map_err(|e| format!("bad start position ({})", e))?,
+        None => Board::new(),
+    };
+    for (i, mv) in saved.moves.iter().enumerate() {
+        board.make_move(*mv)
+            .map_err(|e| format!("move {} is illegal ({})", i + 1, e))?;
+    }
+    Ok((board, saved))
+}
 
+fn spawn_toast(commands: &mut Commands, message: &str) {
     commands.spawn((
-        SpriteBundle {
-            texture,
-            transform: Transform::from_translation(world_pos)
-                .with_scale(Vec3::splat(1.0)),
-            sprite: Sprite {
-                custom_size: Some(Vec2::new(SQUARE_SIZE * 0.8, SQUARE_SIZE * 0.8)),
-                anchor: Anchor::Center,
+        TextBundle::from_section(
+            message,
+            TextStyle {
+                font_size: 20.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(50.0),
+            left: Val::Px(10.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        })
+        .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.8)),
+        Toast {
+            timer: Timer::from_seconds(TOAST_DURATION_SECS, TimerMode::Once),
+        },
+    ));
+}
+
+fn update_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toasts: Query<(Entity, &mut Toast)>,
+) {
+    for (entity, mut toast) in toasts.iter_mut() {
+        if toast.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn handle_theme_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ThemeButton>)>,
+    mut theme: ResMut<BoardTheme>,
+    mut commands: Commands,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            *theme = theme.next();
+            spawn_toast(&mut commands, &format!("Board theme: {}", theme.name));
+        }
+    }
+}
+
+fn handle_blindfold_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<BlindfoldButton>)>,
+    mut text_query: Query<&mut Text, With<BlindfoldText>>,
+    mut blindfold: ResMut<Blindfold>,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            *blindfold = blindfold.next();
+            if let Ok(mut text) = text_query.get_single_mut() {
+                text.sections[0].value = blindfold.label().to_string();
+            }
+        }
+    }
+}
+
+// Hides piece sprites while blindfolded. Runs every frame since pieces are
+// respawned as the game goes on; the editor always shows everything.
+fn apply_blindfold(
+    mut pieces: Query<(&Piece, &mut Visibility)>,
+    blindfold: Res<Blindfold>,
+    game_state: Res<GameState>,
+    editor_mode: Res<State<EditorMode>>,
+) {
+    let editing = *editor_mode.get() == EditorMode::Active;
+    for (piece, mut visibility) in pieces.iter_mut() {
+        let wanted = if !editing && blindfold.hides(piece.is_white, game_state.ai_color) {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+    }
+}
+
+// Hands the AI the other side. Takes effect in the current position, so
+// switching on a fresh game lets the AI open as White.
+#[allow(clippy::too_many_arguments)]
+fn handle_ai_side_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<AiSideButton>)>,
+    mut text_query: Query<&mut Text, With<AiSideText>>,
+    selected_pieces: Query<Entity, With<SelectedPiece>>,
+    mut game_state: ResMut<GameState>,
+    turn: Res<State<Turn>>,
+    mut turn_state: ResMut<NextState<Turn>>,
+    editor_mode: Res<State<EditorMode>>,
+    puzzles: Res<PuzzleState>,
+    mut commands: Commands,
+) {
+    if !interaction_query.iter().any(|i| *i == Interaction::Pressed) {
+        return;
+    }
+    if *turn.get() != Turn::Player || *editor_mode.get() == EditorMode::Active
+        || game_state.pending_promotion.is_some() || game_state.view_ply.is_some()
+        || puzzles.active.is_some() {
+        return;
+    }
+
+    game_state.ai_color = game_state.player_color();
+    for entity in selected_pieces.iter() {
+        commands.entity(entity).remove::<SelectedPiece>();
+    }
+    game_state.selected_square = None;
+    game_state.valid_moves.clear();
+    turn_state.set(game_state.turn());
+
+    let side = match game_state.ai_color {
+        ChessColor::White => "White",
+        ChessColor::Black => "Black",
+    };
+    if let Ok(mut text) = text_query.get_single_mut() {
+        text.sections[0].value = format!("AI: {}", side);
+    }
+    spawn_toast(&mut commands, &format!("AI now plays {}", side));
+}
+
+// Cycles the AI through its playing styles; applies from its next move.
+// Not while it's thinking, since the searching copy would overwrite the change.
+fn handle_personality_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<PersonalityButton>)>,
+    mut text_query: Query<&mut Text, With<PersonalityText>>,
+    mut game_state: ResMut<GameState>,
+) {
+    if game_state.ai_thinking {
+        return;
+    }
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            let personality = game_state.ai.personality().next();
+            game_state.ai.set_personality(personality);
+            if let Ok(mut text) = text_query.get_single_mut() {
+                text.sections[0].value = format!("Style: {}", personality.name());
+            }
+        }
+    }
+}
+
+// Copies the FEN of the position on screen, from the button or Ctrl+C
+fn handle_copy_fen(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<CopyFenButton>)>,
+    keys: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    editor: Res<EditorState>,
+    editor_mode: Res<State<EditorMode>>,
+    mut commands: Commands,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let pressed = interaction_query.iter().any(|i| *i == Interaction::Pressed)
+        || (ctrl && keys.just_pressed(KeyCode::C));
+    if !pressed {
+        return;
+    }
+
+    let fen = if *editor_mode.get() == EditorMode::Active {
+        editor_fen(&editor.board)
+    } else {
+        game_state.board.to_fen()
+    };
+    let copied = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(fen));
+    match copied {
+        Ok(()) => spawn_toast(&mut commands, "FEN copied"),
+        Err(err) => spawn_toast(&mut commands, &format!("Could not copy FEN: {}", err)),
+    }
+}
+
+// Ends the game as a draw when the position allows claiming one
+fn handle_claim_draw(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ClaimDrawButton>)>,
+    mut game_state: ResMut<GameState>,
+    turn: Res<State<Turn>>,
+    editor_mode: Res<State<EditorMode>>,
+    mut commands: Commands,
+) {
+    if !interaction_query.iter().any(|i| *i == Interaction::Pressed) {
+        return;
+    }
+    if *turn.get() != Turn::Player || *editor_mode.get() == EditorMode::Active
+        || game_state.game_result.is_some() {
+        return;
+    }
+
+    if let Some(reason) = game_state.board.can_claim_draw() {
+        game_state.game_result = Some(GameResult::Draw(reason));
+    } else {
+        spawn_toast(&mut commands, "No draw to claim");
+    }
+}
+
+// Steps back and forth through the game with the buttons or the arrow keys
+// (End goes back to live). Earlier positions are rebuilt from the move history;
+// no moves can be made until returning to the live position.
+#[allow(clippy::too_many_arguments)]
+fn handle_review(
+    back_query: Query<&Interaction, (Changed<Interaction>, With<ReviewBackButton>)>,
+    forward_query: Query<&Interaction, (Changed<Interaction>, With<ReviewForwardButton>)>,
+    live_query: Query<&Interaction, (Changed<Interaction>, With<ReviewLiveButton>)>,
+    keys: Res<Input<KeyCode>>,
+    mut game_state: ResMut<GameState>,
+    turn: Res<State<Turn>>,
+    editor_mode: Res<State<EditorMode>>,
+    moving: Query<(), With<MovingPiece>>,
+    pieces: Query<(Entity, &Piece)>,
+    chess_assets: Res<ChessAssets>,
+    mut commands: Commands,
+    layout: Res<BoardLayout>,
+) {
+    let back = back_query.iter().any(|i| *i == Interaction::Pressed) || keys.just_pressed(KeyCode::Left);
+    let forward = forward_query.iter().any(|i| *i == Interaction::Pressed) || keys.just_pressed(KeyCode::Right);
+    let live = live_query.iter().any(|i| *i == Interaction::Pressed) || keys.just_pressed(KeyCode::End);
+    if !(back || forward || live) {
+        return;
+    }
+    if *turn.get() != Turn::Player || *editor_mode.get() == EditorMode::Active
+        || game_state.pending_promotion.is_some() || !moving.is_empty() {
+        return;
+    }
+
+    let played = game_state.board.move_history().len();
+    let current = game_state.view_ply.unwrap_or(played);
+    let target = if live {
+        played
+    } else if back {
+        current.saturating_sub(1)
+    } else {
+        (current + 1).min(played)
+    };
+    let view_ply = (target < played).then_some(target);
+    if view_ply == game_state.view_ply {
+        return;
+    }
+
+    game_state.view_ply = view_ply;
+    game_state.selected_square = None;
+    game_state.valid_moves.clear();
+    let board = match view_ply {
+        Some(ply) => game_state.board_at(ply),
+        None => game_state.board.clone(),
+    };
+    sync_pieces_to_board(&mut commands, &pieces, &board, &layout, &chess_assets);
+}
+
+fn update_review_text(
+    mut text_query: Query<&mut Text, With<ReviewText>>,
+    game_state: Res<GameState>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+    if let Ok(mut text) = text_query.get_single_mut() {
+        text.sections[0].value = match game_state.view_ply {
+            Some(ply) => format!("Reviewing {}/{}", ply, game_state.board.move_history().len()),
+            None => String::new(),
+        };
+    }
+}
+
+// Greys out the claim button unless a draw is actually available
+fn update_claim_draw_button(
+    mut button_query: Query<&mut BackgroundColor, With<ClaimDrawButton>>,
+    game_state: Res<GameState>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+    let claimable = game_state.game_result.is_none() && game_state.board.can_claim_draw().is_some();
+    for mut background in button_query.iter_mut() {
+        *background = if claimable {
+            Color::rgb(0.4, 0.4, 0.4).into()
+        } else {
+            Color::rgb(0.2, 0.2, 0.2).into()
+        };
+    }
+}
+
+type PieceAtRest = (With<Piece>, Without<MovingPiece>, Without<ChessBoard>, Without<Square>);
+type IndicatorOnly = (With<ValidMoveIndicator>, Without<ChessBoard>, Without<Square>);
+type GlowOnly = (With<GameEndGlow>, Without<ChessBoard>, Without<Square>, Without<Piece>);
+type GameEndVisuals = Or<(With<GameEndOverlay>, With<GameEndGlow>)>;
+
+// Recolors the board whenever the theme changes
+fn apply_board_theme(
+    theme: Res<BoardTheme>,
+    mut board_query: Query<&mut Sprite, With<ChessBoard>>,
+    mut square_query: Query<(&mut Sprite, &Square), Without<ChessBoard>>,
+    mut indicator_query: Query<&mut Sprite, IndicatorOnly>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    for mut sprite in board_query.iter_mut() {
+        sprite.color = theme.background;
+    }
+    for (mut sprite, square) in square_query.iter_mut() {
+        sprite.color = theme.square_color(square.position);
+    }
+    for mut sprite in indicator_query.iter_mut() {
+        sprite.color = theme.highlight;
+    }
+}
+
+fn handle_threats_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ThreatsButton>)>,
+    mut overlay: ResMut<ThreatOverlay>,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            overlay.enabled = !overlay.enabled;
+        }
+    }
+}
+
+// Marks hanging pieces: ours in red as a warning, the opponent's in green as free captures.
+// Recomputed only when the position or the toggle changes.
+fn update_threat_highlights(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    overlay: Res<ThreatOverlay>,
+    editor_mode: Res<State<EditorMode>>,
+    highlights: Query<Entity, With<ThreatHighlight>>,
+    mut last_shown: Local<Option<u64>>,
+    layout: Res<BoardLayout>,
+) {
+    let show = overlay.enabled && *editor_mode.get() == EditorMode::Inactive
+        && game_state.view_ply.is_none();
+    let current = show.then(|| game_state.board.zobrist_hash());
+    if *last_shown == current && !layout.is_changed() {
+        return;
+    }
+    *last_shown = current;
+
+    for entity in highlights.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !show {
+        return;
+    }
+
+    for (&pos, piece) in game_state.board.piece_map() {
+        if !game_state.board.is_hanging(pos) {
+            continue;
+        }
+        let color = if piece.color == ChessColor::White {
+            Color::rgba(0.9, 0.1, 0.1, 0.45)
+        } else {
+            Color::rgba(0.1, 0.8, 0.2, 0.45)
+        };
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::splat(layout.square_size)),
+                    ..default()
+                },
+                transform: Transform::from_translation(board_position_to_world(pos, 1.5, &layout)),
+                ..default()
+            },
+            ThreatHighlight,
+        ));
+    }
+}
+
+fn handle_takeback_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<TakebackButton>)>,
+    mut game_state: ResMut<GameState>,
+    turn: Res<State<Turn>>,
+    editor_mode: Res<State<EditorMode>>,
+    mut commands: Commands,
+) {
+    if !interaction_query.iter().any(|i| *i == Interaction::Pressed) {
+        return;
+    }
+    if *turn.get() != Turn::Player || *editor_mode.get() == EditorMode::Active {
+        return;
+    }
+
+    // Take back the AI's reply along with our own move so it's our turn again
+    let played = game_state.board.move_history().len();
+    let plies = if game_state.board.current_turn() == game_state.player_color() { 2 } else { 1 };
+    if played < plies {
+        spawn_toast(&mut commands, "Nothing to take back");
+        return;
+    }
+    game_state.takeback_request = Some(TakebackRequest { plies });
+}
+
+// The AI accepts every takeback; wait for animations to settle before rewinding
+#[allow(clippy::too_many_arguments)]
+fn resolve_takeback(
+    mut commands: Commands,
+    mut game_state: ResMut<GameState>,
+    moving: Query<(), With<MovingPiece>>,
+    pieces: Query<(Entity, &Piece)>,
+    turn: Res<State<Turn>>,
+    mut turn_state: ResMut<NextState<Turn>>,
+    chess_assets: Res<ChessAssets>,
+    layout: Res<BoardLayout>,
+) {
+    let Some(request) = game_state.takeback_request else {
+        return;
+    };
+    if !moving.is_empty() || *turn.get() != Turn::Player || game_state.ai_thinking {
+        return;
+    }
+    game_state.takeback_request = None;
+
+    if let Err(err) = game_state.take_back(request.plies) {
+        spawn_toast(&mut commands, err);
+        return;
+    }
+    sync_pieces_to_board(&mut commands, &pieces, &game_state.board, &layout, &chess_assets);
+    if game_state.turn() == Turn::AI {
+        turn_state.set(Turn::AI);
+    }
+    spawn_toast(&mut commands, "Takeback granted");
+}
+
+fn spawn_editor_panel(commands: &mut Commands) {
+    let button_style = Style {
+        padding: UiRect::all(Val::Px(6.0)),
+        margin: UiRect::top(Val::Px(6.0)),
+        justify_content: JustifyContent::Center,
+        ..default()
+    };
+    let text_style = TextStyle {
+        font_size: 18.0,
+        color: Color::WHITE,
+        ..default()
+    };
+
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(5.0),
+                top: Val::Px(60.0),
+                width: Val::Px(72.0),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(2.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.15, 0.15, 0.15, 0.9).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        EditorPanel,
+    )).with_children(|parent| {
+        // Piece palette, one column per color
+        parent.spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                flex_wrap: FlexWrap::Wrap,
                 ..default()
             },
             ..default()
-        },
-        Piece {
-            piece_type,
-            is_white,
-            position,
-        },
-    ));
+        }).with_children(|parent| {
+            for piece_type in [
+                ChessPieceType::King, ChessPieceType::Queen, ChessPieceType::Rook,
+                ChessPieceType::Bishop, ChessPieceType::Knight, ChessPieceType::Pawn,
+            ] {
+                for color in [ChessColor::White, ChessColor::Black] {
+                    parent.spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(34.0),
+                                height: Val::Px(34.0),
+                                ..default()
+                            },
+                            background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                            ..default()
+                        },
+                        EditorPaletteButton {
+                            piece: Some(ChessPiece::new(piece_type, color)),
+                        },
+                    ));
+                }
+            }
+        });
+
+        parent.spawn((
+            ButtonBundle {
+                style: button_style.clone(),
+                background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                ..default()
+            },
+            EditorPaletteButton { piece: None },
+        )).with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Erase", text_style.clone()));
+        });
+
+        parent.spawn((
+            ButtonBundle {
+                style: button_style.clone(),
+                background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                ..default()
+            },
+            EditorTurnButton,
+        )).with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("White", text_style.clone()),
+                EditorTurnText,
+            ));
+        });
+
+        for (label, is_clear, is_play) in [("Clear", true, false), ("Play", false, true), ("Cancel", false, false)] {
+            let mut button = parent.spawn(ButtonBundle {
+                style: button_style.clone(),
+                background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                ..default()
+            });
+            if is_clear {
+                button.insert(EditorClearButton);
+            } else if is_play {
+                button.insert(EditorPlayButton);
+            } else {
+                button.insert(EditorCancelButton);
+            }
+            button.with_children(|parent| {
+                parent.spawn(TextBundle::from_section(label, text_style.clone()));
+            });
+        }
+    });
 }
 
-fn handle_resize(
-    mut board_query: Query<(&mut Transform, &mut Sprite), With<ChessBoard>>,
-    mut square_query: Query<(&mut Transform, &mut Sprite, &Square), (With<Square>, Without<ChessBoard>)>,
-    mut piece_query: Query<(&mut Transform, &mut Sprite, &Piece), (With<Piece>, Without<ChessBoard>, Without<Square>)>,
+// Brings the piece sprites in line with `board`, touching only what differs:
+// sprites already on the right square stay, pieces that turn up elsewhere
+// slide there from the nearest leftover sprite of the same kind, and the
+// rest are spawned or despawned. Undo and review stay smooth this way.
+fn sync_pieces_to_board(
+    commands: &mut Commands,
+    pieces: &Query<(Entity, &Piece)>,
+    board: &Board,
+    layout: &BoardLayout,
+    assets: &ChessAssets,
 ) {
-    let board_size = 8.0 * SQUARE_SIZE;
-    
-    // Update board
-    if let Ok((mut transform, mut sprite)) = board_query.get_single_mut() {
-        sprite.custom_size = Some(Vec2::new(board_size + 20.0, board_size + 20.0));
-        transform.translation.x = 0.0;
-        transform.translation.y = 0.0;
+    let same_kind = |sprite: &Piece, piece: &ChessPiece| {
+        sprite.piece_type == piece.piece_type && sprite.is_white == (piece.color == ChessColor::White)
+    };
+
+    let mut leftover = Vec::new();
+    for (entity, sprite) in pieces.iter() {
+        commands.entity(entity).remove::<SelectedPiece>();
+        let in_place = board.piece_map().get(&sprite.position)
+            .is_some_and(|piece| same_kind(sprite, piece));
+        if !in_place {
+            leftover.push((entity, *sprite));
+        }
     }
 
-    let board_offset = Vec3::new(-board_size / 2.0, -board_size / 2.0, 0.0);
+    let mut missing: Vec<(Position, ChessPiece)> = board.piece_map().iter()
+        .filter(|(pos, piece)| {
+            !pieces.iter().any(|(_, sprite)| sprite.position == **pos && same_kind(sprite, piece))
+        })
+        .map(|(&pos, &piece)| (pos, piece))
+        .collect();
+    // HashMap order varies; keep which sprite goes where the same every time
+    missing.sort_by_key(|(pos, _)| (pos.rank, pos.file));
 
-    // Update squares
-    for (mut transform, mut sprite, square) in square_query.iter_mut() {
-        sprite.custom_size = Some(Vec2::new(SQUARE_SIZE, SQUARE_SIZE));
-        transform.translation = Vec3::new(
-            board_offset.x + (square.position.file as f32 - 1.0) * SQUARE_SIZE + SQUARE_SIZE / 2.0,
-            board_offset.y + (square.position.rank as f32 - 1.0) * SQUARE_SIZE + SQUARE_SIZE / 2.0,
-            1.0,
-        );
+    for (pos, piece) in missing {
+        let nearest = leftover.iter()
+            .enumerate()
+            .filter(|(_, (_, sprite))| same_kind(sprite, &piece))
+            .min_by_key(|(_, (_, sprite))| {
+                sprite.position.rank.abs_diff(pos.rank).max(sprite.position.file.abs_diff(pos.file))
+            })
+            .map(|(index, _)| index);
+        match nearest {
+            Some(index) => {
+                let (entity, sprite) = leftover.swap_remove(index);
+                commands.entity(entity).insert((
+                    Piece { position: pos, ..sprite },
+                    MovingPiece {
+                        target_position: board_position_to_world(pos, 2.0, layout),
+                        speed: PIECE_SPEED,
+                    },
+                ));
+            }
+            None => {
+                let is_white = piece.color == ChessColor::White;
+                spawn_piece(commands, piece.piece_type, is_white, pos.file, pos.rank, layout, assets);
+            }
+        }
     }
 
-    // Update pieces
-    for (mut transform, mut sprite, piece) in piece_query.iter_mut() {
-        sprite.custom_size = Some(Vec2::new(SQUARE_SIZE * 0.9, SQUARE_SIZE * 0.9));
-        transform.translation = Vec3::new(
-            board_offset.x + (piece.position.file as f32 - 1.0) * SQUARE_SIZE + SQUARE_SIZE / 2.0,
-            board_offset.y + (piece.position.rank as f32 - 1.0) * SQUARE_SIZE + SQUARE_SIZE / 2.0,
-            2.0,
-        );
+    for (entity, _) in leftover {
+        commands.entity(entity).despawn_recursive();
     }
 }
 
-fn handle_input(
+// Enters the editor from the "Edit" button and leaves it again via "Cancel"
+#[allow(clippy::too_many_arguments)]
+fn handle_editor_toggle(
     mut commands: Commands,
-    windows: Query<&Window>,
-    camera_q: Query<(&Camera, &GlobalTransform)>,
+    edit_query: Query<&Interaction, (Changed<Interaction>, With<EditorButton>)>,
+    cancel_query: Query<&Interaction, (Changed<Interaction>, With<EditorCancelButton>)>,
     mut game_state: ResMut<GameState>,
-    mut pieces: Query<(Entity, &mut Piece, &Transform)>,
-    selected_pieces: Query<Entity, With<SelectedPiece>>,
-    chess_assets: Res<ChessAssets>,
-    buttons: Res<Input<MouseButton>>,
+    mut editor: ResMut<EditorState>,
+    mut analysis: ResMut<AnalysisState>,
+    editor_mode: Res<State<EditorMode>>,
+    mut next_editor_mode: ResMut<NextState<EditorMode>>,
     turn: Res<State<Turn>>,
+    pieces: Query<(Entity, &Piece)>,
+    chess_assets: Res<ChessAssets>,
+    layout: Res<BoardLayout>,
+) {
+    let editing = *editor_mode.get() == EditorMode::Active;
+
+    if !editing && *turn.get() == Turn::Player &&
+       edit_query.iter().any(|i| *i == Interaction::Pressed) {
+        // Start from the current position
+        editor.board = game_state.board.clone();
+        game_state.view_ply = None;
+        game_state.selected_square = None;
+        game_state.valid_moves.clear();
+        analysis.open = false;
+        analysis.reset();
+        sync_pieces_to_board(&mut commands, &pieces, &editor.board, &layout, &chess_assets);
+        next_editor_mode.set(EditorMode::Active);
+    }
+
+    if editing && cancel_query.iter().any(|i| *i == Interaction::Pressed) {
+        sync_pieces_to_board(&mut commands, &pieces, &game_state.board, &layout, &chess_assets);
+        next_editor_mode.set(EditorMode::Inactive);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_editor_palette(
+    mut commands: Commands,
+    palette_query: Query<(&Interaction, &EditorPaletteButton), Changed<Interaction>>,
+    turn_query: Query<&Interaction, (Changed<Interaction>, With<EditorTurnButton>)>,
+    clear_query: Query<&Interaction, (Changed<Interaction>, With<EditorClearButton>)>,
+    mut editor: ResMut<EditorState>,
+    pieces: Query<(Entity, &Piece)>,
+    chess_assets: Res<ChessAssets>,
+    layout: Res<BoardLayout>,
+) {
+    for (interaction, button) in palette_query.iter() {
+        if *interaction == Interaction::Pressed {
+            editor.brush = button.piece;
+        }
+    }
+
+    if turn_query.iter().any(|i| *i == Interaction::Pressed) {
+        let turn = match editor.board.current_turn() {
+            ChessColor::White => ChessColor::Black,
+            ChessColor::Black => ChessColor::White,
+        };
+        editor.board.set_turn(turn);
+    }
+
+    if clear_query.iter().any(|i| *i == Interaction::Pressed) {
+        editor.board.clear();
+        sync_pieces_to_board(&mut commands, &pieces, &editor.board, &layout, &chess_assets);
+    }
+}
+
+// Validates the edited position and starts a new game from it
+#[allow(clippy::too_many_arguments)]
+fn handle_editor_play(
+    mut commands: Commands,
+    play_query: Query<&Interaction, (Changed<Interaction>, With<EditorPlayButton>)>,
+    mut game_state: ResMut<GameState>,
+    editor: Res<EditorState>,
+    mut next_editor_mode: ResMut<NextState<EditorMode>>,
     mut turn_state: ResMut<NextState<Turn>>,
+    pieces: Query<(Entity, &Piece)>,
+    chess_assets: Res<ChessAssets>,
+    mut puzzles: ResMut<PuzzleState>,
+    layout: Res<BoardLayout>,
+) {
+    if !play_query.iter().any(|i| *i == Interaction::Pressed) {
+        return;
+    }
+
+    // Going through FEN drops the old game's history and sets castling rights
+    // from where the kings and rooks actually stand. from_fen also runs
+    // Board::validate, so impossible setups are turned away here.
+    let fen = editor_fen(&editor.board);
+    let board = match Board::from_fen(&fen) {
+        Ok(board) => board,
+        Err(err) => {
+            spawn_toast(&mut commands, err);
+            return;
+        }
+    };
+
+    puzzles.active = None;
+    game_state.reset_for_new_position(board, Some(fen));
+
+    sync_pieces_to_board(&mut commands, &pieces, &game_state.board, &layout, &chess_assets);
+    next_editor_mode.set(EditorMode::Inactive);
+    turn_state.set(game_state.turn());
+}
+
+// FEN for the edited position. Castling is allowed wherever king and rook
+// are still on their original squares.
+fn editor_fen(board: &Board) -> String {
+    let fen = board.to_fen();
+    let placement_and_side: Vec<&str> = fen.split(' ').take(2).collect();
+
+    let has = |c: char, file: u8, rank: u8| {
+        board.get_piece(Position { rank, file }).is_some_and(|p| p.to_fen_char() == c)
+    };
+    let mut castling = String::new();
+    if has('K', 5, 1) && has('R', 8, 1) { castling.push('K'); }
+    if has('K', 5, 1) && has('R', 1, 1) { castling.push('Q'); }
+    if has('k', 5, 8) && has('r', 8, 8) { castling.push('k'); }
+    if has('k', 5, 8) && has('r', 1, 8) { castling.push('q'); }
+    if castling.is_empty() {
+        castling.push('-');
+    }
+
+    format!("{} {} - 0 1", placement_and_side.join(" "), castling)
+}
+
+// Places or erases pieces on the board with the current brush
+#[allow(clippy::too_many_arguments)]
+fn handle_editor_input(
+    mut commands: Commands,
+    windows: Query<&Window>,
+    buttons: Res<Input<MouseButton>>,
+    editor_mode: Res<State<EditorMode>>,
+    mut editor: ResMut<EditorState>,
+    pieces: Query<(Entity, &Piece)>,
+    chess_assets: Res<ChessAssets>,
+    layout: Res<BoardLayout>,
 ) {
-    // Only process during player's turn
-    if *turn.get() != Turn::Player {
+    if *editor_mode.get() != EditorMode::Active || !buttons.just_pressed(MouseButton::Left) {
         return;
     }
 
     let window = windows.single();
-    
-    if let Some(cursor_pos) = window.cursor_position() {
-        if let Some(position) = get_board_position(Some(cursor_pos), window) {
-            if buttons.just_pressed(MouseButton::Left) {
-                // First, determine what action to take
-                let action = if let Some(selected_entity) = selected_pieces.iter().next() {
-                    if let Some((_, piece, _)) = pieces.iter().find(|(e, _, _)| *e == selected_entity) {
-                        let valid_moves = game_state.board.get_valid_moves(piece.position);
-                        if let Some(valid_move) = valid_moves.iter().find(|m| m.to == position) {
-                            let is_promotion = piece.piece_type == ChessPieceType::Pawn && 
-                                ((piece.is_white && valid_move.to.rank == 8) ||
-                                 (!piece.is_white && valid_move.to.rank == 1));
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
 
-                            if is_promotion {
-                                Some(PlayerAction::ShowPromotionDialog {
-                                    from: valid_move.from,
-                                    to: valid_move.to,
-                                })
-                            } else {
-                                let captured_entity = pieces.iter()
-                                    .find(|(_, p, _)| p.position == valid_move.to)
-                                    .map(|(e, _, _)| e);
-                                Some(PlayerAction::MakeMove {
-                                    chess_move: *valid_move,
-                                    selected_entity,
-                                    captured_entity,
-                                })
-                            }
-                        } else if let Some((entity, _, _)) = pieces.iter().find(|(_, p, _)| {
-                            p.position == position && p.is_white
-                        }) {
-                            Some(PlayerAction::SelectPiece {
-                                entity,
-                                deselect_entity: Some(selected_entity),
-                            })
-                        } else {
-                            Some(PlayerAction::Deselect {
-                                entity: selected_entity,
-                            })
-                        }
-                    } else {
-                        Some(PlayerAction::Deselect {
-                            entity: selected_entity,
-                        })
-                    }
-                } else if let Some((entity, _, _)) = pieces.iter().find(|(_, p, _)| {
-                    p.position == position && p.is_white
-                }) {
-                    Some(PlayerAction::SelectPiece {
-                        entity,
-                        deselect_entity: None,
-                    })
-                } else {
-                    None
-                };
+    // Ignore clicks beside the board (e.g. on the palette)
+    if let Some(position) = hovered_square(cursor, window, &layout) {
+        let brush = editor.brush;
+        editor.board.set_piece(position, brush);
+        sync_pieces_to_board(&mut commands, &pieces, &editor.board, &layout, &chess_assets);
+    }
+}
 
-                // Then execute the action
-                if let Some(action) = action {
-                    match action {
-                        PlayerAction::ShowPromotionDialog { from, to } => {
-                            game_state.pending_promotion = Some(PendingPromotion { from, to });
-                            spawn_promotion_dialog(&mut commands, &chess_assets, true);
-                        }
-                        PlayerAction::MakeMove { chess_move, selected_entity, captured_entity } => {
-                            if game_state.board.make_move(chess_move).is_ok() {
-                                if let Some(entity) = captured_entity {
-                                    commands.entity(entity).despawn();
-                                }
-
-                                if let Some((entity, mut piece, _transform)) = pieces.iter_mut().find(|(e, _, _)| *e == selected_entity) {
-                                    move_piece(
-                                        &mut commands,
-                                        entity,
-                                        &mut piece,
-                                        chess_move.to,
-                                    );
-                                }
+fn update_editor_panel(
+    editor_mode: Res<State<EditorMode>>,
+    editor: Res<EditorState>,
+    chess_assets: Res<ChessAssets>,
+    mut panel_query: Query<&mut Visibility, With<EditorPanel>>,
+    mut palette_query: Query<(Entity, &EditorPaletteButton, &mut BackgroundColor, Option<&UiImage>)>,
+    mut turn_text_query: Query<&mut Text, With<EditorTurnText>>,
+    mut commands: Commands,
+) {
+    let active = *editor_mode.get() == EditorMode::Active;
+    for mut visibility in panel_query.iter_mut() {
+        *visibility = if active { Visibility::Visible } else { Visibility::Hidden };
+    }
+    if !active {
+        return;
+    }
 
-                                commands.entity(selected_entity).remove::<SelectedPiece>();
-                                turn_state.set(Turn::AI);
-                            }
-                        }
-                        PlayerAction::SelectPiece { entity, deselect_entity } => {
-                            if let Some(old_entity) = deselect_entity {
-                                commands.entity(old_entity).remove::<SelectedPiece>();
-                            }
-                            commands.entity(entity).insert(SelectedPiece);
-                        }
-                        PlayerAction::Deselect { entity } => {
-                            commands.entity(entity).remove::<SelectedPiece>();
-                        }
-                    }
+    for (entity, button, mut color, image) in palette_query.iter_mut() {
+        // Palette buttons get their piece image the first time the panel is shown
+        if let (Some(piece), None) = (button.piece, image) {
+            let is_white = piece.color == ChessColor::White;
+            commands.entity(entity).insert(UiImage::new(chess_assets.piece_texture(piece.piece_type, is_white)));
+        }
+        *color = if button.piece == editor.brush {
+            Color::rgb(0.3, 0.6, 0.3).into()
+        } else {
+            Color::rgb(0.4, 0.4, 0.4).into()
+        };
+    }
+
+    if let Ok(mut text) = turn_text_query.get_single_mut() {
+        text.sections[0].value = match editor.board.current_turn() {
+            ChessColor::White => "White".to_string(),
+            ChessColor::Black => "Black".to_string(),
+        };
+    }
+}
+
+fn update_last_move(
+    mut last_move_query: Query<&mut Text, With<LastMoveText>>,
+    game_state: Res<GameState>,
+) {
+    if let Ok(mut text) = last_move_query.get_single_mut() {
+        let recent = game_state.board.last_n_moves(2);
+        if !recent.is_empty() {
+            let moves: Vec<String> = recent.iter()
+                .map(|m| format!("{} → {}", m.from.to_algebraic(), m.to.to_algebraic()))
+                .collect();
+            let label = if moves.len() > 1 { "Last moves" } else { "Last move" };
+            text.sections[0].value = format!("{}: {}", label, moves.join(", "));
+        }
+    }
+}
+
+fn update_move_count_text(
+    mut query: Query<&mut Text, With<MoveCountText>>,
+    game_state: Res<GameState>,
+) {
+    if let Ok(mut text) = query.get_single_mut() {
+        let board = &game_state.board;
+        let played = board.move_history().len();
+        // Counts down once the fifty-move rule is getting close
+        let left = 100u32.saturating_sub(board.halfmove_clock()).div_ceil(2);
+        text.sections[0].value = if left <= FIFTY_MOVE_WARNING {
+            format!("Moves: {} ({} to fifty-move draw)", played, left)
+        } else {
+            format!("Moves: {}", played)
+        };
+    }
+}
+
+fn update_elo_text(
+    mut query: Query<&mut Text, With<EloText>>,
+    game_state: Res<GameState>,
+) {
+    if let Ok(mut text) = query.get_single_mut() {
+        text.sections[0].value = format!("~{} Elo", game_state.ai.estimated_elo());
+    }
+}
+
+fn update_captured_pieces_text(
+    mut query: Query<&mut Text, With<CapturedPiecesText>>,
+    game_state: Res<GameState>,
+) {
+    if let Ok(mut text) = query.get_single_mut() {
+        // Each side starts with 16 pieces and promotions don't change the count,
+        // so whatever is missing has been captured
+        let pieces = game_state.board.piece_map();
+        let white_left = pieces.values().filter(|p| p.color == ChessColor::White).count();
+        let black_left = pieces.len() - white_left;
+        text.sections[0].value = format!(
+            "Captured - White: {} Black: {}",
+            16 - black_left.min(16),
+            16 - white_left.min(16),
+        );
+    }
+}
+
+fn spawn_square_tooltip(commands: &mut Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 16.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        })
+        .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.8)),
+        SquareTooltip,
+    )).insert(Visibility::Hidden);
+}
+
+fn handle_square_info_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<SquareInfoButton>)>,
+    mut info: ResMut<SquareInfo>,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            info.enabled = !info.enabled;
+        }
+    }
+}
+
+// Follows the cursor over the board, listing how many pieces of each side
+// attack the hovered square and how many moves a piece on it has
+fn update_square_tooltip(
+    windows: Query<&Window>,
+    game_state: Res<GameState>,
+    info: Res<SquareInfo>,
+    editor_mode: Res<State<EditorMode>>,
+    mut tooltip_query: Query<(&mut Text, &mut Style, &mut Visibility), With<SquareTooltip>>,
+    layout: Res<BoardLayout>,
+) {
+    let Ok((mut text, mut style, mut visibility)) = tooltip_query.get_single_mut() else {
+        return;
+    };
+    let window = windows.single();
+    let hovered = window.cursor_position()
+        .filter(|_| info.enabled && *editor_mode.get() == EditorMode::Inactive)
+        .and_then(|cursor| hovered_square(cursor, window, &layout).map(|square| (cursor, square)));
+    let Some((cursor, square)) = hovered else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    // Count on whatever position is on screen
+    let board = match game_state.view_ply {
+        Some(ply) => game_state.board_at(ply),
+        None => game_state.board.clone(),
+    };
+    let white = board.attackers(square, ChessColor::White).len();
+    let black = board.attackers(square, ChessColor::Black).len();
+    let mut label = format!("{}\nWhite attackers: {}\nBlack attackers: {}", square.to_algebraic(), white, black);
+    // A piece's moves are listed whoever's turn it is
+    if board.get_piece(square).is_some() {
+        label.push_str(&format!("\nLegal moves: {}", board.legal_moves_from(square).len()));
+    }
+    if text.sections[0].value != label {
+        text.sections[0].value = label;
+    }
+
+    style.left = Val::Px(cursor.x + 16.0);
+    style.top = Val::Px(cursor.y + 16.0);
+    *visibility = Visibility::Visible;
+}
+
+// Collects keystrokes while the move box has focus and plays the move on
+// Enter. A pawn move to the last rank without a piece opens the promotion
+// dialog, as it does when clicking.
+#[allow(clippy::too_many_arguments)]
+fn handle_move_entry(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<MoveEntryButton>)>,
+    mut text_query: Query<&mut Text, With<MoveEntryText>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut entry: ResMut<MoveEntry>,
+    mut game_state: ResMut<GameState>,
+    mut pieces: Query<(Entity, &mut Piece, &mut Transform)>,
+    selected_pieces: Query<Entity, With<SelectedPiece>>,
+    turn: Res<State<Turn>>,
+    mut turn_state: ResMut<NextState<Turn>>,
+    editor_mode: Res<State<EditorMode>>,
+    puzzles: Res<PuzzleState>,
+    layout: Res<BoardLayout>,
+    chess_assets: Res<ChessAssets>,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            entry.focused = !entry.focused;
+            entry.text.clear();
+        }
+    }
+
+    if entry.focused {
+        for event in characters.read() {
+            let c = event.char;
+            if (c.is_ascii_alphanumeric() || "-=+#".contains(c)) && entry.text.len() < MOVE_ENTRY_MAX_LENGTH {
+                entry.text.push(c);
+            }
+        }
+        if keys.just_pressed(KeyCode::Back) {
+            entry.text.pop();
+        }
+        if keys.just_pressed(KeyCode::Escape) {
+            entry.focused = false;
+            entry.text.clear();
+        }
+    } else {
+        characters.clear();
+    }
+
+    if entry.focused && keys.just_pressed(KeyCode::Return) && !entry.text.is_empty() {
+        let text = std::mem::take(&mut entry.text);
+        let board = game_state.board.clone();
+        if *turn.get() != Turn::Player || *editor_mode.get() == EditorMode::Active
+            || game_state.view_ply.is_some() || game_state.pending_promotion.is_some() {
+            spawn_toast(&mut commands, "You can't move right now");
+        } else if let Some(mv) = Move::from_san(&text, &board).or_else(|| Move::from_uci(&text, &board)) {
+            if play_player_move(
+                &mut commands,
+                &mut game_state,
+                &mut pieces,
+                mv,
+                &layout,
+                &chess_assets,
+                &puzzles,
+                &mut turn_state,
+            ) {
+                for entity in selected_pieces.iter() {
+                    commands.entity(entity).remove::<SelectedPiece>();
                 }
+                game_state.selected_square = None;
+                game_state.valid_moves.clear();
             }
+        } else if let Some(mv) = board.legal_moves().into_iter()
+            .find(|m| m.promotion.is_some() && text.len() == 4 && m.to_uci().starts_with(&text)) {
+            game_state.pending_promotion = Some(PendingPromotion { from: mv.from, to: mv.to });
+            let is_white = board.current_turn() == ChessColor::White;
+            spawn_promotion_dialog(&mut commands, &chess_assets, is_white, &layout);
+        } else {
+            spawn_toast(&mut commands, &format!("Not a legal move: {}", text));
         }
     }
-}
 
-fn update_selected_pieces(
-    mut pieces: Query<(&mut Sprite, Option<&SelectedPiece>), With<Piece>>,
-) {
-    for (mut sprite, selected) in pieces.iter_mut() {
-        if selected.is_some() {
-            sprite.color = sprite.color.with_a(0.7);
+    if let Ok(mut text) = text_query.get_single_mut() {
+        let label = if entry.focused {
+            format!("Move: {}_", entry.text)
         } else {
-            sprite.color = sprite.color.with_a(1.0);
+            "Type move".to_string()
+        };
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
         }
     }
 }
 
-fn update_ai(
-    mut game_state: ResMut<GameState>,
+// Sets up the next puzzle, with the AI's side given to the opponent
+#[allow(clippy::too_many_arguments)]
+fn handle_puzzle_button(
     mut commands: Commands,
-    mut pieces: Query<(Entity, &mut Piece, &mut Transform)>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<PuzzleButton>)>,
+    mut side_text: Query<&mut Text, With<AiSideText>>,
+    mut game_state: ResMut<GameState>,
+    mut puzzles: ResMut<PuzzleState>,
+    editor_mode: Res<State<EditorMode>>,
     mut turn_state: ResMut<NextState<Turn>>,
-    turn: Res<State<Turn>>,
+    pieces: Query<(Entity, &Piece)>,
     chess_assets: Res<ChessAssets>,
+    layout: Res<BoardLayout>,
 ) {
-    // Only process during AI's turn
-    if *turn.get() != Turn::AI {
+    if !interaction_query.iter().any(|i| *i == Interaction::Pressed) {
+        return;
+    }
+    if *editor_mode.get() == EditorMode::Active || game_state.pending_promotion.is_some() {
         return;
     }
 
-    // Set thinking state if not already set
-    if !game_state.ai_thinking {
-        game_state.ai_thinking = true;
+    let index = puzzles.next;
+    let puzzle = &PUZZLES[index];
+    puzzles.next = (index + 1) % PUZZLES.len();
+
+    let Ok(board) = Board::from_fen(puzzle.fen) else {
+        spawn_toast(&mut commands, "Puzzle has an invalid position");
         return;
+    };
+    let mut line = board.clone();
+    let mut solution = Vec::new();
+    for text in puzzle.solution.split_whitespace() {
+        let Some(mv) = Move::from_san(text, &line).or_else(|| Move::from_uci(text, &line)) else {
+            spawn_toast(&mut commands, &format!("Puzzle solution has an illegal move: {}", text));
+            return;
+        };
+        let _ = line.make_move(mv);
+        solution.push(mv);
     }
 
-    // Clone the board to avoid borrow issues
-    let board_clone = game_state.board.clone();
-    
-    // Get AI's move
-    if let Some(ai_move) = game_state.ai.get_move(&board_clone) {
-        // Try to make the move
-        if game_state.board.make_move(ai_move).is_ok() {
-            println!("AI attempting move: {:?}", ai_move);
-            
-            // Check if there's a piece to capture at the destination
-            let captured_entity = pieces.iter()
-                .find(|(_, p, _)| p.position == ai_move.to)
-                .map(|(e, _, _)| e);
+    let player = board.current_turn();
+    game_state.reset_for_new_position(board, Some(puzzle.fen.to_string()));
+    game_state.ai_color = match player {
+        ChessColor::White => ChessColor::Black,
+        ChessColor::Black => ChessColor::White,
+    };
+    puzzles.active = Some(ActivePuzzle { solution, solved: 0 });
 
-            // Remove captured piece if any
-            if let Some(entity) = captured_entity {
-                commands.entity(entity).despawn();
+    sync_pieces_to_board(&mut commands, &pieces, &game_state.board, &layout, &chess_assets);
+    turn_state.set(Turn::Player);
+
+    let (side, ai_side) = match player {
+        ChessColor::White => ("White", "Black"),
+        ChessColor::Black => ("Black", "White"),
+    };
+    if let Ok(mut text) = side_text.get_single_mut() {
+        text.sections[0].value = format!("AI: {}", ai_side);
+    }
+    spawn_toast(&mut commands, &format!("Puzzle {}: {} to move", index + 1, side));
+}
+
+// Checks each of the player's moves against the solution, taking wrong ones
+// back, and answers right ones with the solution's reply. Any mate counts.
+#[allow(clippy::too_many_arguments)]
+fn update_puzzle(
+    mut commands: Commands,
+    mut game_state: ResMut<GameState>,
+    mut puzzles: ResMut<PuzzleState>,
+    mut turn_state: ResMut<NextState<Turn>>,
+    moving: Query<(), With<MovingPiece>>,
+    pieces: Query<(Entity, &Piece)>,
+    chess_assets: Res<ChessAssets>,
+    layout: Res<BoardLayout>,
+) {
+    let Some(puzzle) = puzzles.active.as_mut() else {
+        return;
+    };
+    // Let the player's move finish sliding before judging it
+    if !moving.is_empty() {
+        return;
+    }
+
+    // Takebacks rewind the puzzle along with the game
+    let played = game_state.board.move_history().len();
+    puzzle.solved = puzzle.solved.min(played);
+
+    if played > puzzle.solved {
+        let mv = game_state.board.move_history()[puzzle.solved];
+        let expected = puzzle.solution.get(puzzle.solved);
+        if game_state.board.is_checkmate() {
+            puzzle.solved = puzzle.solution.len();
+        } else if expected.map(|m| m.to_uci()) == Some(mv.to_uci()) {
+            puzzle.solved += 1;
+        } else {
+            if let Err(err) = game_state.take_back(1) {
+                spawn_toast(&mut commands, err);
+                return;
             }
-            
-            // Handle promotion
-            if let Some(promotion_type) = ai_move.promotion {
-                // Remove the old pawn
-                for (entity, piece, _) in pieces.iter() {
-                    if piece.position == ai_move.from {
-                        commands.entity(entity).despawn();
-                        break;
-                    }
-                }
+            sync_pieces_to_board(&mut commands, &pieces, &game_state.board, &layout, &chess_assets);
+            spawn_toast(&mut commands, "Try again");
+            return;
+        }
+    }
 
-                // Spawn the promoted piece
-                let world_pos = board_position_to_world(ai_move.to, 2.0);
-                commands.spawn((
-                    SpriteBundle {
-                        texture: match promotion_type {
-                            ChessPieceType::Queen => chess_assets.black_queen.clone(),
-                            ChessPieceType::Rook => chess_assets.black_rook.clone(),
-                            ChessPieceType::Bishop => chess_assets.black_bishop.clone(),
-                            ChessPieceType::Knight => chess_assets.black_knight.clone(),
-                            _ => unreachable!(),
-                        },
-                        transform: Transform::from_translation(world_pos)
-                            .with_scale(Vec3::splat(1.0)),
-                        sprite: Sprite {
-                            custom_size: Some(Vec2::new(SQUARE_SIZE - 10.0, SQUARE_SIZE - 10.0)),
-                            ..default()
-                        },
-                        ..default()
-                    },
-                    Piece {
-                        piece_type: promotion_type,
-                        is_white: false,
-                        position: ai_move.to,
-                    },
-                ));
+    if puzzle.solved >= puzzle.solution.len() {
+        puzzles.active = None;
+        spawn_toast(&mut commands, "Puzzle solved!");
+        // Play on against the AI from here
+        turn_state.set(game_state.turn());
+        return;
+    }
+
+    if game_state.board.current_turn() != game_state.player_color() {
+        let reply = puzzle.solution[puzzle.solved];
+        if game_state.board.make_move(reply).is_ok() {
+            puzzle.solved += 1;
+            sync_pieces_to_board(&mut commands, &pieces, &game_state.board, &layout, &chess_assets);
+        } else {
+            puzzles.active = None;
+            spawn_toast(&mut commands, "Puzzle reply is illegal here");
+        }
+    }
+}
+
+fn handle_flip_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<FlipBoardButton>)>,
+    mut layout: ResMut<BoardLayout>,
+) {
+    if interaction_query.iter().any(|i| *i == Interaction::Pressed) {
+        layout.flipped = !layout.flipped;
+    }
+}
+
+// Starts a fresh round of coordinate training, or ends the current one
+// with its score
+fn handle_training_button(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<TrainingButton>)>,
+    mut training: ResMut<CoordinateTraining>,
+    editor_mode: Res<State<EditorMode>>,
+    time: Res<Time>,
+) {
+    if !interaction_query.iter().any(|i| *i == Interaction::Pressed) {
+        return;
+    }
+    if training.target.is_some() {
+        training.target = None;
+        spawn_toast(&mut commands, &format!("Coordinate training: {}", training.score()));
+    } else if *editor_mode.get() == EditorMode::Inactive {
+        training.start(time.elapsed_seconds());
+    }
+}
+
+// Checks clicks on the board against the square asked for. Only correct
+// answers count towards the average time.
+#[allow(clippy::too_many_arguments)]
+fn update_training(
+    mut commands: Commands,
+    windows: Query<&Window>,
+    buttons: Res<Input<MouseButton>>,
+    time: Res<Time>,
+    mut training: ResMut<CoordinateTraining>,
+    confirm: Res<ConfirmDialog>,
+    layout: Res<BoardLayout>,
+    mut text_query: Query<&mut Text, With<TrainingText>>,
+) {
+    if let Some(target) = training.target {
+        let window = windows.single();
+        let clicked = window.cursor_position()
+            .filter(|_| buttons.just_pressed(MouseButton::Left) && confirm.pending.is_none())
+            .and_then(|cursor| hovered_square(cursor, window, &layout));
+        if let Some(square) = clicked {
+            let now = time.elapsed_seconds();
+            training.attempts += 1;
+            if square == target {
+                training.correct += 1;
+                training.correct_secs += now - training.asked_at;
+                training.next_target(now);
             } else {
-                // Handle normal move
-                for (entity, mut piece, transform) in pieces.iter_mut() {
-                    if piece.position == ai_move.from {
-                        piece.position = ai_move.to;
-                        let target_pos = board_position_to_world(ai_move.to, transform.translation.z);
-                        commands.entity(entity).insert(MovingPiece {
-                            target_position: target_pos,
-                            speed: 500.0,
-                        });
-                        break;
-                    }
-                }
+                spawn_toast(&mut commands, &format!("That was {}", square.to_algebraic()));
             }
         }
     }
-    
-    game_state.ai_thinking = false;
-    turn_state.set(Turn::Player);
+
+    let label = match training.target {
+        Some(target) => format!("Find {} ({})", target.to_algebraic(), training.score()),
+        None => String::new(),
+    };
+    if let Ok(mut text) = text_query.get_single_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
 }
 
-fn spawn_ui(commands: &mut Commands) {
-    // Main UI container
+fn spawn_captured_tray(commands: &mut Commands) {
     commands.spawn(NodeBundle {
         style: Style {
-            width: Val::Percent(100.0),
-            height: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            left: Val::Px(5.0),
+            top: Val::Px(60.0),
+            width: Val::Px(TRAY_ICON_SIZE * 3.0),
             flex_direction: FlexDirection::Column,
-            justify_content: JustifyContent::SpaceBetween,
             ..default()
         },
         ..default()
     }).with_children(|parent| {
-        // Top bar
-        parent.spawn(NodeBundle {
-            style: Style {
-                width: Val::Percent(100.0),
-                height: Val::Px(50.0),
-                padding: UiRect::all(Val::Px(10.0)),
-                justify_content: JustifyContent::SpaceBetween,
-                align_items: AlignItems::Center,
-                ..default()
-            },
-            background_color: Color::rgb(0.2, 0.2, 0.2).into(),
-            ..default()
-        }).with_children(|parent| {
-            // Left section with game status and evaluation
-            parent.spawn(NodeBundle {
-                style: Style {
-                    flex_direction: FlexDirection::Row,
-                    align_items: AlignItems::Center,
-                    margin: UiRect::right(Val::Px(20.0)),
-                    ..default()
-                },
-                ..default()
-            }).with_children(|parent| {
-                // Game status text
-                parent.spawn((
-                    TextBundle::from_section(
-                        "White's Turn",
-                        TextStyle {
-                            font_size: 24.0,
-                            color: Color::WHITE,
-                            ..default()
-                        },
-                    ),
-                    GameStatusText,
-                ));
-
-                // Evaluation text
-                parent.spawn((
-                    TextBundle::from_section(
-                        "Eval: 0.0",
-                        TextStyle {
-                            font_size: 24.0,
-                            color: Color::WHITE,
-                            ..default()
-                        },
-                    )
-                    .with_style(Style {
-                        margin: UiRect::left(Val::Px(20.0)),
-                        ..default()
-                    }),
-                    EvaluationText,
-                ));
-
-                // AI thinking text
-                parent.spawn((
-                    TextBundle::from_section(
-                        "AI is thinking...",
-                        TextStyle {
-                            font_size: 24.0,
-                            color: Color::YELLOW,
-                            ..default()
-                        },
-                    )
-                    .with_style(Style {
-                        margin: UiRect::left(Val::Px(20.0)),
-                        ..default()
-                    }),
-                    AiThinkingText,
-                ));
-            });
-
-            // New Game button
+        for by_white in [false, true] {
             parent.spawn((
-                ButtonBundle {
+                NodeBundle {
                     style: Style {
-                        padding: UiRect::all(Val::Px(10.0)),
+                        flex_direction: FlexDirection::Row,
+                        flex_wrap: FlexWrap::Wrap,
+                        margin: UiRect::bottom(Val::Px(10.0)),
                         ..default()
                     },
-                    background_color: Color::rgb(0.4, 0.4, 0.4).into(),
                     ..default()
                 },
-                MenuButton,
-            )).with_children(|parent| {
-                parent.spawn(TextBundle::from_section(
-                    "New Game",
-                    TextStyle {
-                        font_size: 20.0,
-                        color: Color::WHITE,
-                        ..default()
-                    },
-                ));
-            });
-        });
-
-        // Bottom bar
-        parent.spawn(NodeBundle {
-            style: Style {
-                width: Val::Percent(100.0),
-                height: Val::Px(40.0),
-                padding: UiRect::all(Val::Px(10.0)),
-                justify_content: JustifyContent::Center,
-                align_items: AlignItems::Center,
-                ..default()
-            },
-            background_color: Color::rgb(0.2, 0.2, 0.2).into(),
-            ..default()
-        }).with_children(|parent| {
-            // Last move text
-            parent.spawn((
-                TextBundle::from_section(
-                    "",
-                    TextStyle {
-                        font_size: 20.0,
-                        color: Color::WHITE,
-                        ..default()
-                    },
-                ),
-                LastMoveText,
+                CapturedTrayRow { by_white },
             ));
-        });
-    });
-}
-
-fn update_ui_text(
-    turn: Res<State<Turn>>,
-    mut text_query: Query<&mut Visibility, With<AiThinkingText>>,
-) {
-    if let Ok(mut visibility) = text_query.get_single_mut() {
-        *visibility = if *turn.get() == Turn::AI {
-            Visibility::Visible
-        } else {
-            Visibility::Hidden
-        };
-    }
+        }
+
+        parent.spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            MaterialDiffText,
+        ));
+    });
 }
 
-fn show_valid_moves(
+// Rebuilds the tray icons whenever the position changes
+fn update_captured_tray(
     mut commands: Commands,
     game_state: Res<GameState>,
-    selected_pieces: Query<&Piece, With<SelectedPiece>>,
     chess_assets: Res<ChessAssets>,
-    indicators: Query<Entity, With<ValidMoveIndicator>>,
+    rows: Query<(Entity, &CapturedTrayRow)>,
+    mut diff_query: Query<&mut Text, With<MaterialDiffText>>,
+    mut last_position: Local<Option<u64>>,
 ) {
-    // Remove existing indicators
-    for entity in indicators.iter() {
-        commands.entity(entity).despawn();
+    let hash = game_state.board.zobrist_hash();
+    if *last_position == Some(hash) {
+        return;
     }
+    *last_position = Some(hash);
 
-    // Show valid moves for selected piece
-    if let Ok(piece) = selected_pieces.get_single() {
-        if piece.is_white {  // Only show moves for white pieces during player's turn
-            let valid_moves = game_state.board.get_valid_moves(piece.position);
-            for valid_move in valid_moves {
-                let target_pos = board_position_to_world(valid_move.to, 2.0);
-                commands.spawn((
-                    SpriteBundle {
-                        texture: chess_assets.valid_move.clone(),
-                        transform: Transform::from_translation(target_pos)
-                            .with_scale(Vec3::splat(1.0)),
-                        sprite: Sprite {
-                            custom_size: Some(Vec2::new(SQUARE_SIZE, SQUARE_SIZE)),
-                            anchor: Anchor::Center,
-                            ..default()
-                        },
+    let (by_white, by_black) = captured_pieces(&game_state.start_board(), &game_state.board);
+    for (entity, row) in rows.iter() {
+        let (captured, is_white) = if row.by_white {
+            (&by_white, false)
+        } else {
+            (&by_black, true)
+        };
+
+        commands.entity(entity).despawn_descendants();
+        commands.entity(entity).with_children(|parent| {
+            for &piece_type in captured {
+                parent.spawn(ImageBundle {
+                    style: Style {
+                        width: Val::Px(TRAY_ICON_SIZE),
+                        height: Val::Px(TRAY_ICON_SIZE),
                         ..default()
                     },
-                    ValidMoveIndicator,
-                ));
+                    image: UiImage::new(chess_assets.piece_texture(piece_type, is_white)),
+                    ..default()
+                });
             }
-        }
+        });
     }
-}
 
-fn get_board_position(cursor_position: Option<Vec2>, window: &Window) -> Option<Position> {
-    cursor_position.map(|cursor| {
-        let window_size = Vec2::new(window.width(), window.height());
-        let board_size = 8.0 * SQUARE_SIZE;
-        
-        // Center the board in the window
-        let board_start = (window_size - Vec2::splat(board_size)) / 2.0;
-        
-        // Calculate relative position on board
-        let relative_pos = cursor - board_start;
-        
-        // Convert to file and rank (1-based)
-        let file = (relative_pos.x / SQUARE_SIZE).floor() as u8 + 1;
-        // Calculate rank from bottom (rank 1) to top (rank 8)
-        let rank = (8.0 - (relative_pos.y / SQUARE_SIZE).floor()) as u8;
-        
-        // Clamp values to valid range
-        let file = file.clamp(1, 8);
-        let rank = rank.clamp(1, 8);
-        
-        Position { file, rank }
-    })
+    if let Ok(mut text) = diff_query.get_single_mut() {
+        let diff: i32 = game_state.board.piece_map().values()
+            .map(|p| {
+                let value = material_value(p.piece_type);
+                if p.color == ChessColor::White { value } else { -value }
+            })
+            .sum();
+        text.sections[0].value = match diff {
+            0 => String::new(),
+            d if d > 0 => format!("White +{}", d),
+            d => format!("Black +{}", -d),
+        };
+    }
 }
 
-fn board_position_to_world(pos: Position, z: f32) -> Vec3 {
-    Vec3::new(
-        ((pos.file as f32 - 1.0) - 3.5) * SQUARE_SIZE,
-        ((pos.rank as f32 - 1.0) - 3.5) * SQUARE_SIZE,
-        z,
-    )
-}
+// Pieces captured by White and by Black, found by replaying the game from its start position.
+// Sorted most valuable first.
+fn captured_pieces(start: &Board, board: &Board) -> (Vec<ChessPieceType>, Vec<ChessPieceType>) {
+    let mut replay = start.clone();
+    let mut by_white = Vec::new();
+    let mut by_black = Vec::new();
 
-fn update_piece_movement(
-    mut commands: Commands,
-    time: Res<Time>,
-    mut query: Query<(Entity, &mut Transform, &MovingPiece)>,
-) {
-    for (entity, mut transform, moving) in query.iter_mut() {
-        let direction = (moving.target_position - transform.translation).normalize();
-        let distance = (moving.target_position - transform.translation).length();
-        
-        if distance < 1.0 {
-            // Snap to final position when close enough
-            transform.translation = moving.target_position;
-            commands.entity(entity).remove::<MovingPiece>();
-        } else {
-            // Smooth movement
-            let movement = direction * moving.speed * time.delta_seconds();
-            // Prevent overshooting
-            if movement.length() > distance {
-                transform.translation = moving.target_position;
-                commands.entity(entity).remove::<MovingPiece>();
-            } else {
-                transform.translation += movement;
+    for &mv in board.move_history() {
+        let mover = replay.current_turn();
+        let Ok(captured) = replay.make_move_capturing(mv) else {
+            break;
+        };
+        if let Some((_, piece)) = captured {
+            match mover {
+                ChessColor::White => by_white.push(piece.piece_type),
+                ChessColor::Black => by_black.push(piece.piece_type),
             }
         }
     }
-}
 
-fn move_piece(
-    commands: &mut Commands,
-    piece_entity: Entity,
-    piece: &mut Piece,
-    to: Position,
-) {
-    // Update the piece's position immediately
-    piece.position = to;
-    
-    // Calculate the target position in world coordinates
-    let target_pos = board_position_to_world(to, 2.0);
+    by_white.sort_by_key(|&p| -material_value(p));
+    by_black.sort_by_key(|&p| -material_value(p));
+    (by_white, by_black)
+}
 
-    // Add the MovingPiece component to handle smooth movement
-    commands.entity(piece_entity).insert(MovingPiece {
-        target_position: target_pos,
-        speed: 500.0,
-    });
+// Standard material values in pawns
+fn material_value(piece_type: ChessPieceType) -> i32 {
+    match piece_type {
+        ChessPieceType::Pawn => 1,
+        ChessPieceType::Knight | ChessPieceType::Bishop => 3,
+        ChessPieceType::Rook => 5,
+        ChessPieceType::Queen => 9,
+        ChessPieceType::King => 0,
+    }
 }
 
-fn update_game_status(
+fn update_pv_text(
+    mut pv_query: Query<&mut Text, With<PvText>>,
     game_state: Res<GameState>,
-    turn: Res<State<Turn>>,
-    mut query: Query<&mut Text, With<GameStatusText>>,
 ) {
-    if let Ok(mut text) = query.get_single_mut() {
-        let status = if game_state.board.is_checkmate() {
-            if *turn.get() == Turn::Player {
-                "Checkmate - Black wins!"
-            } else {
-                "Checkmate - White wins!"
-            }
-        } else if game_state.board.is_stalemate() {
-            "Stalemate - Draw!"
+    if let Ok(mut text) = pv_query.get_single_mut() {
+        text.sections[0].value = if game_state.ai_move_source == Some(MoveSource::Book) {
+            "Book move".to_string()
+        } else if game_state.expected_line.is_empty() {
+            String::new()
         } else {
-            match *turn.get() {
-                Turn::Player => "White's Turn",
-                Turn::AI => "Black's Turn",
-            }
+            format!("Engine expects: {}", game_state.expected_line.join(" "))
         };
-        text.sections[0].value = status.to_string();
     }
 }
 
-fn handle_new_game_button(
-    mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor),
-        (Changed<Interaction>, With<MenuButton>),
-    >,
-    mut game_state: ResMut<GameState>,
-    mut commands: Commands,
-    pieces: Query<Entity, With<Piece>>,
-    mut turn_state: ResMut<NextState<Turn>>,
-    chess_assets: Res<ChessAssets>,
+fn update_think_info_text(
+    mut think_query: Query<&mut Text, With<ThinkInfoText>>,
+    game_state: Res<GameState>,
 ) {
-    for (interaction, mut color) in interaction_query.iter_mut() {
-        match *interaction {
-            Interaction::Pressed => {
-                // Reset game state
-                game_state.board = Board::new();
-                game_state.selected_square = None;
-                game_state.valid_moves.clear();
-                game_state.ai_thinking = false;
-                game_state.game_end_state = GameEndState::Ongoing;
-
-                // Remove all pieces
-                for entity in pieces.iter() {
-                    commands.entity(entity).despawn();
-                }
-
-                // Spawn new pieces
-                let board_size = 8.0;
-                let board_offset = Vec3::new(
-                    -board_size * SQUARE_SIZE / 2.0,
-                    -board_size * SQUARE_SIZE / 2.0,
-                    0.0
-                );
-                spawn_initial_pieces(&mut commands, board_offset, &chess_assets);
-
-                // Reset turn to player
-                turn_state.set(Turn::Player);
+    if !game_state.is_changed() {
+        return;
+    }
+    let Ok(mut text) = think_query.get_single_mut() else {
+        return;
+    };
 
-                *color = Color::rgb(0.4, 0.4, 0.4).into();
-            }
-            Interaction::Hovered => {
-                *color = Color::rgb(0.5, 0.5, 0.5).into();
-            }
-            Interaction::None => {
-                *color = Color::rgb(0.4, 0.4, 0.4).into();
-            }
+    let side = match game_state.ai_color {
+        ChessColor::White => "White",
+        ChessColor::Black => "Black",
+    };
+    let info = match (game_state.ai_move_source, game_state.ai.last_search()) {
+        (None, _) => None,
+        (Some(MoveSource::Book), _) => Some("book move".to_string()),
+        (Some(MoveSource::Random), _) => Some("random move".to_string()),
+        (Some(MoveSource::Fallback), _) => Some("fallback move".to_string()),
+        (Some(_), Some(result)) if result.from_tt => {
+            Some(format!("d{} from table", result.depth))
         }
-    }
+        (Some(_), Some(result)) => {
+            Some(format!("d{} in {:.1}s", result.depth, result.time.as_secs_f32()))
+        }
+        (Some(_), None) => None,
+    };
+    text.sections[0].value = match info {
+        Some(info) => format!("{}: {}", side, info),
+        None => String::new(),
+    };
 }
 
-fn update_last_move(
-    mut last_move_query: Query<&mut Text, With<LastMoveText>>,
-    game_state: Res<GameState>,
-) {
-    if let Ok(mut text) = last_move_query.get_single_mut() {
-        if let Some(last_move) = game_state.board.last_move() {
-            let from_square = format!("{}{}", 
-                (b'a' + (last_move.from.file - 1)) as char,
-                last_move.from.rank
-            );
-            let to_square = format!("{}{}", 
-                (b'a' + (last_move.to.file - 1)) as char,
-                last_move.to.rank
-            );
-            text.sections[0].value = format!("Last move: {} → {}", from_square, to_square);
+// Replays a line of moves from `board`, converting up to `max_moves` of them to SAN
+fn pv_to_san(board: &Board, pv: &[Move], max_moves: usize) -> Vec<String> {
+    let mut board = board.clone();
+    let mut line = Vec::new();
+    for &mv in pv.iter().take(max_moves) {
+        let san = mv.to_san(&board);
+        if board.make_move(mv).is_err() {
+            break;
         }
+        line.push(san);
     }
+    line
 }
 
 fn update_evaluation_text(
@@ -1004,7 +4714,7 @@ fn update_evaluation_text(
     mut query: Query<&mut Text, With<EvaluationText>>,
 ) {
     if let Ok(mut text) = query.get_single_mut() {
-        let evaluation = chess_engine::evaluation::evaluate_position(&game_state.board);
+        let evaluation = game_state.ai.evaluate(&game_state.board);
         
         // Convert centipawns to pawns for readability
         let eval_in_pawns = evaluation as f32 / 100.0;
@@ -1030,94 +4740,136 @@ fn update_evaluation_text(
     }
 }
 
+// The one place a finished game's result (and so its winner) is decided:
+// board.result() goes by the side to move, which is the side that's mated
 fn check_game_end(
     mut game_state: ResMut<GameState>,
 ) {
     // Only check if the game is still ongoing
-    if game_state.game_end_state != GameEndState::Ongoing {
-        return;
-    }
-
-    let current_turn = game_state.board.current_turn();
-    
-    // Check for checkmate
-    if game_state.board.is_checkmate() {
-        // The winner is the opposite color of current turn
-        let winner = match current_turn {
-            ChessColor::White => ChessColor::Black,
-            ChessColor::Black => ChessColor::White,
-        };
-        game_state.game_end_state = GameEndState::Checkmate(winner);
-        return;
-    }
-
-    // Check for stalemate
-    if game_state.board.is_stalemate() {
-        game_state.game_end_state = GameEndState::Stalemate;
+    if game_state.game_result.is_some() {
         return;
     }
 
-    // Check for insufficient material
-    if is_insufficient_material(&game_state.board) {
-        game_state.game_end_state = GameEndState::InsufficientMaterial;
-        return;
-    }
+    game_state.game_result = game_state.board.result();
 }
 
 fn update_game_end_overlay(
     mut commands: Commands,
     game_state: Res<GameState>,
-    query: Query<Entity, With<GameEndOverlay>>,
+    query: Query<Entity, GameEndVisuals>,
+    chess_assets: Res<ChessAssets>,
+    layout: Res<BoardLayout>,
 ) {
-    match game_state.game_end_state {
-        GameEndState::Ongoing => {
-            // Remove overlay if it exists
+    match game_state.game_result {
+        None => {
+            // Remove overlay (and glow) if it exists
             for entity in query.iter() {
                 commands.entity(entity).despawn_recursive();
             }
         }
-        _ => {
+        Some(result) => {
             // Only spawn overlay if it doesn't exist
             if query.is_empty() {
-                spawn_game_end_overlay(&mut commands, &game_state);
+                let message = game_end_message(result, &game_state.board);
+                spawn_game_end_overlay(&mut commands, &message);
+                announce_game_end(&mut commands, result, game_state.player_color(), &chess_assets, &layout);
             }
         }
     }
 }
 
-fn spawn_game_end_overlay(commands: &mut Commands, game_state: &GameState) {
-    let message = match game_state.game_end_state {
-        GameEndState::Checkmate(winner) => {
-            match winner {
-                ChessColor::White => "Checkmate! White wins!",
-                ChessColor::Black => "Checkmate! Black wins!",
-            }
-        }
-        GameEndState::Stalemate => "Game Over - Stalemate!",
-        GameEndState::InsufficientMaterial => "Game Over - Insufficient Material!",
-        GameEndState::Ongoing => unreachable!(),
+// Plays the ending's sound as the overlay appears, from the player's side of
+// the board: a fanfare and a gold glow around the board for a win, a falling
+// figure for a loss and something in between for a draw
+fn announce_game_end(
+    commands: &mut Commands,
+    result: GameResult,
+    player: ChessColor,
+    assets: &ChessAssets,
+    layout: &BoardLayout,
+) {
+    let player_won = match result {
+        GameResult::WhiteWins => Some(player == ChessColor::White),
+        GameResult::BlackWins => Some(player == ChessColor::Black),
+        GameResult::Draw(_) => None,
+    };
+    let sound = match player_won {
+        Some(true) => assets.win_sound.clone(),
+        Some(false) => assets.loss_sound.clone(),
+        None => assets.draw_sound.clone(),
     };
+    commands.spawn(AudioBundle {
+        source: sound,
+        settings: PlaybackSettings::DESPAWN,
+    });
 
-    commands
-        .spawn((
-            NodeBundle {
-                style: Style {
-                    position_type: PositionType::Absolute,
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Center,
+    if player_won == Some(true) {
+        // Behind the board's frame, so only the rim past it shows
+        let frame_size = layout.board_size() + 2.0 * BOARD_BORDER;
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(1.0, 0.8, 0.2, 0.6),
+                    custom_size: Some(Vec2::splat(frame_size + 2.0 * GAME_END_GLOW_WIDTH)),
                     ..default()
                 },
-                background_color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+                transform: Transform::from_translation(layout.offset - Vec3::Z),
                 ..default()
             },
-            GameEndOverlay,
-        ))
+            GameEndGlow,
+        ));
+    }
+}
+
+// Headline for a finished game. Every result and draw reason is matched
+// explicitly, so a new one won't compile until it has a message here.
+fn game_end_message(result: GameResult, board: &Board) -> String {
+    // Wins can also be awarded without a mate on the board
+    let win = |winner: &str| {
+        if board.is_checkmate() {
+            format!("Checkmate! {} wins!", winner)
+        } else {
+            format!("{} wins!", winner)
+        }
+    };
+    match result {
+        GameResult::WhiteWins => win("White"),
+        GameResult::BlackWins => win("Black"),
+        GameResult::Draw(reason) => match reason {
+            DrawReason::Stalemate => "Game Over - Stalemate!",
+            DrawReason::InsufficientMaterial => "Game Over - Insufficient Material!",
+            DrawReason::ThreefoldRepetition => "Draw - Threefold Repetition!",
+            DrawReason::FiftyMoveRule => "Draw - Fifty-Move Rule!",
+            DrawReason::SeventyFiveMoveRule => "Game Over - Seventy-Five-Move Rule!",
+            DrawReason::Adjudicated => "Game Over - Draw!",
+        }.to_string(),
+    }
+}
+
+// Darkened full-window backdrop with its contents centered
+fn overlay_node() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+        ..default()
+    }
+}
+
+fn spawn_game_end_overlay(commands: &mut Commands, message: &str) {
+
+    commands
+        .spawn((overlay_node(), GameEndOverlay))
         .with_children(|parent| {
             parent.spawn(
                 TextBundle::from_section(
-                    message,
+                    message.to_string(),
                     TextStyle {
                         font_size: 40.0,
                         color: Color::WHITE,
@@ -1156,50 +4908,57 @@ fn spawn_game_end_overlay(commands: &mut Commands, game_state: &GameState) {
         });
 }
 
-fn is_insufficient_material(board: &Board) -> bool {
-    let mut white_pieces = Vec::new();
-    let mut black_pieces = Vec::new();
+fn spawn_confirm_dialog(commands: &mut Commands, question: &str) {
+    commands
+        .spawn((overlay_node(), ConfirmOverlay))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    question.to_string(),
+                    TextStyle {
+                        font_size: 30.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                }),
+            );
 
-    // Collect all pieces
-    for rank in 1..=8 {
-        for file in 1..=8 {
-            if let Some(piece) = board.get_piece(Position { rank, file }) {
-                match piece.color {
-                    ChessColor::White => white_pieces.push(piece.piece_type),
-                    ChessColor::Black => black_pieces.push(piece.piece_type),
-                }
+            for (label, yes) in [("Yes", true), ("No", false)] {
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            margin: UiRect::all(Val::Px(8.0)),
+                            padding: UiRect::all(Val::Px(8.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.4, 0.4, 0.4).into(),
+                        ..default()
+                    },
+                    ConfirmAnswerButton { yes },
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        label,
+                        TextStyle {
+                            font_size: 30.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
             }
-        }
-    }
-
-    // King vs King
-    if white_pieces.len() == 1 && black_pieces.len() == 1 {
-        return true;
-    }
-
-    // King and Bishop vs King or King and Knight vs King
-    if (white_pieces.len() == 2 && black_pieces.len() == 1) ||
-       (white_pieces.len() == 1 && black_pieces.len() == 2) {
-        let longer_side = if white_pieces.len() > black_pieces.len() { &white_pieces } else { &black_pieces };
-        if longer_side.contains(&ChessPieceType::Bishop) || longer_side.contains(&ChessPieceType::Knight) {
-            return true;
-        }
-    }
-
-    // King and Bishop vs King and Bishop (same color bishops)
-    if white_pieces.len() == 2 && black_pieces.len() == 2 {
-        if white_pieces.contains(&ChessPieceType::Bishop) && black_pieces.contains(&ChessPieceType::Bishop) {
-            return true;
-        }
-    }
-
-    false
+        });
 }
 
 fn spawn_promotion_dialog(
     commands: &mut Commands,
     chess_assets: &ChessAssets,
     is_white: bool,
+    layout: &BoardLayout,
 ) {
     commands.spawn((
         NodeBundle {
@@ -1242,8 +5001,8 @@ fn spawn_promotion_dialog(
                 parent.spawn((
                     ButtonBundle {
                         style: Style {
-                            width: Val::Px(SQUARE_SIZE),
-                            height: Val::Px(SQUARE_SIZE),
+                            width: Val::Px(layout.square_size),
+                            height: Val::Px(layout.square_size),
                             margin: UiRect::all(Val::Px(5.0)),
                             ..default()
                         },
@@ -1255,8 +5014,8 @@ fn spawn_promotion_dialog(
                 .with_children(|parent| {
                     parent.spawn(ImageBundle {
                         style: Style {
-                            width: Val::Px(SQUARE_SIZE - 10.0),
-                            height: Val::Px(SQUARE_SIZE - 10.0),
+                            width: Val::Px(layout.square_size - 10.0),
+                            height: Val::Px(layout.square_size - 10.0),
                             margin: UiRect::all(Val::Px(5.0)),
                             ..default()
                         },
@@ -1269,6 +5028,7 @@ fn spawn_promotion_dialog(
     });
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_promotion_selection(
     mut commands: Commands,
     mut game_state: ResMut<GameState>,
@@ -1280,6 +5040,8 @@ fn handle_promotion_selection(
     dialog_query: Query<Entity, With<PromotionDialog>>,
     mut pieces: Query<(Entity, &mut Piece, &mut Transform)>,
     mut turn_state: ResMut<NextState<Turn>>,
+    puzzles: Res<PuzzleState>,
+    layout: Res<BoardLayout>,
 ) {
     let mut promotion_to_handle = None;
     
@@ -1296,41 +5058,16 @@ fn handle_promotion_selection(
     if let Some((from, to, piece_type)) = promotion_to_handle {
         let promotion_move = Move::with_promotion(from, to, piece_type);
 
-        if game_state.board.make_move(promotion_move).is_ok() {
-            // Remove the old pawn
-            for (entity, piece, _) in pieces.iter() {
-                if piece.position == from {
-                    commands.entity(entity).despawn();
-                    break;
-                }
-            }
-
-            // Spawn the promoted piece
-            let world_pos = board_position_to_world(to, 2.0);
-            commands.spawn((
-                SpriteBundle {
-                    texture: match piece_type {
-                        ChessPieceType::Queen => chess_assets.white_queen.clone(),
-                        ChessPieceType::Rook => chess_assets.white_rook.clone(),
-                        ChessPieceType::Bishop => chess_assets.white_bishop.clone(),
-                        ChessPieceType::Knight => chess_assets.white_knight.clone(),
-                        _ => unreachable!(),
-                    },
-                    transform: Transform::from_translation(world_pos)
-                        .with_scale(Vec3::splat(1.0)),
-                    sprite: Sprite {
-                        custom_size: Some(Vec2::new(SQUARE_SIZE - 10.0, SQUARE_SIZE - 10.0)),
-                        ..default()
-                    },
-                    ..default()
-                },
-                Piece {
-                    piece_type,
-                    is_white: true,
-                    position: to,
-                },
-            ));
-
+        if play_player_move(
+            &mut commands,
+            &mut game_state,
+            &mut pieces,
+            promotion_move,
+            &layout,
+            &chess_assets,
+            &puzzles,
+            &mut turn_state,
+        ) {
             // Remove the promotion dialog
             for entity in dialog_query.iter() {
                 commands.entity(entity).despawn_recursive();
@@ -1338,9 +5075,42 @@ fn handle_promotion_selection(
 
             // Clear pending promotion
             game_state.pending_promotion = None;
-
-            // Switch to AI's turn
-            turn_state.set(Turn::AI);
         }
     }
-}
\ No newline at end of file
+}
+
+// Takes the promotion dialog down once nothing is waiting on it any more,
+// e.g. after a new game was started while it was open
+fn close_abandoned_promotion_dialog(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    dialog_query: Query<Entity, With<PromotionDialog>>,
+) {
+    if game_state.pending_promotion.is_some() {
+        return;
+    }
+    for entity in dialog_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A new game hands the first move to the AI when it plays White
+    #[test]
+    fn ai_playing_white_moves_first() {
+        let mut game_state = GameState { ai_color: ChessColor::White, ..Default::default() };
+        game_state.reset_for_new_position(Board::new(), None);
+        assert_eq!(game_state.turn(), Turn::AI);
+
+        let opening = Move::from_uci("e2e4", &game_state.board).unwrap();
+        game_state.board.make_move(opening).unwrap();
+        assert_eq!(game_state.turn(), Turn::Player);
+
+        game_state.ai_color = ChessColor::Black;
+        game_state.reset_for_new_position(Board::new(), None);
+        assert_eq!(game_state.turn(), Turn::Player);
+    }
+}